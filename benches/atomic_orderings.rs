@@ -0,0 +1,66 @@
+//! Compares the cost of the decoder's per-edge atomic traffic (load state and direction,
+//! store the new ones, bump the watchdog counter) with `SeqCst` against the relaxed
+//! orderings used by `rotary_encoder`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
+
+/// Clockwise sequence of states, as produced by alternating CLK and DT edges
+const STATES: [u8; 4] = [0b01, 0b11, 0b10, 0b00];
+
+struct Decoder {
+    state: AtomicU8,
+    direction: AtomicU8,
+    edges: AtomicU32,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Self {
+            state: AtomicU8::new(0),
+            direction: AtomicU8::new(0),
+            edges: AtomicU32::new(0),
+        }
+    }
+
+    fn edge(&self, new_state: u8, load: Ordering, store: Ordering, counter: Ordering) {
+        let old_state = self.state.load(load);
+        let old_direction = self.direction.load(load);
+        self.state.store(black_box(new_state), store);
+        self.direction
+            .store(black_box(old_direction ^ old_state), store);
+        self.edges.fetch_add(1, counter);
+    }
+}
+
+fn churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("edge_atomics");
+    for (label, load, store, counter) in [
+        (
+            "seq_cst",
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ),
+        (
+            "acquire_release",
+            Ordering::Acquire,
+            Ordering::Release,
+            Ordering::Relaxed,
+        ),
+    ] {
+        let decoder = Decoder::new();
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                for state in STATES {
+                    decoder.edge(state, load, store, counter);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, churn);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! Acceleration of rotary encoders: fast spins move further than slow ones.
+//!
+//! An [`AccelProfile`] maps the time since the previous detent to a number of steps the
+//! detent counts for. The steps are applied to the position (see
+//! [`Encoder::position`](crate::rotary_encoder::Encoder::position)) and passed to delta
+//! callbacks (see [`EncoderBuilder::delta_callback`](crate::rotary_encoder::EncoderBuilder::delta_callback)),
+//! so a volume knob can be turned a few units at a time or swept across its range.
+
+use std::time::Duration;
+
+/// Detents further apart than this always count as a single step
+pub const ACCEL_SLOW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Mapping of the time between two detents to the steps the second one counts for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccelProfile {
+    /// Every detent is one step
+    #[default]
+    None,
+    /// Steps grow with the rotation speed: `100ms / interval`, between 1 and 10.
+    /// Detents 50ms apart count twice, 20ms apart five times.
+    Linear,
+    /// Steps double for every 20ms the interval is shorter than 100ms:
+    /// `2 ^ ((100ms - interval) / 20ms)`, between 1 and 32.
+    /// Detents 80ms apart count twice, 40ms apart eight times.
+    Exponential,
+}
+
+impl AccelProfile {
+    /// Steps a detent counts for, `interval` being the time since the previous detent.
+    /// The first detent (`None`) and slow ones count as one step.
+    pub fn steps(self, interval: Option<Duration>) -> u8 {
+        let Some(interval) = interval.filter(|interval| *interval < ACCEL_SLOW_INTERVAL) else {
+            return 1;
+        };
+        match self {
+            AccelProfile::None => 1,
+            AccelProfile::Linear => {
+                let steps = ACCEL_SLOW_INTERVAL.as_micros() / interval.as_micros().max(1);
+                steps.clamp(1, 10) as u8
+            }
+            AccelProfile::Exponential => {
+                let faster = ACCEL_SLOW_INTERVAL - interval;
+                let doublings = faster.as_millis() / 20;
+                1 << doublings.min(5)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps_at(profile: AccelProfile, intervals_ms: &[u64]) -> Vec<u8> {
+        intervals_ms
+            .iter()
+            .map(|&ms| profile.steps(Some(Duration::from_millis(ms))))
+            .collect()
+    }
+
+    const INTERVALS_MS: [u64; 8] = [500, 100, 80, 50, 40, 20, 10, 0];
+
+    #[test]
+    fn test_no_acceleration() {
+        assert_eq!(steps_at(AccelProfile::None, &INTERVALS_MS), vec![1; 8]);
+    }
+
+    #[test]
+    fn test_linear_acceleration() {
+        assert_eq!(
+            steps_at(AccelProfile::Linear, &INTERVALS_MS),
+            vec![1, 1, 1, 2, 2, 5, 10, 10]
+        );
+    }
+
+    #[test]
+    fn test_exponential_acceleration() {
+        assert_eq!(
+            steps_at(AccelProfile::Exponential, &INTERVALS_MS),
+            vec![1, 1, 2, 4, 8, 16, 16, 32]
+        );
+    }
+
+    #[test]
+    fn test_first_detent_is_one_step() {
+        for profile in [
+            AccelProfile::None,
+            AccelProfile::Linear,
+            AccelProfile::Exponential,
+        ] {
+            assert_eq!(profile.steps(None), 1);
+        }
+    }
+}
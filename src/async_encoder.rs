@@ -0,0 +1,89 @@
+//! Async interface for rotary encoders, bridging detents into a tokio channel.
+//!
+//! Detents are decoded in rppal's interrupt thread, which cannot `.await`.
+//! Events are therefore handed over with [`Sender::try_send`]: when the channel is full
+//! the event is dropped instead of stalling the interrupt thread (which would make the
+//! decoder miss edges and produce invalid transitions).
+//! Awaiting on a full channel is not an option here; if dropped events are a concern,
+//! choose a larger `capacity` or consume the receiver in a dedicated task.
+
+use crate::error::EncoderError;
+use crate::rotary_encoder::{ClkPin, DtPin, Encoder, EncoderBuilder, RotaryEvent, SwPin};
+
+use log::debug;
+use rppal::gpio::Gpio;
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+impl Encoder {
+    /// Create a new rotary encoder delivering its detents through an async channel
+    /// # Arguments
+    /// * `encoder_name` - Name of the encoder
+    /// * `encoder_name_shifted` - Name of the encoder when pressed
+    /// * `gpio` - Gpio instance to use for the encoder
+    /// * `dt_pin` - GPIO pin number for data (DT) encoder signal
+    /// * `clk_pin` - GPIO pin number for clock (CLK) encoder signal
+    /// * `sw_pin` - Optional GPIO pin number for the built-in switch used for shifting
+    /// * `capacity` - Number of events buffered before new events are dropped
+    ///
+    /// See [`EncoderBuilder::spawn`] for further options.
+    pub fn spawn(
+        encoder_name: &str,
+        encoder_name_shifted: Option<&str>,
+        gpio: &Gpio,
+        dt_pin: DtPin,
+        clk_pin: ClkPin,
+        sw_pin: Option<SwPin>,
+        capacity: usize,
+    ) -> Result<(Self, Receiver<RotaryEvent>), EncoderError> {
+        let mut builder = Self::builder()
+            .name(encoder_name)
+            .dt_pin(dt_pin)
+            .clk_pin(clk_pin);
+        if let Some(name_shifted) = encoder_name_shifted {
+            builder = builder.shifted_name(name_shifted);
+        }
+        if let Some(sw_pin) = sw_pin {
+            builder = builder.switch_pin(sw_pin);
+        }
+        builder.spawn(gpio, capacity)
+    }
+}
+
+impl EncoderBuilder {
+    /// Claim the GPIO pins and deliver the encoder's events through an async channel.
+    /// Setting a callback is optional, if set it is called in addition.
+    /// # Arguments
+    /// * `gpio` - Gpio instance to use for the encoder
+    /// * `capacity` - Number of events buffered before new events are dropped
+    ///
+    /// Besides detents, [`RotaryEvent::Fault`] is sent when the encoder stops producing detents.
+    ///
+    /// The returned `Encoder` must be kept alive for as long as events should be received.
+    pub fn spawn(
+        self,
+        gpio: &Gpio,
+        capacity: usize,
+    ) -> Result<(Encoder, Receiver<RotaryEvent>), EncoderError> {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let log_target = self.target().to_owned();
+        let encoder = self.build_with_events(
+            gpio,
+            Arc::new(move |event| forward(&sender, event, &log_target)),
+        )?;
+        Ok((encoder, receiver))
+    }
+}
+
+fn forward(sender: &Sender<RotaryEvent>, event: RotaryEvent, log_target: &str) {
+    match sender.try_send(event) {
+        Ok(()) => {}
+        Err(TrySendError::Full(event)) => {
+            debug!(target: log_target, "Channel full, dropping {:?}", event)
+        }
+        Err(TrySendError::Closed(event)) => {
+            debug!(target: log_target, "Receiver dropped, discarding {:?}", event)
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Coalescing of rapid same-direction detents into [`RotaryEvent::Batched`].
+//!
+//! Detents are collected until no further detent arrives within the window, then a single
+//! batch is flushed by a small timer thread. A direction reversal, or any other event,
+//! flushes the pending batch immediately so events stay in order.
+#![cfg_attr(not(feature = "async"), allow(dead_code))]
+
+use crate::clock::Clock;
+use crate::rotary_encoder::{Direction, EventSink, RotaryEvent};
+use crate::util::spawn_named;
+
+use log::trace;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// Detents collected but not yet flushed
+struct Batch {
+    name: String,
+    direction: Direction,
+    steps: u32,
+    last: Instant,
+}
+
+pub(crate) struct Coalescer {
+    window: Duration,
+    pending: Mutex<Option<Batch>>,
+    wake: Condvar,
+    sink: EventSink,
+    /// Time source of the window
+    clock: Arc<dyn Clock>,
+    log_target: String,
+}
+
+impl Coalescer {
+    /// Wrap `sink` so that detents are batched, returning the sink to feed events into.
+    /// The timer thread, named after the encoder `name`, ends once the returned sink is dropped.
+    /// Flushes are logged under the encoder's `log_target`.
+    pub(crate) fn spawn(
+        name: &str,
+        window: Duration,
+        log_target: &str,
+        clock: Arc<dyn Clock>,
+        sink: EventSink,
+    ) -> EventSink {
+        let coalescer = Arc::new(Coalescer::new(window, log_target, clock, sink));
+        let weak = Arc::downgrade(&coalescer);
+        spawn_named(name, move || Coalescer::run(weak));
+        Arc::new(move |event| coalescer.push(event))
+    }
+
+    fn new(window: Duration, log_target: &str, clock: Arc<dyn Clock>, sink: EventSink) -> Self {
+        Self {
+            window,
+            pending: Mutex::new(None),
+            wake: Condvar::new(),
+            sink,
+            clock,
+            log_target: log_target.to_owned(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Batch>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, event: RotaryEvent) {
+        let mut pending = self.lock();
+        let RotaryEvent::Turned {
+            name, direction, ..
+        } = event
+        else {
+            self.flush(&mut pending);
+            (self.sink)(event);
+            return;
+        };
+
+        match pending.as_mut() {
+            Some(batch) if batch.name == name && batch.direction == direction => {
+                batch.steps += 1;
+                batch.last = self.clock.now();
+            }
+            _ => {
+                self.flush(&mut pending);
+                *pending = Some(Batch {
+                    name,
+                    direction,
+                    steps: 1,
+                    last: self.clock.now(),
+                });
+                self.wake.notify_one();
+            }
+        }
+    }
+
+    fn flush(&self, pending: &mut Option<Batch>) {
+        if let Some(batch) = pending.take() {
+            trace!(
+                target: &self.log_target,
+                "Flushing {} detents {:?} of {}",
+                batch.steps, batch.direction, batch.name
+            );
+            (self.sink)(RotaryEvent::Batched {
+                name: batch.name,
+                direction: batch.direction,
+                steps: batch.steps,
+            });
+        }
+    }
+
+    /// Flush the pending batch if it has been quiet for the window by now, returning the
+    /// locked batch and how long to wait until the next check
+    fn expire(&self) -> (MutexGuard<'_, Option<Batch>>, Duration) {
+        let mut pending = self.lock();
+        let quiet = match pending.as_ref() {
+            Some(batch) => self.clock.now().saturating_duration_since(batch.last),
+            // wake up regularly to notice when the encoder is dropped
+            None => return (pending, self.window),
+        };
+        if quiet < self.window {
+            return (pending, self.window - quiet);
+        }
+        self.flush(&mut pending);
+        (pending, self.window)
+    }
+
+    /// Timer loop flushing batches that have been quiet for the window
+    fn run(weak: Weak<Coalescer>) {
+        while let Some(coalescer) = weak.upgrade() {
+            let (pending, timeout) = coalescer.expire();
+            drop(
+                coalescer
+                    .wake
+                    .wait_timeout(pending, timeout)
+                    .unwrap_or_else(|e| e.into_inner()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn turned(direction: Direction) -> RotaryEvent {
+        RotaryEvent::Turned {
+            name: "knob".to_owned(),
+            direction,
+            timestamp: Duration::ZERO,
+        }
+    }
+
+    fn batched(direction: Direction, steps: u32) -> RotaryEvent {
+        RotaryEvent::Batched {
+            name: "knob".to_owned(),
+            direction,
+            steps,
+        }
+    }
+
+    #[test]
+    fn test_coalesce_detents() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = Arc::clone(&events);
+        let clock = Arc::new(MockClock::new());
+        let coalescer = Coalescer::new(
+            Duration::from_millis(50),
+            module_path!(),
+            Arc::clone(&clock) as Arc<dyn Clock>,
+            Arc::new(move |event| events_clone.lock().unwrap().push(event)),
+        );
+
+        for _ in 0..3 {
+            coalescer.push(turned(Direction::Clockwise));
+        }
+        // the reversal flushes the clockwise batch right away
+        coalescer.push(turned(Direction::CounterClockwise));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![batched(Direction::Clockwise, 3)]
+        );
+
+        clock.advance(Duration::from_millis(30));
+        let (pending, timeout) = coalescer.expire();
+        drop(pending);
+        assert_eq!(timeout, Duration::from_millis(20));
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        clock.advance(Duration::from_millis(20));
+        drop(coalescer.expire());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                batched(Direction::Clockwise, 3),
+                batched(Direction::CounterClockwise, 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Threaded dispatch of detents, see [`Dispatch::Threaded`].
+//!
+//! The interrupt handler only queues the completed detent; a worker thread takes it from
+//! the bounded queue and runs the callbacks. The worker holds a weak reference to the
+//! decoder and ends once the encoder is dropped, which closes the queue.
+
+use crate::rotary_encoder::{Detent, Direction, Shared};
+use crate::util::spawn_named;
+
+use log::{debug, trace};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+#[cfg(doc)]
+use crate::rotary_encoder::Dispatch;
+
+/// Number of detents queued for the worker by default
+pub const DISPATCH_QUEUE_CAPACITY: usize = 64;
+
+/// What happens to a detent completed while the dispatch queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Drop the new detent, keeping the queued ones
+    #[default]
+    DropNewest,
+    /// Discard the oldest queued detent to make room, so the latest rotation is reported
+    DropOldest,
+    /// Wait in the interrupt thread until the worker took a detent. No detent is lost, but no
+    /// edges are decoded while waiting: a callback that never returns stalls the encoder.
+    Block,
+}
+
+/// A detent queued for the worker thread
+pub(crate) struct QueuedDetent {
+    name: String,
+    direction: Direction,
+    timestamp: Duration,
+    pressed: bool,
+    position: i32,
+    steps: u8,
+}
+
+impl From<&Detent<'_>> for QueuedDetent {
+    fn from(detent: &Detent) -> Self {
+        Self {
+            name: detent.name.to_owned(),
+            direction: detent.direction,
+            timestamp: detent.timestamp,
+            pressed: detent.pressed,
+            position: detent.position,
+            steps: detent.steps,
+        }
+    }
+}
+
+struct QueueState {
+    detents: VecDeque<QueuedDetent>,
+    /// Set once the encoder is dropped, ending the worker
+    closed: bool,
+}
+
+/// Bounded queue between the interrupt thread and the worker
+pub(crate) struct DispatchQueue {
+    state: Mutex<QueueState>,
+    /// Signalled when a detent was queued or the queue was closed
+    queued: Condvar,
+    /// Signalled when the worker took a detent or the queue was closed
+    taken: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: AtomicU64,
+    /// Target of the log messages, the encoder's one
+    log_target: String,
+}
+
+impl DispatchQueue {
+    /// Create a queue of `capacity` detents (at least one), logging under `log_target`
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy, log_target: &str) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                detents: VecDeque::with_capacity(capacity.max(1)),
+                closed: false,
+            }),
+            queued: Condvar::new(),
+            taken: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+            dropped: AtomicU64::new(0),
+            log_target: log_target.to_owned(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Detents dropped so far because the queue was full
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn drop_detent(&self, detent: &QueuedDetent) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        debug!(
+            target: &self.log_target,
+            "Dispatch queue of {} full, dropping {} detent",
+            detent.name, detent.direction
+        );
+    }
+
+    /// Queue `detent`, applying the overflow policy if the queue is full
+    pub(crate) fn push(&self, detent: QueuedDetent) {
+        let mut state = self.lock();
+        if state.detents.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropNewest => {
+                    self.drop_detent(&detent);
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    if let Some(oldest) = state.detents.pop_front() {
+                        self.drop_detent(&oldest);
+                    }
+                }
+                OverflowPolicy::Block => {
+                    while state.detents.len() >= self.capacity && !state.closed {
+                        state = self.taken.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                }
+            }
+        }
+        if state.closed {
+            return;
+        }
+        state.detents.push_back(detent);
+        self.queued.notify_one();
+    }
+
+    /// Take the oldest detent, waiting for one; `None` once the queue is closed
+    fn pop(&self) -> Option<QueuedDetent> {
+        let mut state = self.lock();
+        loop {
+            if let Some(detent) = state.detents.pop_front() {
+                self.taken.notify_one();
+                return Some(detent);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.queued.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Stop the worker and release an interrupt thread waiting for room
+    fn close(&self) {
+        self.lock().closed = true;
+        self.queued.notify_all();
+        self.taken.notify_all();
+    }
+}
+
+/// Sending side of the queue owned by the decoder, closing the queue when dropped
+pub(crate) struct Dispatcher(Arc<DispatchQueue>);
+
+impl Dispatcher {
+    pub(crate) fn new(queue: &Arc<DispatchQueue>) -> Self {
+        Self(Arc::clone(queue))
+    }
+
+    /// Queue `detent` for the worker, see [`OverflowPolicy`]
+    pub(crate) fn enqueue(&self, detent: &Detent) {
+        self.0.push(detent.into());
+    }
+
+    /// Detents dropped so far because the queue was full
+    pub(crate) fn dropped(&self) -> u64 {
+        self.0.dropped()
+    }
+}
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// Start the thread running the callbacks of the encoder `name` for every queued detent
+pub(crate) fn spawn_worker(name: &str, shared: Weak<Shared>, queue: Arc<DispatchQueue>) {
+    spawn_named(name, move || {
+        while let Some(queued) = queue.pop() {
+            let Some(shared) = shared.upgrade() else {
+                break;
+            };
+            shared.notify(&Detent {
+                name: &queued.name,
+                direction: queued.direction,
+                timestamp: queued.timestamp,
+                pressed: queued.pressed,
+                position: queued.position,
+                steps: queued.steps,
+            });
+        }
+        trace!(target: &queue.log_target, "Dispatch worker stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn detent(position: i32) -> QueuedDetent {
+        QueuedDetent {
+            name: "knob".to_owned(),
+            direction: Direction::Clockwise,
+            timestamp: Duration::ZERO,
+            pressed: false,
+            position,
+            steps: 1,
+        }
+    }
+
+    fn positions(queue: &DispatchQueue) -> Vec<i32> {
+        queue.lock().detents.iter().map(|d| d.position).collect()
+    }
+
+    #[test]
+    fn test_drop_newest_keeps_queued() {
+        let queue = DispatchQueue::new(2, OverflowPolicy::DropNewest, module_path!());
+        (1..=4).for_each(|position| queue.push(detent(position)));
+        assert_eq!(positions(&queue), vec![1, 2]);
+        assert_eq!(queue.dropped(), 2);
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_latest() {
+        let queue = DispatchQueue::new(2, OverflowPolicy::DropOldest, module_path!());
+        (1..=4).for_each(|position| queue.push(detent(position)));
+        assert_eq!(positions(&queue), vec![3, 4]);
+        assert_eq!(queue.dropped(), 2);
+    }
+
+    #[test]
+    fn test_block_waits_for_worker() {
+        let queue = Arc::new(DispatchQueue::new(1, OverflowPolicy::Block, module_path!()));
+        queue.push(detent(1));
+        let pusher = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || queue.push(detent(2)))
+        };
+        thread::sleep(Duration::from_millis(20));
+        assert!(!pusher.is_finished());
+        assert_eq!(queue.pop().map(|d| d.position), Some(1));
+        pusher.join().unwrap();
+        assert_eq!(positions(&queue), vec![2]);
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn test_close_ends_worker_and_releases_blocked_push() {
+        let queue = Arc::new(DispatchQueue::new(1, OverflowPolicy::Block, module_path!()));
+        queue.push(detent(1));
+        let pusher = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || queue.push(detent(2)))
+        };
+        drop(Dispatcher::new(&queue));
+        pusher.join().unwrap();
+        assert_eq!(queue.pop().map(|d| d.position), Some(1));
+        assert!(queue.pop().is_none());
+    }
+}
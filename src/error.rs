@@ -0,0 +1,98 @@
+//! Error type returned when setting up encoders.
+//!
+//! `EncoderError` implements [`std::error::Error`], so `?` converts it into an
+//! [`anyhow::Error`] for callers that do not need to tell the cases apart.
+
+use thiserror::Error;
+
+/// Reasons why an encoder or [`PiInput`](crate::PiInput) could not be created
+#[derive(Debug, Error)]
+pub enum EncoderError {
+    /// The pin is already claimed elsewhere in the application
+    #[error("GPIO pin {0} is already in use")]
+    PinInUse(u8),
+    /// The pin number does not exist on the board
+    #[error("GPIO pin {pin} out of range 0-{max}")]
+    InvalidPin { pin: u8, max: u8 },
+    /// The same pin was assigned twice within the given definitions
+    #[error("GPIO pin {pin} is used by both {first} and {second}")]
+    DuplicatePin {
+        pin: u8,
+        first: String,
+        second: String,
+    },
+    /// Registering the interrupt handler for a pin failed
+    #[error("Failed to enable callbacks: {0}")]
+    InterruptFailed(#[source] rppal::gpio::Error),
+    /// A selector, gray encoder or matrix was created without any pins
+    #[error("{0} needs at least one pin")]
+    NoPins(String),
+    /// A gray encoder or switch bank was created with more pins than fit its code
+    #[error("{name} uses {count} pins, at most {max} are supported")]
+    TooManyPins {
+        name: String,
+        count: usize,
+        max: usize,
+    },
+    /// A switch was configured with a trigger that reports no edges
+    #[error("Switch trigger {0:?} does not report any edge")]
+    InvalidTrigger(rppal::gpio::Trigger),
+    /// A rotary encoder was given a rest state that is no pin state its decoder supports
+    #[error(
+        "Rest state {0:#04b} is not supported, expected 0b00-0b11 (0b00 with ClkInterruptDtRead)"
+    )]
+    InvalidRestState(u8),
+    /// A GPIO chip other than the Raspberry Pi's main GPIO controller was requested,
+    /// which rppal cannot open
+    #[error("GPIO chip {0} is not supported, only the main GPIO controller can be used")]
+    UnsupportedChip(String),
+    /// A required builder field was not set
+    #[error("{builder} builder: missing required field `{field}`")]
+    MissingField {
+        builder: &'static str,
+        field: &'static str,
+    },
+    /// The SIGINT/SIGTERM handler of [`PiInput::run_blocking`](crate::PiInput::run_blocking)
+    /// could not be installed
+    #[error("Failed to install signal handler: {0}")]
+    SignalHandler(#[source] std::io::Error),
+    /// Any other GPIO error, e.g. when the GPIO peripheral cannot be opened
+    #[error(transparent)]
+    Gpio(rppal::gpio::Error),
+}
+
+impl From<rppal::gpio::Error> for EncoderError {
+    fn from(e: rppal::gpio::Error) -> Self {
+        match e {
+            rppal::gpio::Error::PinUsed(pin) => EncoderError::PinInUse(pin),
+            e => EncoderError::Gpio(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_gpio_error() {
+        assert!(matches!(
+            EncoderError::from(rppal::gpio::Error::PinUsed(17)),
+            EncoderError::PinInUse(17)
+        ));
+        assert!(matches!(
+            EncoderError::from(rppal::gpio::Error::ThreadPanic),
+            EncoderError::Gpio(rppal::gpio::Error::ThreadPanic)
+        ));
+    }
+
+    #[test]
+    fn test_into_anyhow() {
+        let err: anyhow::Error = EncoderError::PinInUse(17).into();
+        assert_eq!(err.to_string(), "GPIO pin 17 is already in use");
+        assert!(matches!(
+            err.downcast_ref::<EncoderError>(),
+            Some(EncoderError::PinInUse(17))
+        ));
+    }
+}
@@ -0,0 +1,290 @@
+//! Gesture detection for switches: taps, double taps, holds and long presses.
+//!
+//! Every press is classified on release by how long the switch was held:
+//!
+//! * up to [`GestureTiming::tap`]: a tap
+//! * longer, up to [`GestureTiming::long_press`]: [`Gesture::Hold`] with the held time
+//! * longer than [`GestureTiming::long_press`]: [`Gesture::LongPress`]
+//!
+//! With a [`GestureTiming::double_tap`] window, a tap is not reported right away: if the
+//! next press starts within the window after the release and is a tap as well, a single
+//! [`Gesture::DoubleTap`] is reported instead. Otherwise [`Gesture::Tap`] follows once the
+//! window has passed (from a small timer thread), or right before the gesture of a second
+//! press that turned into a hold. So the window adds latency to every single tap; keep it
+//! short, and keep `tap` well below `long_press`, as `Hold` is only reported in between.
+
+use crate::clock::{Clock, SystemClock};
+use crate::util::{catch_panic, spawn_named};
+
+use log::trace;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// Gesture recognised on a switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Gesture {
+    /// A short press
+    Tap,
+    /// Two taps in quick succession
+    DoubleTap,
+    /// A press longer than the long press threshold
+    LongPress,
+    /// A press longer than a tap but shorter than a long press
+    Hold { elapsed: Duration },
+}
+
+/// Timing parameters of the gesture detection, see the [module docs](self) for how they interact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GestureTiming {
+    /// Longest press still counted as a tap
+    pub tap: Duration,
+    /// Time after a tap's release in which a second tap makes a double tap,
+    /// `None` reports every tap immediately
+    pub double_tap: Option<Duration>,
+    /// Shortest press counted as a long press
+    pub long_press: Duration,
+}
+
+impl Default for GestureTiming {
+    fn default() -> Self {
+        Self {
+            tap: Duration::from_millis(300),
+            double_tap: Some(Duration::from_millis(250)),
+            long_press: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A tap waiting for the double tap window to pass
+struct PendingTap {
+    /// Edge timestamp of the release
+    released: Duration,
+    /// Wall clock time of the release, for the timer thread
+    at: Instant,
+    /// A second press started within the window and is still held
+    second_press: bool,
+}
+
+#[derive(Default)]
+struct State {
+    pressed_at: Option<Duration>,
+    pending: Option<PendingTap>,
+}
+
+pub(crate) struct GestureDetector {
+    name: String,
+    timing: GestureTiming,
+    callback: fn(&str, Gesture),
+    state: Mutex<State>,
+    wake: Condvar,
+    clock: Arc<dyn Clock>,
+}
+
+impl GestureDetector {
+    pub(crate) fn new(name: &str, timing: GestureTiming, callback: fn(&str, Gesture)) -> Self {
+        Self {
+            name: name.to_owned(),
+            timing,
+            callback,
+            state: Mutex::new(State::default()),
+            wake: Condvar::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Take the time of releases and window expiry from `clock`
+    #[cfg(test)]
+    fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Start the thread reporting taps once their double tap window has passed.
+    /// The thread ends when the detector is dropped.
+    pub(crate) fn spawn_timer(detector: &Arc<Self>) {
+        if detector.timing.double_tap.is_some() {
+            let weak = Arc::downgrade(detector);
+            spawn_named(&detector.name, move || GestureDetector::run(weak));
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn report(&self, gesture: Gesture) {
+        trace!("Switch {} gesture {:?}", self.name, gesture);
+        catch_panic(module_path!(), &self.name, || {
+            (self.callback)(&self.name, gesture)
+        });
+    }
+
+    /// Feed a press or release of the switch, `timestamp` being the time of the edge
+    pub(crate) fn handle(&self, pressed: bool, timestamp: Duration) {
+        let mut state = self.lock();
+        if pressed {
+            state.pressed_at = Some(timestamp);
+            let window = self.timing.double_tap.unwrap_or_default();
+            match state.pending.as_mut() {
+                Some(pending) if timestamp.saturating_sub(pending.released) <= window => {
+                    pending.second_press = true;
+                }
+                Some(_) => {
+                    state.pending = None;
+                    self.report(Gesture::Tap);
+                }
+                None => {}
+            }
+            return;
+        }
+
+        // a release without a recorded press (e.g. held during startup) is ignored
+        let Some(pressed_at) = state.pressed_at.take() else {
+            return;
+        };
+        let held = timestamp.saturating_sub(pressed_at);
+        let pending = state.pending.take();
+        let gesture = if held > self.timing.long_press {
+            Gesture::LongPress
+        } else if held > self.timing.tap {
+            Gesture::Hold { elapsed: held }
+        } else if pending.is_some() {
+            self.report(Gesture::DoubleTap);
+            return;
+        } else if self.timing.double_tap.is_some() {
+            state.pending = Some(PendingTap {
+                released: timestamp,
+                at: self.clock.now(),
+                second_press: false,
+            });
+            self.wake.notify_one();
+            return;
+        } else {
+            Gesture::Tap
+        };
+        if pending.is_some() {
+            // the second press was no tap, so the first one stands on its own
+            self.report(Gesture::Tap);
+        }
+        self.report(gesture);
+    }
+
+    /// Report a pending tap whose double tap window has passed at `now`,
+    /// returning how long to wait until the next check
+    fn expire(&self, now: Instant) -> Duration {
+        let window = self.timing.double_tap.unwrap_or_default();
+        let mut state = self.lock();
+        match state.pending.as_ref() {
+            Some(pending) if !pending.second_press => {
+                let waited = now.saturating_duration_since(pending.at);
+                if waited >= window {
+                    state.pending = None;
+                    self.report(Gesture::Tap);
+                    window
+                } else {
+                    window - waited
+                }
+            }
+            _ => window,
+        }
+    }
+
+    fn run(weak: Weak<GestureDetector>) {
+        while let Some(detector) = weak.upgrade() {
+            let timeout = detector.expire(detector.clock.now());
+            let state = detector.lock();
+            drop(
+                detector
+                    .wake
+                    .wait_timeout(state, timeout)
+                    .unwrap_or_else(|e| e.into_inner()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    static LOG: Mutex<Vec<(String, Gesture)>> = Mutex::new(Vec::new());
+
+    fn callback(name: &str, gesture: Gesture) {
+        LOG.lock().unwrap().push((name.to_owned(), gesture));
+    }
+
+    fn logged(name: &str) -> Vec<Gesture> {
+        LOG.lock()
+            .unwrap()
+            .iter()
+            .filter(|(n, _)| *n == name)
+            .map(|(_, g)| *g)
+            .collect()
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_classify_without_double_tap() {
+        let timing = GestureTiming {
+            double_tap: None,
+            ..GestureTiming::default()
+        };
+        let detector = GestureDetector::new("single", timing, callback);
+        for (press, release) in [(0, 100), (200, 700), (1000, 2500)] {
+            detector.handle(true, ms(press));
+            detector.handle(false, ms(release));
+        }
+        assert_eq!(
+            logged("single"),
+            vec![
+                Gesture::Tap,
+                Gesture::Hold { elapsed: ms(500) },
+                Gesture::LongPress
+            ]
+        );
+    }
+
+    #[test]
+    fn test_double_tap_window() {
+        let clock = Arc::new(MockClock::new());
+        let detector = GestureDetector::new("double", GestureTiming::default(), callback)
+            .with_clock(clock.clone());
+
+        // two taps within the window
+        detector.handle(true, ms(0));
+        detector.handle(false, ms(100));
+        detector.handle(true, ms(200));
+        detector.handle(false, ms(300));
+        assert_eq!(logged("double"), vec![Gesture::DoubleTap]);
+
+        // a tap followed by a hold reports both on their own
+        detector.handle(true, ms(1000));
+        detector.handle(false, ms(1100));
+        detector.handle(true, ms(1200));
+        detector.handle(false, ms(1800));
+
+        // a single tap is only reported once the window has passed
+        detector.handle(true, ms(3000));
+        detector.handle(false, ms(3100));
+        assert_eq!(logged("double").len(), 3);
+        clock.advance(ms(200));
+        detector.expire(clock.now());
+        assert_eq!(logged("double").len(), 3);
+        clock.advance(ms(50));
+        detector.expire(clock.now());
+        assert_eq!(
+            logged("double"),
+            vec![
+                Gesture::DoubleTap,
+                Gesture::Tap,
+                Gesture::Hold { elapsed: ms(600) },
+                Gesture::Tap
+            ]
+        );
+    }
+}
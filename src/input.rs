@@ -0,0 +1,1297 @@
+//! [`PiInput`], creating and holding all encoders of a set of definitions.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use log::{debug, trace};
+use rppal::gpio::Gpio;
+
+use crate::error::EncoderError;
+use crate::rotary_encoder::{self, ClkPin, Direction, DtPin, SwPin};
+use crate::shift::{self, SharedShift};
+#[cfg(feature = "sim")]
+use crate::sim;
+use crate::switch_encoder::{self, SwitchMode};
+use crate::{matrix, selector};
+
+// All encoders can be moved to and shared between threads, e.g. in an `Arc<Mutex<Vec<_>>>`.
+// Failing to compile here means a change made one of them lose `Send` or `Sync`.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PiInput>();
+    assert_send_sync::<rotary_encoder::Encoder>();
+    assert_send_sync::<rotary_encoder::EncoderBuilder>();
+    assert_send_sync::<rotary_encoder::PhaseTraceGuard>();
+    assert_send_sync::<switch_encoder::Encoder>();
+    assert_send_sync::<selector::Selector>();
+    assert_send_sync::<selector::GrayEncoder>();
+    assert_send_sync::<switch_encoder::SwitchBank>();
+    assert_send_sync::<switch_encoder::Chord>();
+    assert_send_sync::<matrix::MatrixScanner>();
+    assert_send_sync::<shift::SharedShift>();
+    #[cfg(feature = "sim")]
+    assert_send_sync::<sim::SimulatedEncoder>();
+    #[cfg(feature = "sim")]
+    assert_send_sync::<sim::SimulatedSwitch>();
+};
+
+/// All encoders of a set of definitions.
+///
+/// `PiInput` is `Send` and `Sync`, like all encoders of this crate.
+pub struct PiInput {
+    rot_encoders: Vec<RotaryInput>,
+    sw_encoders: Vec<SwitchInput>,
+    shift: Option<SharedShift>,
+}
+
+/// Rotary encoder created for a definition
+enum RotaryInput {
+    Gpio(Box<rotary_encoder::Encoder>),
+    /// Driven from code, see [`PiInput::simulated`]
+    #[cfg(feature = "mock-gpio")]
+    Simulated(Box<sim::SimulatedEncoder>),
+}
+
+impl RotaryInput {
+    fn name(&self) -> Arc<str> {
+        match self {
+            RotaryInput::Gpio(encoder) => encoder.name(),
+            #[cfg(feature = "mock-gpio")]
+            RotaryInput::Simulated(encoder) => encoder.name(),
+        }
+    }
+
+    fn shifted_name(&self) -> Option<Arc<str>> {
+        match self {
+            RotaryInput::Gpio(encoder) => encoder.shifted_name(),
+            #[cfg(feature = "mock-gpio")]
+            RotaryInput::Simulated(encoder) => encoder.shifted_name(),
+        }
+    }
+
+    fn position(&self) -> i32 {
+        match self {
+            RotaryInput::Gpio(encoder) => encoder.position(),
+            #[cfg(feature = "mock-gpio")]
+            RotaryInput::Simulated(encoder) => encoder.position(),
+        }
+    }
+
+    fn last_triggered_direction(&self) -> Direction {
+        match self {
+            RotaryInput::Gpio(encoder) => encoder.last_triggered_direction(),
+            #[cfg(feature = "mock-gpio")]
+            RotaryInput::Simulated(encoder) => encoder.last_triggered_direction(),
+        }
+    }
+
+    fn add_detent_callback(&self, callback: rotary_encoder::Callback) {
+        match self {
+            RotaryInput::Gpio(encoder) => encoder.add_detent_callback(callback),
+            #[cfg(feature = "mock-gpio")]
+            RotaryInput::Simulated(encoder) => encoder.add_detent_callback(callback),
+        }
+    }
+
+    fn replace_callback(&self, callback: rotary_encoder::Callback) {
+        match self {
+            RotaryInput::Gpio(encoder) => encoder.replace_callback(callback),
+            #[cfg(feature = "mock-gpio")]
+            RotaryInput::Simulated(encoder) => encoder.replace_callback(callback),
+        }
+    }
+}
+
+/// Switch created for a definition
+enum SwitchInput {
+    Gpio(switch_encoder::Encoder),
+    /// Driven from code, see [`PiInput::simulated`]
+    #[cfg(feature = "mock-gpio")]
+    Simulated(Box<sim::SimulatedSwitch>),
+}
+
+impl SwitchInput {
+    fn name(&self) -> Arc<str> {
+        match self {
+            SwitchInput::Gpio(switch) => switch.name(),
+            #[cfg(feature = "mock-gpio")]
+            SwitchInput::Simulated(switch) => switch.name(),
+        }
+    }
+
+    fn long_press_name(&self) -> Option<Arc<str>> {
+        match self {
+            SwitchInput::Gpio(switch) => switch.long_press_name(),
+            #[cfg(feature = "mock-gpio")]
+            SwitchInput::Simulated(switch) => switch.long_press_name(),
+        }
+    }
+
+    fn is_pressed(&self) -> bool {
+        match self {
+            SwitchInput::Gpio(switch) => switch.is_pressed(),
+            #[cfg(feature = "mock-gpio")]
+            SwitchInput::Simulated(switch) => switch.is_pressed(),
+        }
+    }
+
+    fn add_listener(&self, listener: switch_encoder::Listener) {
+        match self {
+            SwitchInput::Gpio(switch) => switch.add_listener(listener),
+            #[cfg(feature = "mock-gpio")]
+            SwitchInput::Simulated(switch) => switch.add_listener(listener),
+        }
+    }
+
+    fn replace_callback(&self, callback: fn(&str, bool)) {
+        match self {
+            SwitchInput::Gpio(switch) => switch.replace_callback(callback),
+            #[cfg(feature = "mock-gpio")]
+            SwitchInput::Simulated(switch) => switch.replace_callback(callback),
+        }
+    }
+}
+
+/// Event of any input of a [`PiInput`], see [`PiInput::event_stream`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputEvent {
+    /// A rotary encoder was turned by one detent, `name` is the shifted name while pressed
+    Rotary { name: String, direction: Direction },
+    /// A switch was pressed or released, `name` is the long press name for long releases
+    Switch { name: String, pressed: bool },
+}
+
+/// Live state of all inputs of a [`PiInput`], see [`PiInput::snapshot`]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputSnapshot {
+    /// Name of every switch and whether it is held down
+    pub switches: Vec<(String, bool)>,
+    /// Name, position and direction of the last detent of every rotary encoder
+    pub rotaries: Vec<(String, i32, Direction)>,
+}
+
+#[derive(Debug)]
+pub enum EncoderType {
+    Rotary,
+    Switch,
+}
+
+/// Board family, determining the range of valid BCM GPIO pin numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PiModel {
+    /// Boards with the 40-pin header, exposing BCM GPIO 0-27
+    #[default]
+    Header40Pin,
+    /// Compute Modules, exposing BCM GPIO 0-45
+    ComputeModule,
+}
+
+impl PiModel {
+    /// Highest valid BCM GPIO pin number on this model
+    pub const fn max_pin(self) -> u8 {
+        match self {
+            PiModel::Header40Pin => 27,
+            PiModel::ComputeModule => 45,
+        }
+    }
+
+    /// Check that `pin` is a valid BCM GPIO pin number on this model
+    pub fn check_pin(self, pin: u8) -> Result<(), EncoderError> {
+        if pin > self.max_pin() {
+            return Err(EncoderError::InvalidPin {
+                pin,
+                max: self.max_pin(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Definition of a switch.
+///
+/// With the `serde` feature it can be deserialized; the callbacks are not part of the
+/// serialized form and default to doing nothing.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwitchDefinition {
+    pub name: String,
+    /// Name reported on the release of a long press, enables long press detection
+    pub name_long_press: Option<String>,
+    pub sw_pin: u8,
+    pub active_low: bool,
+    #[cfg_attr(feature = "serde", serde(skip, default = "ignore_switch"))]
+    pub callback: fn(&str, bool),
+    /// Optional function to call on release with the time the switch was held
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub on_release: Option<fn(&str, Duration)>,
+    /// Long press threshold: presses held longer are released under `name_long_press`,
+    /// `None` disables long press detection
+    pub time_threshold: Option<Duration>,
+    pub debounce: Option<Duration>,
+    /// Minimum time a press must last to be reported, filtering phantom presses.
+    /// Not applied by the simulated switches of [`PiInput::simulated`].
+    pub min_press: Option<Duration>,
+    /// Whether presses and releases are reported or each press toggles a latched state
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mode: SwitchMode,
+    /// Target of the switch's log messages, defaults to the module path
+    pub log_target: Option<String>,
+}
+
+/// Definition of a rotary encoder, deserializable like a [`SwitchDefinition`]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RotaryDefinition {
+    pub name: String,
+    pub name_shifted: Option<String>,
+    pub sw_pin: Option<SwPin>,
+    pub dt_pin: DtPin,
+    pub clk_pin: ClkPin,
+    /// Function to call when the encoder is turned, receiving the (shifted) name, the
+    /// direction and whether the built-in switch was held
+    #[cfg_attr(feature = "serde", serde(skip, default = "ignore_rotation"))]
+    pub callback: fn(&str, Direction, bool),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub on_error: Option<fn(&str, anyhow::Error)>,
+    /// Optional function to call on every detent regardless of direction, before `callback`,
+    /// e.g. to pulse a buzzer for audio or haptic feedback
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub on_detent: Option<fn(&str)>,
+    pub reversed: bool,
+    /// rppal software debounce for the DT and CLK interrupts (e.g. 2-5ms for cheap encoders),
+    /// `None` disables debouncing
+    pub debounce: Option<Duration>,
+    /// Target of the encoder's log messages, e.g. `myapp::volume_knob` to filter them with
+    /// `RUST_LOG=myapp::volume_knob=trace`. Defaults to the module path.
+    pub log_target: Option<String>,
+}
+
+/// Callback of deserialized switch definitions
+#[cfg(feature = "serde")]
+fn ignore_switch() -> fn(&str, bool) {
+    |_, _| {}
+}
+
+/// Callback of deserialized rotary definitions
+#[cfg(feature = "serde")]
+fn ignore_rotation() -> fn(&str, Direction, bool) {
+    |_, _, _| {}
+}
+
+/// All definitions of a [`PiInput`] in one value, e.g. deserialized from a config file
+/// with the `serde` feature. Callbacks of deserialized definitions do nothing; consume
+/// the events with [`PiInput::event_stream`] or replace them with [`PiInput::on_any`].
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputConfig {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub switches: Vec<SwitchDefinition>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rotaries: Vec<RotaryDefinition>,
+    /// Board model used to validate pin numbers
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub model: PiModel,
+}
+
+impl InputConfig {
+    /// Check the definitions for pin collisions and out of range pins, open the GPIO and
+    /// create all encoders
+    pub fn build(self) -> Result<PiInput, EncoderError> {
+        PiInput::with_model(&self.switches, &self.rotaries, self.model)
+    }
+}
+
+#[cfg(feature = "mock-gpio")]
+impl PiInput {
+    /// Create simulated encoders for all definitions of `config` instead of claiming GPIO
+    /// pins, e.g. to run on a host without a Pi. Pin numbers are still validated.
+    /// Drive them with [`PiInput::simulated_rotary`] and [`PiInput::simulated_switch`].
+    pub fn simulated(config: InputConfig) -> Result<Self, EncoderError> {
+        Self::create_simulated(&config.switches, &config.rotaries, None, config.model)
+    }
+
+    /// Simulated rotary encoder created for the definition named `name`
+    pub fn simulated_rotary(&self, name: &str) -> Option<&sim::SimulatedEncoder> {
+        self.rot_encoders.iter().find_map(|r| match r {
+            RotaryInput::Simulated(encoder) if *encoder.name() == *name => Some(&**encoder),
+            _ => None,
+        })
+    }
+
+    /// Simulated switch created for the definition named `name`
+    pub fn simulated_switch(&self, name: &str) -> Option<&sim::SimulatedSwitch> {
+        self.sw_encoders.iter().find_map(|s| match s {
+            SwitchInput::Simulated(switch) if *switch.name() == *name => Some(&**switch),
+            _ => None,
+        })
+    }
+
+    fn create_simulated(
+        switches: &[SwitchDefinition],
+        rotaries: &[RotaryDefinition],
+        shift_pin: Option<u8>,
+        model: PiModel,
+    ) -> Result<Self, EncoderError> {
+        debug!("Initializing simulated PiInput...");
+        check_definitions(switches, rotaries, shift_pin, model)?;
+        let (rot_encoders, sw_encoders, shift) =
+            create_simulated_encoders(switches, rotaries, shift_pin);
+
+        trace!("Simulated PiInput initialized");
+        Ok(Self {
+            rot_encoders,
+            sw_encoders,
+            shift,
+        })
+    }
+}
+
+impl PiInput {
+    /// Start assembling a `PiInput` incrementally
+    pub fn builder() -> PiInputBuilder {
+        PiInputBuilder::default()
+    }
+
+    /// Rotary encoder created for the definition named `name`
+    pub fn rotary_by_name(&self, name: &str) -> Option<&rotary_encoder::Encoder> {
+        self.rot_encoders.iter().find_map(|r| match r {
+            RotaryInput::Gpio(encoder) if *encoder.name() == *name => Some(&**encoder),
+            _ => None,
+        })
+    }
+
+    /// Switch created for the definition named `name`
+    pub fn switch_by_name(&self, name: &str) -> Option<&switch_encoder::Encoder> {
+        self.sw_encoders.iter().find_map(|s| match s {
+            SwitchInput::Gpio(switch) if *switch.name() == *name => Some(switch),
+            _ => None,
+        })
+    }
+
+    /// Every name the callbacks and events of this `PiInput` can report: the names of all
+    /// rotaries and their shifted names, then those of all switches and their long press
+    /// names. E.g. to check at startup that a handler map covers all of them.
+    pub fn registered_names(&self) -> Vec<String> {
+        let rotaries = self
+            .rot_encoders
+            .iter()
+            .flat_map(|r| [Some(r.name()), r.shifted_name()]);
+        let switches = self
+            .sw_encoders
+            .iter()
+            .flat_map(|s| [Some(s.name()), s.long_press_name()]);
+        rotaries
+            .chain(switches)
+            .flatten()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Current state of every input in definition order, e.g. for a status endpoint.
+    /// Switches are read from their pins, rotaries report their position and the direction
+    /// of their last detent ([`Direction::None`] before the first one).
+    pub fn snapshot(&self) -> InputSnapshot {
+        InputSnapshot {
+            switches: self
+                .sw_encoders
+                .iter()
+                .map(|s| (s.name().to_string(), s.is_pressed()))
+                .collect(),
+            rotaries: self
+                .rot_encoders
+                .iter()
+                .map(|r| {
+                    (
+                        r.name().to_string(),
+                        r.position(),
+                        r.last_triggered_direction(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Receive the events of all encoders through a single channel, e.g. for a central
+    /// dispatcher. The definitions' callbacks are still called as well.
+    ///
+    /// Every call registers a new channel with all encoders, so keep the receiver around
+    /// instead of calling this repeatedly. Events are dropped once the receiver is gone.
+    pub fn event_stream(&self) -> Receiver<InputEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.forward_events(Arc::new(move |event| {
+            let _ = sender.send(event);
+        }));
+        receiver
+    }
+
+    /// Pass the events of all encoders to `sink` in addition to their callbacks
+    pub(crate) fn forward_events(&self, sink: Arc<dyn Fn(InputEvent) + Send + Sync>) {
+        for encoder in &self.rot_encoders {
+            let sink = Arc::clone(&sink);
+            encoder.add_detent_callback(Arc::new(move |detent| {
+                sink(InputEvent::Rotary {
+                    name: detent.name.to_owned(),
+                    direction: detent.direction,
+                })
+            }));
+        }
+        for encoder in &self.sw_encoders {
+            let sink = Arc::clone(&sink);
+            encoder.add_listener(Arc::new(move |name, pressed| {
+                sink(InputEvent::Switch {
+                    name: name.to_owned(),
+                    pressed,
+                })
+            }));
+        }
+    }
+
+    /// Report all rotaries to `rotary` and all switches to `switch`, e.g. to print every
+    /// event while prototyping without writing a callback per definition.
+    ///
+    /// This **replaces** the callbacks given in the definitions, they are no longer called.
+    /// Callbacks registered on the encoders afterwards, like [`PiInput::event_stream`],
+    /// are kept.
+    pub fn on_any(&mut self, rotary: fn(&str, Direction), switch: fn(&str, bool)) {
+        for encoder in &self.rot_encoders {
+            encoder.replace_callback(rotary_encoder::untimed(rotary));
+        }
+        for encoder in &self.sw_encoders {
+            encoder.replace_callback(switch);
+        }
+    }
+
+    /// Block until the process receives SIGINT or SIGTERM, then drop all encoders, which
+    /// unregisters their interrupts and releases the pins. Replaces the usual
+    /// flag-and-park scaffolding around the encoders of a daemon.
+    #[cfg(feature = "signal")]
+    pub fn run_blocking(self) -> Result<(), EncoderError> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        use signal_hook::iterator::Signals;
+
+        let mut signals = Signals::new([SIGINT, SIGTERM]).map_err(EncoderError::SignalHandler)?;
+        if let Some(signal) = signals.forever().next() {
+            debug!("Received signal {}, releasing all encoders", signal);
+        }
+        drop(self);
+        Ok(())
+    }
+
+    /// Create all encoders for a board with the 40-pin header, opening the GPIO peripheral
+    pub fn new(
+        switches: &[SwitchDefinition],
+        rotaries: &[RotaryDefinition],
+    ) -> Result<Self, EncoderError> {
+        Self::with_model(switches, rotaries, PiModel::default())
+    }
+
+    /// Create all encoders, validating pin numbers against the given board `model`
+    pub fn with_model(
+        switches: &[SwitchDefinition],
+        rotaries: &[RotaryDefinition],
+        model: PiModel,
+    ) -> Result<Self, EncoderError> {
+        Self::create(None, switches, rotaries, None, model)
+    }
+
+    /// Create all encoders on an already opened `gpio`, e.g. one also used for other pins.
+    /// rppal's `Gpio` is cheap to clone and share; it is only borrowed while the encoders
+    /// claim their pins, so it can be reused for further `PiInput`s afterwards.
+    pub fn with_gpio(
+        gpio: &Gpio,
+        switches: &[SwitchDefinition],
+        rotaries: &[RotaryDefinition],
+    ) -> Result<Self, EncoderError> {
+        Self::create(Some(gpio), switches, rotaries, None, PiModel::default())
+    }
+
+    /// Create all encoders with one shift button on `shift_pin` shared by all rotaries that
+    /// have no `sw_pin` of their own: while it is held, they report their shifted names.
+    /// The button pulls the pin low while pressed.
+    pub fn with_shared_shift(
+        shift_pin: u8,
+        switches: &[SwitchDefinition],
+        rotaries: &[RotaryDefinition],
+    ) -> Result<Self, EncoderError> {
+        Self::create(
+            None,
+            switches,
+            rotaries,
+            Some(shift_pin),
+            PiModel::default(),
+        )
+    }
+
+    /// The shift button shared by the rotaries, if created with [`PiInput::with_shared_shift`]
+    /// (a simulated one for [`PiInputBuilder::build_simulated`])
+    pub fn shared_shift(&self) -> Option<&SharedShift> {
+        self.shift.as_ref()
+    }
+
+    fn create(
+        gpio: Option<&Gpio>,
+        switches: &[SwitchDefinition],
+        rotaries: &[RotaryDefinition],
+        shift_pin: Option<u8>,
+        model: PiModel,
+    ) -> Result<Self, EncoderError> {
+        debug!("Initializing PiInput...");
+        // fail before any pin is claimed, rather than half way through creating the encoders
+        check_definitions(switches, rotaries, shift_pin, model)?;
+        let (rot_encoders, sw_encoders, shift) =
+            create_encoders(gpio, switches, rotaries, shift_pin)?;
+
+        trace!("PiInput initialized");
+        Ok(Self {
+            rot_encoders,
+            sw_encoders,
+            shift,
+        })
+    }
+}
+
+/// Encoders created for the definitions, and the shift button shared by the rotaries
+type Encoders = (Vec<RotaryInput>, Vec<SwitchInput>, Option<SharedShift>);
+
+/// Check the definitions for pin collisions and pins the board `model` does not have
+fn check_definitions(
+    switches: &[SwitchDefinition],
+    rotaries: &[RotaryDefinition],
+    shift_pin: Option<u8>,
+    model: PiModel,
+) -> Result<(), EncoderError> {
+    check_pin_collisions(switches, rotaries, shift_pin)?;
+    for pin in claimed_pins(switches, rotaries).map(|(pin, _)| pin) {
+        model.check_pin(pin)?;
+    }
+    if let Some(pin) = shift_pin {
+        model.check_pin(pin)?;
+    }
+    Ok(())
+}
+
+/// Claim the GPIO pins and create the encoders for all definitions,
+/// opening the GPIO peripheral unless `gpio` is given
+fn create_encoders(
+    gpio: Option<&Gpio>,
+    switches: &[SwitchDefinition],
+    rotaries: &[RotaryDefinition],
+    shift_pin: Option<u8>,
+) -> Result<Encoders, EncoderError> {
+    let opened;
+    let gpio = match gpio {
+        Some(gpio) => gpio,
+        None => {
+            opened = Gpio::new()?;
+            &opened
+        }
+    };
+    let shift = shift_pin
+        .map(|pin| SharedShift::new(gpio, pin))
+        .transpose()?;
+
+    let mut rot_encoders = Vec::with_capacity(rotaries.len());
+    let mut sw_encoders = Vec::with_capacity(switches.len());
+    let created = rotaries
+        .iter()
+        .try_for_each(|r| {
+            let encoder = create_rotary(gpio, r, shift.as_ref())?;
+            rot_encoders.push(RotaryInput::Gpio(Box::new(encoder)));
+            Ok(())
+        })
+        .and_then(|()| {
+            switches.iter().try_for_each(|s| {
+                sw_encoders.push(SwitchInput::Gpio(create_switch(gpio, s)?));
+                Ok(())
+            })
+        });
+    if let Err(e) = created {
+        debug!(
+            "Creating the encoders failed ({}), releasing the {} created before",
+            e,
+            rot_encoders.len() + sw_encoders.len()
+        );
+        // dropping an encoder clears its interrupts and releases its pins, so the caller
+        // can retry with corrected definitions right away
+        drop(sw_encoders);
+        drop(rot_encoders);
+        drop(shift);
+        return Err(e);
+    }
+
+    Ok((rot_encoders, sw_encoders, shift))
+}
+
+/// Claim the pins of a single rotary definition
+fn create_rotary(
+    gpio: &Gpio,
+    r: &RotaryDefinition,
+    shift: Option<&SharedShift>,
+) -> Result<rotary_encoder::Encoder, EncoderError> {
+    let mut builder = rotary_encoder::Encoder::builder()
+        .name(&r.name)
+        .dt_pin(r.dt_pin)
+        .clk_pin(r.clk_pin)
+        .reversed(r.reversed)
+        .shifted_callback(r.callback);
+    if let Some(name_shifted) = &r.name_shifted {
+        builder = builder.shifted_name(name_shifted);
+    }
+    match (r.sw_pin, shift) {
+        (Some(sw_pin), _) => builder = builder.switch_pin(sw_pin),
+        (None, Some(shift)) => builder = builder.shared_shift(shift),
+        (None, None) => {}
+    }
+    if let Some(on_error) = r.on_error {
+        builder = builder.on_error(on_error);
+    }
+    if let Some(on_detent) = r.on_detent {
+        builder = builder.on_detent(on_detent);
+    }
+    if let Some(debounce) = r.debounce {
+        builder = builder.debounce(debounce);
+    }
+    if let Some(log_target) = &r.log_target {
+        builder = builder.log_target(log_target);
+    }
+    builder.build(gpio)
+}
+
+/// Claim the pin of a single switch definition
+fn create_switch(
+    gpio: &Gpio,
+    s: &SwitchDefinition,
+) -> Result<switch_encoder::Encoder, EncoderError> {
+    let mut builder = switch_encoder::Encoder::builder()
+        .name(&s.name)
+        .pin(s.sw_pin)
+        .active_low(s.active_low)
+        .debounce(s.debounce)
+        .mode(s.mode)
+        .callback(s.callback);
+    if let Some(name_long_press) = &s.name_long_press {
+        builder = builder.long_press_name(name_long_press);
+    }
+    if let Some(time_threshold) = s.time_threshold {
+        builder = builder.long_press_threshold(time_threshold);
+    }
+    if let Some(min_press) = s.min_press {
+        builder = builder.min_press(min_press);
+    }
+    if let Some(log_target) = &s.log_target {
+        builder = builder.log_target(log_target);
+    }
+    let mut encoder = builder.build(gpio)?;
+    if let Some(on_release) = s.on_release {
+        encoder.add_release_callback(on_release);
+    }
+    Ok(encoder)
+}
+
+/// Create simulated encoders for all definitions, no GPIO is touched
+#[cfg(feature = "mock-gpio")]
+fn create_simulated_encoders(
+    switches: &[SwitchDefinition],
+    rotaries: &[RotaryDefinition],
+    shift_pin: Option<u8>,
+) -> Encoders {
+    let shift = shift_pin.map(|_| SharedShift::simulated());
+    let rot_encoders = rotaries
+        .iter()
+        .map(|r| {
+            let encoder = sim::SimulatedEncoder::from_parts(
+                &r.name,
+                r.name_shifted.as_deref(),
+                r.sw_pin.is_some(),
+                r.reversed,
+                rotary_encoder::shift_aware(r.callback),
+            );
+            let encoder = match (r.sw_pin, &shift) {
+                (None, Some(shift)) => encoder.with_shared_shift(shift),
+                _ => encoder,
+            };
+            let encoder = match r.on_detent {
+                Some(on_detent) => encoder.on_detent(on_detent),
+                None => encoder,
+            };
+            let encoder = match &r.log_target {
+                Some(log_target) => encoder.log_target(log_target),
+                None => encoder,
+            };
+            RotaryInput::Simulated(Box::new(encoder))
+        })
+        .collect();
+    let sw_encoders = switches
+        .iter()
+        .map(|s| {
+            let mut switch = sim::SimulatedSwitch::new(
+                &s.name,
+                s.name_long_press.as_deref(),
+                s.time_threshold,
+                s.callback,
+            );
+            if let Some(log_target) = &s.log_target {
+                switch = switch.log_target(log_target);
+            }
+            switch = switch.mode(s.mode);
+            if let Some(on_release) = s.on_release {
+                switch.add_release_callback(on_release);
+            }
+            SwitchInput::Simulated(Box::new(switch))
+        })
+        .collect();
+    (rot_encoders, sw_encoders, shift)
+}
+
+/// Builder for [`PiInput`], collecting definitions one at a time
+#[derive(Debug, Default)]
+pub struct PiInputBuilder {
+    switches: Vec<SwitchDefinition>,
+    rotaries: Vec<RotaryDefinition>,
+    model: PiModel,
+    shift_pin: Option<u8>,
+    gpio: Option<Gpio>,
+    chip: Option<String>,
+}
+
+impl PiInputBuilder {
+    /// Register a switch
+    pub fn add_switch(mut self, switch: SwitchDefinition) -> Self {
+        self.switches.push(switch);
+        self
+    }
+
+    /// Register a rotary encoder
+    pub fn add_rotary(mut self, rotary: RotaryDefinition) -> Self {
+        self.rotaries.push(rotary);
+        self
+    }
+
+    /// Board model used to validate pin numbers, defaults to [`PiModel::Header40Pin`]
+    pub fn model(mut self, model: PiModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Share one shift button on `pin` between all rotaries without their own `sw_pin`,
+    /// see [`PiInput::with_shared_shift`]
+    pub fn shared_shift(mut self, pin: u8) -> Self {
+        self.shift_pin = Some(pin);
+        self
+    }
+
+    /// Reuse an already opened `gpio` instead of opening the GPIO peripheral in `build`
+    pub fn gpio(mut self, gpio: &Gpio) -> Self {
+        self.gpio = Some(gpio.clone());
+        self
+    }
+
+    /// Place the encoders on the GPIO chip `chip`, e.g. `/dev/gpiochip1`.
+    ///
+    /// rppal always opens the Raspberry Pi's main GPIO controller, so `build` fails with
+    /// [`EncoderError::UnsupportedChip`] for any chip given here rather than silently
+    /// claiming the same line offsets on the main controller.
+    pub fn gpio_chip(mut self, chip: impl Into<String>) -> Self {
+        self.chip = Some(chip.into());
+        self
+    }
+
+    /// Check the definitions for pin collisions, open the GPIO and create all encoders
+    pub fn build(self) -> Result<PiInput, EncoderError> {
+        check_chip(self.chip.as_deref())?;
+        PiInput::create(
+            self.gpio.as_ref(),
+            &self.switches,
+            &self.rotaries,
+            self.shift_pin,
+            self.model,
+        )
+    }
+
+    /// Check the definitions like [`PiInputBuilder::build`], but create simulated encoders
+    /// instead of claiming GPIO pins, see [`PiInput::simulated`]. The shared shift button
+    /// is a [`SharedShift::simulated`] one.
+    #[cfg(feature = "mock-gpio")]
+    pub fn build_simulated(self) -> Result<PiInput, EncoderError> {
+        PiInput::create_simulated(&self.switches, &self.rotaries, self.shift_pin, self.model)
+    }
+}
+
+/// Ensure no GPIO chip other than the main GPIO controller was requested, as rppal
+/// has no way to open another one
+fn check_chip(chip: Option<&str>) -> Result<(), EncoderError> {
+    match chip {
+        Some(chip) => Err(EncoderError::UnsupportedChip(chip.to_owned())),
+        None => Ok(()),
+    }
+}
+
+/// All pins claimed by the definitions, paired with the name of the claiming definition
+fn claimed_pins<'a>(
+    switches: &'a [SwitchDefinition],
+    rotaries: &'a [RotaryDefinition],
+) -> impl Iterator<Item = (u8, &'a String)> {
+    let switch_pins = switches.iter().map(|s| (s.sw_pin, &s.name));
+    let rotary_pins = rotaries.iter().flat_map(|r| {
+        [
+            Some(r.dt_pin.0),
+            Some(r.clk_pin.0),
+            r.sw_pin.map(|pin| pin.0),
+        ]
+        .into_iter()
+        .flatten()
+        .map(move |pin| (pin, &r.name))
+    });
+    switch_pins.chain(rotary_pins)
+}
+
+/// Ensure no GPIO pin is claimed by more than one definition (or twice by the same one),
+/// nor by a definition and the shared shift button
+fn check_pin_collisions(
+    switches: &[SwitchDefinition],
+    rotaries: &[RotaryDefinition],
+    shift_pin: Option<u8>,
+) -> Result<(), EncoderError> {
+    let shift_name = "shared shift".to_owned();
+    let shift = shift_pin.map(|pin| (pin, &shift_name));
+    let mut claimed: HashMap<u8, &String> = HashMap::new();
+    for (pin, name) in claimed_pins(switches, rotaries).chain(shift) {
+        if let Some(other) = claimed.insert(pin, name) {
+            return Err(EncoderError::DuplicatePin {
+                pin,
+                first: other.clone(),
+                second: name.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn switch(name: &str, sw_pin: u8) -> SwitchDefinition {
+        SwitchDefinition {
+            name: name.to_owned(),
+            name_long_press: None,
+            sw_pin,
+            active_low: true,
+            callback: |_, _| {},
+            on_release: None,
+            time_threshold: None,
+            debounce: None,
+            min_press: None,
+            mode: SwitchMode::Momentary,
+            log_target: None,
+        }
+    }
+
+    fn rotary(name: &str, dt_pin: u8, clk_pin: u8, sw_pin: Option<u8>) -> RotaryDefinition {
+        RotaryDefinition {
+            name: name.to_owned(),
+            name_shifted: None,
+            sw_pin: sw_pin.map(SwPin),
+            dt_pin: DtPin(dt_pin),
+            clk_pin: ClkPin(clk_pin),
+            callback: |_, _, _| {},
+            on_error: None,
+            on_detent: None,
+            reversed: false,
+            debounce: None,
+            log_target: None,
+        }
+    }
+
+    #[test]
+    fn test_builder_collects_definitions() {
+        let builder = PiInput::builder()
+            .add_switch(switch("button", 22))
+            .add_rotary(rotary("volume", 17, 27, None))
+            .add_rotary(rotary("menu", 5, 6, Some(13)));
+        assert_eq!(builder.switches.len(), 1);
+        assert_eq!(builder.rotaries.len(), 2);
+        assert!(check_pin_collisions(&builder.switches, &builder.rotaries, None).is_ok());
+    }
+
+    #[test]
+    fn test_pin_collision_between_switch_and_rotary() {
+        let err = check_pin_collisions(
+            &[switch("button", 11)],
+            &[rotary("volume", 9, 11, None)],
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "GPIO pin 11 is used by both button and volume"
+        );
+    }
+
+    #[test]
+    fn test_pin_collision_between_rotaries() {
+        let err = check_pin_collisions(
+            &[],
+            &[rotary("volume", 9, 10, None), rotary("menu", 5, 6, Some(9))],
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "GPIO pin 9 is used by both volume and menu"
+        );
+    }
+
+    #[test]
+    fn test_pin_collision_between_switches() {
+        let err = check_pin_collisions(&[switch("a", 4), switch("b", 4)], &[], None).unwrap_err();
+        assert_eq!(err.to_string(), "GPIO pin 4 is used by both a and b");
+    }
+
+    #[test]
+    fn test_pin_collision_with_shared_shift() {
+        let rotaries = [rotary("volume", 9, 10, None)];
+        assert!(check_pin_collisions(&[], &rotaries, Some(11)).is_ok());
+        let err = check_pin_collisions(&[], &rotaries, Some(10)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "GPIO pin 10 is used by both volume and shared shift"
+        );
+    }
+
+    #[test]
+    fn test_pin_range_per_model() {
+        assert!(PiModel::Header40Pin.check_pin(27).is_ok());
+        assert_eq!(
+            PiModel::Header40Pin.check_pin(28).unwrap_err().to_string(),
+            "GPIO pin 28 out of range 0-27"
+        );
+        assert!(PiModel::ComputeModule.check_pin(45).is_ok());
+        assert_eq!(
+            PiModel::ComputeModule
+                .check_pin(99)
+                .unwrap_err()
+                .to_string(),
+            "GPIO pin 99 out of range 0-45"
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_pin_rejected_before_gpio() {
+        let result = PiInput::new(&[switch("button", 99)], &[]);
+        assert_eq!(
+            result.map(|_| ()).unwrap_err().to_string(),
+            "GPIO pin 99 out of range 0-27"
+        );
+    }
+
+    #[test]
+    fn test_other_gpio_chip_rejected_before_gpio() {
+        let result = PiInput::builder()
+            .add_switch(switch("button", 22))
+            .gpio_chip("/dev/gpiochip1")
+            .build();
+        assert_eq!(
+            result.map(|_| ()).unwrap_err().to_string(),
+            "GPIO chip /dev/gpiochip1 is not supported, only the main GPIO controller can be used"
+        );
+        assert!(check_chip(None).is_ok());
+    }
+
+    #[test]
+    fn test_config_validated_before_gpio() {
+        let config = InputConfig {
+            switches: vec![switch("button", 11)],
+            rotaries: vec![rotary("volume", 9, 11, None)],
+            model: PiModel::Header40Pin,
+        };
+        assert!(matches!(
+            config.build(),
+            Err(EncoderError::DuplicatePin { pin: 11, .. })
+        ));
+        let config = InputConfig {
+            rotaries: vec![rotary("volume", 9, 30, None)],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.build(),
+            Err(EncoderError::InvalidPin { pin: 30, max: 27 })
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_config_from_json() {
+        let config: InputConfig = serde_json::from_str(
+            r#"{
+                "switches": [{"name": "button", "sw_pin": 22, "active_low": true}],
+                "rotaries": [{"name": "volume", "dt_pin": 9, "clk_pin": 10, "sw_pin": 11,
+                              "reversed": false, "debounce": {"secs": 0, "nanos": 2000000}}],
+                "model": "ComputeModule"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.model, PiModel::ComputeModule);
+        let button = &config.switches[0];
+        assert_eq!((button.name.as_str(), button.sw_pin), ("button", 22));
+        assert_eq!(button.mode, SwitchMode::Momentary);
+        let volume = &config.rotaries[0];
+        assert_eq!(
+            (volume.dt_pin, volume.clk_pin, volume.sw_pin),
+            (DtPin(9), ClkPin(10), Some(SwPin(11)))
+        );
+        assert_eq!(volume.debounce, Some(Duration::from_millis(2)));
+    }
+
+    #[test]
+    fn test_pin_collision_rejected_before_gpio() {
+        let result = PiInput::new(&[switch("button", 11)], &[rotary("volume", 9, 11, None)]);
+        assert!(matches!(
+            result,
+            Err(EncoderError::DuplicatePin { pin: 11, .. })
+        ));
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    #[test]
+    fn test_mock_gpio_drives_definitions() {
+        use std::sync::Mutex;
+
+        static LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        let mut volume = rotary("volume", 17, 27, None);
+        volume.reversed = true;
+        volume.callback =
+            |name, direction, _| LOG.lock().unwrap().push(format!("{name} {direction}"));
+        let mut button = switch("button", 22);
+        button.callback = |name, pressed| LOG.lock().unwrap().push(format!("{name} {pressed}"));
+
+        let input = PiInput::builder()
+            .add_rotary(volume)
+            .add_switch(button)
+            .build_simulated()
+            .unwrap();
+        input
+            .simulated_rotary("volume")
+            .unwrap()
+            .simulate_clockwise();
+        input.simulated_switch("button").unwrap().simulate_press();
+        assert!(input.simulated_rotary("button").is_none());
+        // the real accessors only return GPIO backed encoders
+        assert!(input.rotary_by_name("volume").is_none());
+
+        assert_eq!(*LOG.lock().unwrap(), vec!["volume CCW", "button true"]);
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    #[test]
+    fn test_mock_gpio_snapshot() {
+        let input = PiInput::builder()
+            .add_rotary(rotary("volume", 17, 27, None))
+            .add_rotary(rotary("menu", 5, 6, None))
+            .add_switch(switch("button", 22))
+            .build_simulated()
+            .unwrap();
+        let volume = input.simulated_rotary("volume").unwrap();
+        volume.simulate_clockwise();
+        volume.simulate_clockwise();
+        volume.simulate_counter_clockwise();
+        input.simulated_switch("button").unwrap().simulate_press();
+
+        let snapshot = input.snapshot();
+        assert_eq!(
+            snapshot,
+            InputSnapshot {
+                switches: vec![("button".to_owned(), true)],
+                rotaries: vec![
+                    ("volume".to_owned(), 1, Direction::CounterClockwise),
+                    ("menu".to_owned(), 0, Direction::None)
+                ],
+            }
+        );
+        #[cfg(feature = "json")]
+        assert_eq!(
+            serde_json::to_string(&snapshot).unwrap(),
+            r#"{"switches":[["button",true]],"rotaries":[["volume",1,"CounterClockwise"],["menu",0,"None"]]}"#
+        );
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    #[test]
+    fn test_registered_names() {
+        let mut menu = rotary("menu", 5, 6, Some(13));
+        menu.name_shifted = Some("menu_fast".to_owned());
+        let mut button = switch("button", 22);
+        button.name_long_press = Some("button_long".to_owned());
+        let input = PiInput::builder()
+            .add_rotary(rotary("volume", 17, 27, None))
+            .add_rotary(menu)
+            .add_switch(button)
+            .add_switch(switch("mute", 23))
+            .build_simulated()
+            .unwrap();
+        assert_eq!(
+            input.registered_names(),
+            vec![
+                "volume",
+                "menu",
+                "menu_fast",
+                "button",
+                "button_long",
+                "mute"
+            ]
+        );
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    #[test]
+    fn test_mock_gpio_event_stream() {
+        let mut button = switch("button", 22);
+        button.name_long_press = Some("button_long".to_owned());
+        let input = PiInput::builder()
+            .add_rotary(rotary("volume", 17, 27, None))
+            .add_switch(button)
+            .build_simulated()
+            .unwrap();
+        let events = input.event_stream();
+
+        input
+            .simulated_rotary("volume")
+            .unwrap()
+            .simulate_clockwise();
+        input.simulated_switch("button").unwrap().simulate_press();
+        input
+            .simulated_rotary("volume")
+            .unwrap()
+            .simulate_counter_clockwise();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                InputEvent::Rotary {
+                    name: "volume".to_owned(),
+                    direction: Direction::Clockwise
+                },
+                InputEvent::Switch {
+                    name: "button".to_owned(),
+                    pressed: true
+                },
+                InputEvent::Rotary {
+                    name: "volume".to_owned(),
+                    direction: Direction::CounterClockwise
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    #[test]
+    fn test_mock_gpio_shared_shift() {
+        let mut volume = rotary("volume", 17, 27, None);
+        volume.name_shifted = Some("balance".to_owned());
+        let mut menu = rotary("menu", 5, 6, None);
+        menu.name_shifted = Some("page".to_owned());
+        let input = PiInput::builder()
+            .add_rotary(volume)
+            .add_rotary(menu)
+            .shared_shift(13)
+            .build_simulated()
+            .unwrap();
+        let events = input.event_stream();
+        let shift = input.shared_shift().unwrap();
+
+        input
+            .simulated_rotary("volume")
+            .unwrap()
+            .simulate_clockwise();
+        shift.simulate_press();
+        input
+            .simulated_rotary("volume")
+            .unwrap()
+            .simulate_clockwise();
+        input.simulated_rotary("menu").unwrap().simulate_clockwise();
+        shift.simulate_release();
+        input.simulated_rotary("menu").unwrap().simulate_clockwise();
+
+        let names: Vec<String> = events
+            .try_iter()
+            .map(|event| match event {
+                InputEvent::Rotary { name, .. } | InputEvent::Switch { name, .. } => name,
+            })
+            .collect();
+        assert_eq!(names, vec!["volume", "balance", "page", "menu"]);
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    #[test]
+    fn test_mock_gpio_on_any() {
+        use std::sync::Mutex;
+
+        static EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let mut input = PiInput::builder()
+            .add_rotary(rotary("volume", 17, 27, None))
+            .add_switch(switch("button", 22))
+            .build_simulated()
+            .unwrap();
+        let events = input.event_stream();
+        input.on_any(
+            |name, direction| {
+                EVENTS
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {}", name, direction))
+            },
+            |name, pressed| EVENTS.lock().unwrap().push(format!("{} {}", name, pressed)),
+        );
+
+        input
+            .simulated_rotary("volume")
+            .unwrap()
+            .simulate_clockwise();
+        input.simulated_switch("button").unwrap().simulate_press();
+
+        assert_eq!(*EVENTS.lock().unwrap(), vec!["volume CW", "button true"]);
+        // listeners added before are kept
+        assert_eq!(events.try_iter().count(), 2);
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    #[test]
+    fn test_mock_gpio_long_press_threshold() {
+        use std::sync::Mutex;
+
+        static LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+        let mut button = switch("button", 22);
+        button.name_long_press = Some("button_long".to_owned());
+        button.time_threshold = Some(Duration::from_millis(20));
+        button.callback = |name, pressed| LOG.lock().unwrap().push((name.to_owned(), pressed));
+
+        let input = PiInput::simulated(InputConfig {
+            switches: vec![button],
+            ..Default::default()
+        })
+        .unwrap();
+        let button = input.simulated_switch("button").unwrap();
+        button.simulate_press();
+        button.simulate_release();
+        button.simulate_press();
+        std::thread::sleep(Duration::from_millis(50));
+        button.simulate_release();
+
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec![
+                ("button".to_owned(), true),
+                ("button".to_owned(), false),
+                ("button".to_owned(), true),
+                ("button_long".to_owned(), false)
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Recording input events as JSON lines and replaying them, e.g. to reproduce a bug report
+//! from a device in the field on a development machine.
+//!
+//! Every line holds one serialized [`InputEvent`]. Lines carry no timestamps, so a replay
+//! delivers all events at once, in their original order.
+
+use crate::rotary_encoder::Direction;
+use crate::{InputEvent, PiInput};
+
+use log::error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::{Arc, Mutex};
+
+impl PiInput {
+    /// Write every event of all encoders to `writer` as a JSON line, in addition to their
+    /// callbacks. Each line is flushed right away, so a log file is complete even if the
+    /// process is killed. Write errors are logged and the event is skipped.
+    pub fn tee_events<W: Write + Send + 'static>(&mut self, writer: W) {
+        let writer = Mutex::new(writer);
+        self.forward_events(Arc::new(move |event| {
+            let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = write_event(&mut *writer, &event) {
+                error!("Failed to write input event {:?}: {}", event, e);
+            }
+        }));
+    }
+}
+
+/// Read JSON lines written by [`PiInput::tee_events`] and pass each event to `rotary` or
+/// `switch`, as if it came from the hardware. Empty lines are skipped.
+///
+/// Returns the number of events replayed, or the first read or parse error.
+pub fn replay_events<R: Read>(
+    reader: R,
+    rotary: fn(&str, Direction),
+    switch: fn(&str, bool),
+) -> io::Result<usize> {
+    let mut count = 0;
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line)? {
+            InputEvent::Rotary { name, direction } => rotary(&name, direction),
+            InputEvent::Switch { name, pressed } => switch(&name, pressed),
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn write_event<W: Write>(writer: &mut W, event: &InputEvent) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, event)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_replay() {
+        static REPLAYED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let mut lines = Vec::new();
+        let events = [
+            InputEvent::Rotary {
+                name: "volume".to_owned(),
+                direction: Direction::CounterClockwise,
+            },
+            InputEvent::Switch {
+                name: "button".to_owned(),
+                pressed: true,
+            },
+        ];
+        for event in &events {
+            write_event(&mut lines, event).unwrap();
+        }
+        lines.extend_from_slice(b"\n");
+        assert_eq!(
+            String::from_utf8_lossy(&lines).lines().next(),
+            Some(r#"{"Rotary":{"name":"volume","direction":"CounterClockwise"}}"#)
+        );
+
+        let count = replay_events(
+            lines.as_slice(),
+            |name, direction| {
+                REPLAYED
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {}", name, direction))
+            },
+            |name, pressed| {
+                REPLAYED
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {}", name, pressed))
+            },
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(*REPLAYED.lock().unwrap(), vec!["volume CCW", "button true"]);
+    }
+
+    #[test]
+    fn test_replay_rejects_garbage() {
+        let err = replay_events(&b"not json\n"[..], |_, _| {}, |_, _| {}).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,83 +1,44 @@
-use std::time::Duration;
-
-use anyhow::Result;
-use log::{debug, trace};
-use rppal::gpio::Gpio;
-
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod accel;
+#[cfg(feature = "async")]
+pub mod async_encoder;
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
+mod coalesce;
+#[cfg(feature = "std")]
+mod dispatch;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod gesture;
+#[cfg(feature = "std")]
+mod input;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod matrix;
+#[cfg(feature = "std")]
+mod polling;
+pub mod quadrature;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "std")]
 pub mod rotary_encoder;
+#[cfg(feature = "std")]
+pub mod selector;
+#[cfg(feature = "std")]
+pub mod shift;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(feature = "std")]
 pub mod switch_encoder;
+#[cfg(feature = "std")]
+mod util;
 
-use rotary_encoder::Direction;
-
-#[allow(dead_code)]
-pub struct PiInput {
-    rot_encoders: Vec<rotary_encoder::Encoder>,
-    sw_encoders: Vec<switch_encoder::Encoder>,
-}
-
-#[derive(Debug)]
-pub enum EncoderType {
-    Rotary,
-    Switch,
-}
-
-#[derive(Debug)]
-pub struct SwitchDefinition {
-    pub name: String,
-    pub name_long_press: Option<String>,
-    pub sw_pin: u8,
-    pub callback: fn(&str, bool),
-    pub time_threshold: Option<Duration>,
-}
-
-#[derive(Debug)]
-pub struct RotaryDefinition {
-    pub name: String,
-    pub name_shifted: Option<String>,
-    pub sw_pin: Option<u8>,
-    pub dt_pin: u8,
-    pub clk_pin: u8,
-    pub callback: fn(&str, Direction),
-}
-
-impl PiInput {
-    pub fn new(switches: &[SwitchDefinition], rotaries: &[RotaryDefinition]) -> Result<Self> {
-        debug!("Initializing PiInput...");
-        let gpio = Gpio::new()?;
-
-        let rot_encoders = rotaries
-            .iter()
-            .map(|r| {
-                rotary_encoder::Encoder::new(
-                    &r.name,
-                    r.name_shifted.as_deref(),
-                    &gpio,
-                    r.dt_pin,
-                    r.clk_pin,
-                    r.sw_pin,
-                    r.callback,
-                )
-            })
-            .collect::<Result<Vec<rotary_encoder::Encoder>>>()?;
-
-        let sw_encoders = switches
-            .iter()
-            .map(|s| {
-                switch_encoder::Encoder::new(
-                    &s.name,
-                    s.name_long_press.as_deref(),
-                    &gpio,
-                    s.sw_pin,
-                    s.time_threshold,
-                    s.callback,
-                )
-            })
-            .collect::<Result<Vec<switch_encoder::Encoder>>>()?;
-
-        trace!("PiInput initialized");
-        Ok(Self {
-            rot_encoders,
-            sw_encoders,
-        })
-    }
-}
+#[cfg(feature = "std")]
+pub use error::EncoderError;
+#[cfg(feature = "std")]
+pub use input::*;
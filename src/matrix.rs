@@ -0,0 +1,234 @@
+//! Button matrices (keypads), scanning the columns whenever a row changes.
+//!
+//! Each button connects one row with one column, usually through a diode. The rows are inputs
+//! with pull-ups and interrupts, the columns are outputs held low while idle, so pressing any
+//! button pulls its row low and raises an interrupt. The handler then drives one column low at
+//! a time to find out which buttons are actually held and reports every change.
+//!
+//! While idle the rows only tell whether *any* button in them is held: pressing or releasing a
+//! second button in a row that already has one held down raises no interrupt. Such changes are
+//! picked up by the next scan, or right away with [`MatrixScanner::scan`].
+
+use crate::error::EncoderError;
+use crate::util::{SharedPins, catch_panic};
+
+use log::trace;
+use rppal::gpio::{Event, Gpio, InputPin, Level, OutputPin, Trigger};
+use std::thread;
+use std::time::Duration;
+
+/// Time for the rows to follow a column change before they are read
+const SETTLE: Duration = Duration::from_micros(10);
+
+pub struct MatrixScanner {
+    matrix: SharedPins<Matrix>,
+    callback: fn(usize, usize, bool),
+}
+
+/// Pins and last scanned state of a matrix
+struct Matrix {
+    rows: Vec<InputPin>,
+    cols: Vec<OutputPin>,
+    /// Row levels with all columns low after the last scan
+    idle: Vec<Level>,
+    /// Buttons held at the last scan, indexed by `row * cols + col`
+    pressed: Vec<bool>,
+}
+
+impl MatrixScanner {
+    /// Create a new matrix scanner
+    /// # Arguments
+    /// * `gpio` - Gpio instance to use for the matrix
+    /// * `row_pins` - GPIO pin numbers of the rows, read with pull-ups and interrupts
+    /// * `col_pins` - GPIO pin numbers of the columns, driven low while idle
+    /// * `callback` - Function to call with row index, column index and whether the
+    ///   button was pressed for every button that changed
+    pub fn new(
+        gpio: &Gpio,
+        row_pins: &[u8],
+        col_pins: &[u8],
+        callback: fn(usize, usize, bool),
+    ) -> Result<Self, EncoderError> {
+        trace!(
+            "Initializing GPIO for {}x{} matrix",
+            row_pins.len(),
+            col_pins.len()
+        );
+        validate_pins(row_pins, col_pins)?;
+
+        let rows = row_pins
+            .iter()
+            .map(|&p| Ok(gpio.get(p)?.into_input_pullup()))
+            .collect::<Result<Vec<InputPin>, EncoderError>>()?;
+        let cols = col_pins
+            .iter()
+            .map(|&p| Ok(gpio.get(p)?.into_output_low()))
+            .collect::<Result<Vec<OutputPin>, EncoderError>>()?;
+
+        let mut matrix = Matrix {
+            idle: Vec::new(),
+            pressed: vec![false; rows.len() * cols.len()],
+            rows,
+            cols,
+        };
+        // start from the buttons currently held without reporting them
+        matrix.scan();
+
+        let scanner = Self {
+            matrix: SharedPins::new(matrix),
+            callback,
+        };
+        scanner
+            .enable_callbacks()
+            .map_err(EncoderError::InterruptFailed)?;
+        trace!("Matrix initialized");
+        Ok(scanner)
+    }
+
+    /// Scan all buttons now and report the changes since the last scan
+    pub fn scan(&self) {
+        let changes = self.matrix.with(Matrix::scan);
+        report(self.callback, &changes);
+    }
+
+    /// Whether the button at `row`, `col` was held at the last scan
+    pub fn is_pressed(&self, row: usize, col: usize) -> bool {
+        self.matrix.with(|matrix| {
+            row < matrix.rows.len()
+                && col < matrix.cols.len()
+                && matrix.pressed[row * matrix.cols.len() + col]
+        })
+    }
+
+    fn enable_callbacks(&self) -> Result<(), rppal::gpio::Error> {
+        trace!("Enabling callbacks for matrix");
+        self.matrix.with(|matrix| {
+            for pin in matrix.rows.iter_mut() {
+                let matrix = self.matrix.handle();
+                let callback = self.callback;
+                pin.set_async_interrupt(Trigger::Both, None, move |_: Event| {
+                    if let Some(changes) = matrix.with(Matrix::scan_if_changed) {
+                        report(callback, &changes);
+                    }
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Matrix {
+    /// Scan unless the rows are at the levels the last scan left them in. Driving the columns
+    /// during a scan raises row interrupts itself, which must not start another scan.
+    fn scan_if_changed(&mut self) -> Vec<(usize, usize, bool)> {
+        if read_levels(&self.rows) == self.idle {
+            return Vec::new();
+        }
+        self.scan()
+    }
+
+    /// Drive one column low at a time and return the buttons that changed
+    fn scan(&mut self) -> Vec<(usize, usize, bool)> {
+        for col in self.cols.iter_mut() {
+            col.set_high();
+        }
+        let mut pressed = vec![false; self.pressed.len()];
+        let width = self.cols.len();
+        for (c, col) in self.cols.iter_mut().enumerate() {
+            col.set_low();
+            thread::sleep(SETTLE);
+            for (r, row) in self.rows.iter().enumerate() {
+                pressed[r * width + c] = row.read() == Level::Low;
+            }
+            col.set_high();
+        }
+        for col in self.cols.iter_mut() {
+            col.set_low();
+        }
+        thread::sleep(SETTLE);
+        self.idle = read_levels(&self.rows);
+
+        let changes = diff(&self.pressed, &pressed, width);
+        self.pressed = pressed;
+        changes
+    }
+}
+
+fn read_levels(pins: &[InputPin]) -> Vec<Level> {
+    pins.iter().map(|pin| pin.read()).collect()
+}
+
+/// Invoke the callback outside the lock, so it may use the scanner itself
+fn report(callback: fn(usize, usize, bool), changes: &[(usize, usize, bool)]) {
+    for &(row, col, pressed) in changes {
+        trace!("Matrix button {}/{} pressed: {}", row, col, pressed);
+        catch_panic(module_path!(), "matrix", || callback(row, col, pressed));
+    }
+}
+
+/// Buttons whose state differs between two scans as `(row, col, pressed)`,
+/// `width` being the number of columns
+fn diff(old: &[bool], new: &[bool], width: usize) -> Vec<(usize, usize, bool)> {
+    old.iter()
+        .zip(new)
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(i, (_, &pressed))| (i / width, i % width, pressed))
+        .collect()
+}
+
+fn validate_pins(row_pins: &[u8], col_pins: &[u8]) -> Result<(), EncoderError> {
+    if row_pins.is_empty() || col_pins.is_empty() {
+        return Err(EncoderError::NoPins(
+            "Matrix rows and columns each".to_owned(),
+        ));
+    }
+    let pins: Vec<(u8, String)> = row_pins
+        .iter()
+        .enumerate()
+        .map(|(i, &pin)| (pin, format!("matrix row {}", i)))
+        .chain(
+            col_pins
+                .iter()
+                .enumerate()
+                .map(|(i, &pin)| (pin, format!("matrix column {}", i))),
+        )
+        .collect();
+    for (i, (pin, name)) in pins.iter().enumerate() {
+        if let Some((_, first)) = pins[..i].iter().find(|(p, _)| p == pin) {
+            return Err(EncoderError::DuplicatePin {
+                pin: *pin,
+                first: first.clone(),
+                second: name.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        // 2 rows, 3 columns
+        let old = [false, true, false, false, false, false];
+        let new = [false, false, false, false, false, true];
+        assert_eq!(diff(&old, &new, 3), vec![(0, 1, false), (1, 2, true)]);
+        assert!(diff(&new, &new, 3).is_empty());
+    }
+
+    #[test]
+    fn test_validate_pins() {
+        assert!(validate_pins(&[5, 6], &[13, 19, 26]).is_ok());
+        assert_eq!(
+            validate_pins(&[], &[13]).unwrap_err().to_string(),
+            "Matrix rows and columns each needs at least one pin"
+        );
+        assert_eq!(
+            validate_pins(&[5, 6], &[13, 6]).unwrap_err().to_string(),
+            "GPIO pin 6 is used by both matrix row 1 and matrix column 1"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Polling edge source for rotary encoders, for systems where GPIO interrupts are
+//! unavailable or unreliable (some kernels and containers).
+//!
+//! A thread reads DT and CLK at a fixed interval and feeds every level change into the
+//! same decoder the interrupt handlers use. This costs CPU time even while the knob rests,
+//! and the interval must be shorter than the time between two edges of a fast turn,
+//! otherwise edges are lost. Timestamps are measured from the start of the polling thread
+//! instead of system boot.
+
+use crate::rotary_encoder::{Pin, Shared, Shift};
+use crate::util::spawn_named;
+
+use log::trace;
+use rppal::gpio::{InputPin, Level, Trigger};
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Thread polling DT and CLK, stopped and joined on drop
+pub(crate) struct Poller {
+    dt_pin: Arc<InputPin>,
+    clk_pin: Arc<InputPin>,
+    interval: Duration,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Poller {
+    pub(crate) fn spawn(
+        dt_pin: InputPin,
+        clk_pin: InputPin,
+        shift: Arc<Shift>,
+        shared: Arc<Shared>,
+        interval: Duration,
+    ) -> Self {
+        let dt_pin = Arc::new(dt_pin);
+        let clk_pin = Arc::new(clk_pin);
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = Arc::clone(&stop);
+        let (dt, clk) = (Arc::clone(&dt_pin), Arc::clone(&clk_pin));
+        let thread = spawn_named(&shared.name(), move || {
+            trace!(
+                target: shared.log_target(),
+                "Polling rotary encoder {} every {:?}",
+                shared.name(),
+                interval
+            );
+            let started = Instant::now();
+            let mut last = [dt.read(), clk.read()];
+            while !stop_clone.load(Ordering::Relaxed) {
+                thread::sleep(interval);
+                apply_levels(
+                    &shared,
+                    &mut last,
+                    [dt.read(), clk.read()],
+                    shift.pressed(),
+                    started.elapsed(),
+                );
+            }
+        });
+        Self {
+            dt_pin,
+            clk_pin,
+            interval,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Current levels of DT and CLK
+    pub(crate) fn levels(&self) -> (Level, Level) {
+        (self.dt_pin.read(), self.clk_pin.read())
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for Poller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poller")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Feed the level changes of DT and CLK since the last poll into the decoder as edges
+fn apply_levels(
+    shared: &Shared,
+    last: &mut [Level; 2],
+    levels: [Level; 2],
+    shifted: Option<bool>,
+    timestamp: Duration,
+) {
+    for (i, pin) in [Pin::Dt, Pin::Clk].into_iter().enumerate() {
+        if levels[i] == last[i] {
+            continue;
+        }
+        last[i] = levels[i];
+        let trigger = match levels[i] {
+            Level::Low => Trigger::FallingEdge,
+            Level::High => Trigger::RisingEdge,
+        };
+        shared.handle_edge(pin, trigger, shifted, timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotary_encoder::{Direction, untimed};
+    use std::sync::Mutex;
+
+    static LOG: Mutex<Vec<Direction>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_apply_levels() {
+        let shared = Shared::new(
+            "knob".to_owned(),
+            None,
+            false,
+            untimed(|_, direction| LOG.lock().unwrap().push(direction)),
+        );
+        let mut last = [Level::High, Level::High];
+        // [DT, CLK] levels of a clockwise detent, with an unchanged poll in between
+        for levels in [
+            [Level::High, Level::Low],
+            [Level::High, Level::Low],
+            [Level::Low, Level::Low],
+            [Level::Low, Level::High],
+            [Level::High, Level::High],
+        ] {
+            apply_levels(&shared, &mut last, levels, None, Duration::ZERO);
+        }
+        assert_eq!(*LOG.lock().unwrap(), vec![Direction::Clockwise]);
+    }
+}
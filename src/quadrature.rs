@@ -0,0 +1,647 @@
+//! Quadrature decoding independent of rppal and interrupts.
+//!
+//! [`QuadratureDecoder`] turns DT and CLK samples or edges into detents, so the decode logic
+//! can be driven by any GPIO backend, e.g. `embedded-hal` input pins read in a loop. The rppal
+//! [`Encoder`](crate::rotary_encoder::Encoder) feeds its interrupts through the same decoder.
+//!
+//! This module only depends on `core`: with `default-features = false` the crate is `no_std`
+//! and contains nothing else, e.g. for decoding on a bare-metal microcontroller.
+
+use atomic_enum::atomic_enum;
+use core::fmt;
+use thiserror::Error;
+
+/// Direction of rotation
+#[atomic_enum]
+#[derive(PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+    None,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Clockwise => "CW",
+            Direction::CounterClockwise => "CCW",
+            Direction::None => "None",
+        })
+    }
+}
+
+impl Direction {
+    /// Signed step of one detent in this direction, matching the position count of the
+    /// rppal encoder: `1` clockwise, `-1` counter-clockwise and `0` for none
+    pub const fn as_delta(self) -> i8 {
+        match self {
+            Direction::Clockwise => 1,
+            Direction::CounterClockwise => -1,
+            Direction::None => 0,
+        }
+    }
+}
+
+/// Compact encoding of a direction: 0 clockwise, 1 counter-clockwise, 2 none
+impl From<Direction> for u8 {
+    fn from(direction: Direction) -> Self {
+        direction as u8
+    }
+}
+
+impl TryFrom<u8> for Direction {
+    type Error = InvalidDirection;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Direction::Clockwise),
+            1 => Ok(Direction::CounterClockwise),
+            2 => Ok(Direction::None),
+            _ => Err(InvalidDirection(value)),
+        }
+    }
+}
+
+/// A `u8` outside the range of [`Direction`]'s compact encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("{0} is not a valid direction, expected 0-2")]
+pub struct InvalidDirection(pub u8);
+
+/// Input of a rotary encoder, e.g. the one whose edge completed a detent
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
+pub enum Pin {
+    Dt,
+    Clk,
+}
+
+/// Result of a single quadrature state transition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    /// Direction the encoder is turning in after the transition
+    pub direction: Direction,
+    /// Whether the transition completes a detent.
+    /// Triggering transitions are only valid if the previous direction equals `direction`.
+    pub trigger: bool,
+}
+
+const fn step(direction: Direction) -> Option<Transition> {
+    Some(Transition {
+        direction,
+        trigger: false,
+    })
+}
+
+const fn detent(direction: Direction) -> Option<Transition> {
+    Some(Transition {
+        direction,
+        trigger: true,
+    })
+}
+
+/// Quadrature decode table, indexed by `old_state << 2 | new_state`.
+///
+/// A state holds the DT level in bit 1 and the CLK level in bit 0, where `1` means the
+/// pin is pulled low. `0b00` is the resting (detent) position.
+/// `None` marks transitions that cannot happen with a single pin change.
+pub const QUADRATURE_TABLE: [Option<Transition>; 16] = [
+    None,                                // 0b0000: no change
+    step(Direction::Clockwise),          // 0b0001: resting position & turned right 1
+    step(Direction::CounterClockwise),   // 0b0010: resting position & turned left 1
+    None,                                // 0b0011: both pins changed
+    detent(Direction::CounterClockwise), // 0b0100: R1 or L3 position & turned left 1
+    None,                                // 0b0101: no change
+    None,                                // 0b0110: both pins changed
+    step(Direction::Clockwise),          // 0b0111: R1 or L3 position & turned right 1
+    detent(Direction::Clockwise),        // 0b1000: R3 or L1 position & turned right 1
+    None,                                // 0b1001: both pins changed
+    None,                                // 0b1010: no change
+    step(Direction::CounterClockwise),   // 0b1011: R3 or L1 position & turned left 1
+    None,                                // 0b1100: both pins changed
+    step(Direction::CounterClockwise),   // 0b1101: R2 or L2 position & turned left 1
+    step(Direction::Clockwise),          // 0b1110: R2 or L2 position & turned right 1
+    None,                                // 0b1111: no change
+];
+
+/// Run two full clockwise and two full counter-clockwise cycles through the decoder and
+/// check that every cycle completes exactly one detent in its direction and ends at rest.
+/// Guards [`QUADRATURE_TABLE`] against edits that silently break decoding; debug builds
+/// check it whenever an encoder is built.
+pub fn verify_decode_table() -> bool {
+    const CYCLES: [(Direction, [(Pin, u8); 4]); 2] = [
+        (
+            Direction::Clockwise,
+            [(Pin::Clk, 1), (Pin::Dt, 1), (Pin::Clk, 0), (Pin::Dt, 0)],
+        ),
+        (
+            Direction::CounterClockwise,
+            [(Pin::Dt, 1), (Pin::Clk, 1), (Pin::Dt, 0), (Pin::Clk, 0)],
+        ),
+    ];
+    CYCLES.iter().all(|(expected, edges)| {
+        let mut state = 0b00;
+        let mut direction = Direction::None;
+        (0..2).all(|_| {
+            let mut detents = 0;
+            let mut last = Direction::None;
+            for &(pin, level) in edges {
+                let Ok((new_state, new_direction, trigger)) =
+                    update_state(state, direction, pin, level)
+                else {
+                    return false;
+                };
+                state = new_state;
+                direction = new_direction;
+                if trigger {
+                    detents += 1;
+                    last = new_direction;
+                }
+            }
+            state == 0b00 && detents == 1 && last == *expected
+        })
+    })
+}
+
+/// A pin change the decoder cannot follow, see [`QUADRATURE_TABLE`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Invalid state transition: from {old_state:04b} / {old_direction:?} -> {transition:04b}")]
+pub struct InvalidTransition {
+    /// State before the change
+    pub old_state: u8,
+    /// Direction latched before the change
+    pub old_direction: Direction,
+    /// Index into [`QUADRATURE_TABLE`], `old_state << 2 | new_state`
+    pub transition: u8,
+}
+
+/// Quadrature state machine of one encoder, without any GPIO access.
+///
+/// Levels are passed as `bool`, `true` meaning the pin is pulled low (active), as read
+/// with `embedded_hal::digital::InputPin::is_low`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuadratureDecoder {
+    state: u8,
+    direction: Direction,
+}
+
+impl Default for QuadratureDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuadratureDecoder {
+    /// Decoder at rest, both pins released
+    pub const fn new() -> Self {
+        Self {
+            state: 0b00,
+            direction: Direction::None,
+        }
+    }
+
+    /// Decoder starting from the phase the encoder is parked at, e.g. between detents
+    pub const fn starting_at(dt: bool, clk: bool) -> Self {
+        Self {
+            state: phase_bits(dt, clk),
+            direction: Direction::None,
+        }
+    }
+
+    /// Rebuild a decoder from a state and direction previously taken from one
+    pub(crate) const fn from_parts(state: u8, direction: Direction) -> Self {
+        Self { state, direction }
+    }
+
+    /// Current state, DT in bit 1 and CLK in bit 0, `1` meaning pulled low
+    pub fn state(&self) -> u8 {
+        self.state
+    }
+
+    /// Direction the encoder is turning in, `None` at rest or after an ambiguous change
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Feed a sample of both pins, returning the direction of a completed detent.
+    ///
+    /// The decoder always follows the samples: if both pins changed since the last sample,
+    /// or the change does not fit the latched direction, the intermediate edges were missed
+    /// and the direction is reset, so no detent is reported for them.
+    pub fn update(&mut self, dt: bool, clk: bool) -> Option<Direction> {
+        let new_state = phase_bits(dt, clk);
+        let pin = match new_state ^ self.state {
+            0b00 => return None,
+            0b01 => Pin::Clk,
+            0b10 => Pin::Dt,
+            _ => {
+                *self = Self::from_parts(new_state, Direction::None);
+                return None;
+            }
+        };
+        let level = match pin {
+            Pin::Dt => dt,
+            Pin::Clk => clk,
+        };
+        self.edge(pin, level).unwrap_or_else(|_| {
+            *self = Self::from_parts(new_state, Direction::None);
+            None
+        })
+    }
+
+    /// Feed an edge of a single pin, e.g. from an interrupt, returning the direction of a
+    /// completed detent. An invalid transition leaves the decoder unchanged, the next valid
+    /// one brings it back in sync.
+    pub fn edge(&mut self, pin: Pin, low: bool) -> Result<Option<Direction>, InvalidTransition> {
+        let (state, direction, trigger) =
+            update_state(self.state, self.direction, pin, u8::from(low))?;
+        self.state = state;
+        self.direction = direction;
+        Ok(trigger.then_some(direction))
+    }
+
+    /// Run recorded `(pin, level)` edges through a decoder at rest, e.g. a capture of an
+    /// encoder that decodes wrongly, and return the decision for every edge: the latched
+    /// direction and whether the edge completed a detent. A rejected edge leaves the
+    /// decoder unchanged, as in [`QuadratureDecoder::edge`].
+    #[cfg(feature = "std")]
+    pub fn replay(events: &[(Pin, u8)]) -> std::vec::Vec<(Direction, bool)> {
+        let mut decoder = Self::new();
+        events
+            .iter()
+            .map(|&(pin, level)| {
+                let trigger = matches!(decoder.edge(pin, level == 1), Ok(Some(_)));
+                (decoder.direction(), trigger)
+            })
+            .collect()
+    }
+}
+
+/// State of the given levels, `true` meaning pulled low
+pub(crate) const fn phase_bits(dt: bool, clk: bool) -> u8 {
+    ((dt as u8) << 1) | clk as u8
+}
+
+/// Look up a transition in [`QUADRATURE_TABLE`], honouring the direction guard of
+/// triggering entries.
+fn decode(trans_state: u8, old_direction: Direction) -> Option<(Direction, bool)> {
+    match QUADRATURE_TABLE
+        .get(trans_state as usize)
+        .copied()
+        .flatten()
+    {
+        Some(t) if !t.trigger || t.direction == old_direction => Some((t.direction, t.trigger)),
+        _ => None,
+    }
+}
+
+/// State after `pin` changed to `level`, whether or not the transition is valid
+pub(crate) fn next_state(old_state: u8, pin: Pin, level: u8) -> u8 {
+    match pin {
+        Pin::Clk => (old_state & 0b10) + level,
+        Pin::Dt => (old_state & 0b01) + (level << 1),
+    }
+}
+
+/// Apply `pin` changing to `level` to `old_state`, returning the new state, the direction
+/// and whether a detent was completed
+fn update_state(
+    old_state: u8,
+    old_direction: Direction,
+    pin: Pin,
+    level: u8,
+) -> Result<(u8, Direction, bool), InvalidTransition> {
+    let new_state = next_state(old_state, pin, level);
+    let trans_state = (old_state << 2) + new_state;
+
+    let (direction, trigger) = decode(trans_state, old_direction).ok_or(InvalidTransition {
+        old_state,
+        old_direction,
+        transition: trans_state,
+    })?;
+    Ok((new_state, direction, trigger))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // the tests use std, also when testing the no_std decoder on its own
+    #[cfg(not(feature = "std"))]
+    extern crate std;
+    #[cfg(not(feature = "std"))]
+    use std::{string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn test_update_state_from_rest_clockwise() {
+        // From resting state (00), CLK goes high -> transition 0001
+        let result = update_state(0b00, Direction::None, Pin::Clk, 1);
+        assert!(result.is_ok());
+        let (new_state, direction, trigger) = result.unwrap();
+        assert_eq!(new_state, 0b01);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(!trigger);
+    }
+
+    #[test]
+    fn test_update_state_from_rest_counterclockwise() {
+        // From resting state (00), DT goes high -> transition 0010
+        let result = update_state(0b00, Direction::None, Pin::Dt, 1);
+        assert!(result.is_ok());
+        let (new_state, direction, trigger) = result.unwrap();
+        assert_eq!(new_state, 0b10);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(!trigger);
+    }
+
+    #[test]
+    fn test_update_state_clockwise_complete_rotation() {
+        // Simulate a complete clockwise rotation sequence: 00 -> 01 -> 11 -> 10 -> 00
+
+        // Step 1: 00 -> 01 (CLK high)
+        let (state, direction, trigger) = update_state(0b00, Direction::None, Pin::Clk, 1).unwrap();
+        assert_eq!(state, 0b01);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(!trigger);
+
+        // Step 2: 01 -> 11 (DT high) - transition 0111
+        let (state, direction, trigger) =
+            update_state(0b01, Direction::Clockwise, Pin::Dt, 1).unwrap();
+        assert_eq!(state, 0b11);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(!trigger);
+
+        // Step 3: 11 -> 10 (CLK low) - transition 1110
+        let (state, direction, trigger) =
+            update_state(0b11, Direction::Clockwise, Pin::Clk, 0).unwrap();
+        assert_eq!(state, 0b10);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(!trigger);
+
+        // Step 4: 10 -> 00 (DT low) - transition 1000, should trigger
+        let (state, direction, trigger) =
+            update_state(0b10, Direction::Clockwise, Pin::Dt, 0).unwrap();
+        assert_eq!(state, 0b00);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(trigger, "Should trigger callback on complete rotation");
+    }
+
+    #[test]
+    fn test_update_state_counterclockwise_complete_rotation() {
+        // Simulate a complete counter-clockwise rotation: 00 -> 10 -> 11 -> 01 -> 00
+
+        // Step 1: 00 -> 10 (DT high)
+        let (state, direction, trigger) = update_state(0b00, Direction::None, Pin::Dt, 1).unwrap();
+        assert_eq!(state, 0b10);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(!trigger);
+
+        // Step 2: 10 -> 11 (CLK high) - transition 1011
+        let (state, direction, trigger) =
+            update_state(0b10, Direction::CounterClockwise, Pin::Clk, 1).unwrap();
+        assert_eq!(state, 0b11);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(!trigger);
+
+        // Step 3: 11 -> 01 (DT low) - transition 1101
+        let (state, direction, trigger) =
+            update_state(0b11, Direction::CounterClockwise, Pin::Dt, 0).unwrap();
+        assert_eq!(state, 0b01);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(!trigger);
+
+        // Step 4: 01 -> 00 (CLK low) - transition 0100, should trigger
+        let (state, direction, trigger) =
+            update_state(0b01, Direction::CounterClockwise, Pin::Clk, 0).unwrap();
+        assert_eq!(state, 0b00);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(trigger, "Should trigger callback on complete rotation");
+    }
+
+    #[test]
+    fn test_update_state_transition_0111() {
+        // Transition 0111: from state 01, DT goes high
+        let (new_state, direction, trigger) =
+            update_state(0b01, Direction::Clockwise, Pin::Dt, 1).unwrap();
+        assert_eq!(new_state, 0b11);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(!trigger);
+    }
+
+    #[test]
+    fn test_update_state_transition_0100_trigger() {
+        // Transition 0100 with CCW direction should trigger
+        let (new_state, direction, trigger) =
+            update_state(0b01, Direction::CounterClockwise, Pin::Clk, 0).unwrap();
+        assert_eq!(new_state, 0b00);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(trigger);
+    }
+
+    #[test]
+    fn test_update_state_transition_1011() {
+        // Transition 1011: from state 10, CLK goes high
+        let (new_state, direction, trigger) =
+            update_state(0b10, Direction::CounterClockwise, Pin::Clk, 1).unwrap();
+        assert_eq!(new_state, 0b11);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(!trigger);
+    }
+
+    #[test]
+    fn test_update_state_transition_1000_trigger() {
+        // Transition 1000 with CW direction should trigger
+        let (new_state, direction, trigger) =
+            update_state(0b10, Direction::Clockwise, Pin::Dt, 0).unwrap();
+        assert_eq!(new_state, 0b00);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(trigger);
+    }
+
+    #[test]
+    fn test_update_state_transition_1101() {
+        // Transition 1101: from state 11, DT goes low
+        let (new_state, direction, trigger) =
+            update_state(0b11, Direction::CounterClockwise, Pin::Dt, 0).unwrap();
+        assert_eq!(new_state, 0b01);
+        assert_eq!(direction, Direction::CounterClockwise);
+        assert!(!trigger);
+    }
+
+    #[test]
+    fn test_update_state_transition_1110() {
+        // Transition 1110: from state 11, CLK goes low
+        let (new_state, direction, trigger) =
+            update_state(0b11, Direction::Clockwise, Pin::Clk, 0).unwrap();
+        assert_eq!(new_state, 0b10);
+        assert_eq!(direction, Direction::Clockwise);
+        assert!(!trigger);
+    }
+
+    #[test]
+    fn test_update_state_invalid_transition() {
+        // Test an invalid state transition (e.g., 0000)
+        let result = update_state(0b00, Direction::None, Pin::Clk, 0);
+        assert!(result.is_err(), "Transition 0000 should be invalid");
+    }
+
+    #[test]
+    fn test_update_state_pin_dt_updates_correct_bits() {
+        // DT pin should update bit 1 (second bit)
+        let (new_state, _, _) = update_state(0b00, Direction::None, Pin::Dt, 1).unwrap();
+        assert_eq!(new_state, 0b10, "DT=1 should set bit 1");
+
+        let (new_state, _, _) = update_state(0b11, Direction::Clockwise, Pin::Dt, 0).unwrap();
+        assert_eq!(new_state, 0b01, "DT=0 should clear bit 1");
+    }
+
+    #[test]
+    fn test_update_state_pin_clk_updates_correct_bits() {
+        // CLK pin should update bit 0 (first bit)
+        let (new_state, _, _) = update_state(0b00, Direction::None, Pin::Clk, 1).unwrap();
+        assert_eq!(new_state, 0b01, "CLK=1 should set bit 0");
+
+        let (new_state, _, _) = update_state(0b11, Direction::Clockwise, Pin::Clk, 0).unwrap();
+        assert_eq!(new_state, 0b10, "CLK=0 should clear bit 0");
+    }
+
+    /// The match arms `update_state` used before the table was introduced
+    fn legacy_decode(trans_state: u8, old_direction: Direction) -> Option<(Direction, bool)> {
+        match trans_state {
+            0b0001 => Some((Direction::Clockwise, false)),
+            0b0010 => Some((Direction::CounterClockwise, false)),
+            0b0111 => Some((Direction::Clockwise, false)),
+            0b0100 if old_direction == Direction::CounterClockwise => {
+                Some((Direction::CounterClockwise, true))
+            }
+            0b1011 => Some((Direction::CounterClockwise, false)),
+            0b1000 if old_direction == Direction::Clockwise => Some((Direction::Clockwise, true)),
+            0b1101 => Some((Direction::CounterClockwise, false)),
+            0b1110 => Some((Direction::Clockwise, false)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_quadrature_table_matches_legacy_decode() {
+        for trans_state in 0..16u8 {
+            for old_direction in [
+                Direction::Clockwise,
+                Direction::CounterClockwise,
+                Direction::None,
+            ] {
+                assert_eq!(
+                    decode(trans_state, old_direction),
+                    legacy_decode(trans_state, old_direction),
+                    "Mismatch for transition {:04b} / {:?}",
+                    trans_state,
+                    old_direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_decode_table() {
+        assert!(verify_decode_table());
+    }
+
+    #[test]
+    fn test_decoder_update_samples() {
+        let mut decoder = QuadratureDecoder::new();
+        // [DT, CLK] samples of a clockwise and a counter-clockwise detent, with repeats
+        let clockwise = [
+            (false, true),
+            (false, true),
+            (true, true),
+            (true, false),
+            (false, false),
+        ];
+        let detents: Vec<Direction> = clockwise
+            .iter()
+            .filter_map(|&(dt, clk)| decoder.update(dt, clk))
+            .collect();
+        assert_eq!(detents, vec![Direction::Clockwise]);
+        let counter_clockwise = [(true, false), (true, true), (false, true), (false, false)];
+        let detents: Vec<Direction> = counter_clockwise
+            .iter()
+            .filter_map(|&(dt, clk)| decoder.update(dt, clk))
+            .collect();
+        assert_eq!(detents, vec![Direction::CounterClockwise]);
+        assert_eq!(decoder.state(), 0b00);
+    }
+
+    #[test]
+    fn test_decoder_update_follows_skipped_phase() {
+        let mut decoder = QuadratureDecoder::new();
+        assert_eq!(decoder.update(false, true), None);
+        // both pins changed between two samples, the direction cannot be told
+        assert_eq!(decoder.update(true, false), None);
+        assert_eq!(decoder.state(), 0b10);
+        assert_eq!(decoder.direction(), Direction::None);
+        // returning to rest without a known direction completes no detent
+        assert_eq!(decoder.update(false, false), None);
+        assert_eq!(decoder, QuadratureDecoder::new());
+    }
+
+    #[test]
+    fn test_decoder_edge_rejects_invalid_transition() {
+        let mut decoder = QuadratureDecoder::starting_at(false, true);
+        let err = decoder.edge(Pin::Clk, true).unwrap_err();
+        assert_eq!(err.transition, 0b0101);
+        assert_eq!(
+            err.to_string(),
+            "Invalid state transition: from 0001 / None -> 0101"
+        );
+        assert_eq!(decoder, QuadratureDecoder::starting_at(false, true));
+    }
+
+    #[test]
+    fn test_decode_table_symmetric_in_pins() {
+        // swapping DT and CLK mirrors the rotation, so every transition must map to the one
+        // in the opposite direction: a detent completed by DT and one completed by CLK are
+        // decoded alike and trigger exactly once
+        let swap = |state: u8| ((state & 0b01) << 1) | ((state >> 1) & 0b01);
+        for index in 0..16u8 {
+            let mirrored = swap(index >> 2) << 2 | swap(index & 0b11);
+            let expected = QUADRATURE_TABLE[index as usize].map(|t| Transition {
+                direction: match t.direction {
+                    Direction::Clockwise => Direction::CounterClockwise,
+                    Direction::CounterClockwise => Direction::Clockwise,
+                    Direction::None => Direction::None,
+                },
+                trigger: t.trigger,
+            });
+            assert_eq!(
+                QUADRATURE_TABLE[mirrored as usize], expected,
+                "transition {:04b} vs {:04b}",
+                index, mirrored
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_replay() {
+        // a counter-clockwise detent, a bounce on CLK the decoder rejects, then half a turn back
+        let decisions = QuadratureDecoder::replay(&[
+            (Pin::Dt, 1),
+            (Pin::Clk, 1),
+            (Pin::Dt, 0),
+            (Pin::Clk, 0),
+            (Pin::Clk, 0),
+            (Pin::Clk, 1),
+            (Pin::Dt, 1),
+        ]);
+        assert_eq!(
+            decisions,
+            vec![
+                (Direction::CounterClockwise, false),
+                (Direction::CounterClockwise, false),
+                (Direction::CounterClockwise, false),
+                (Direction::CounterClockwise, true),
+                (Direction::CounterClockwise, false),
+                (Direction::Clockwise, false),
+                (Direction::Clockwise, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Recording the raw DT and CLK edges of a rotary encoder, e.g. to reproduce a decode bug
+//! reported from specific hardware with [`QuadratureDecoder::replay`].
+//!
+//! Every line of a capture holds one edge as `<pin> <level> <timestamp>`: the pin is `dt` or
+//! `clk`, the level `1` for pulled low and the timestamp is given in microseconds since boot,
+//! e.g. `clk 1 5123456`.
+
+use crate::quadrature::{Direction, Pin, QuadratureDecoder};
+
+use log::error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(doc)]
+use crate::rotary_encoder::EncoderBuilder;
+
+/// Writer of a capture, see [`EncoderBuilder::record_edges`]
+pub struct EdgeRecorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl EdgeRecorder {
+    /// Record edges to `writer`, e.g. a `File`. Each line is flushed right away, so the
+    /// capture is complete even if the process is killed.
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Append an edge. Write errors are logged and the edge is skipped.
+    pub(crate) fn record(&self, pin: Pin, level: u8, timestamp: Duration) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = write_edge(&mut *writer, pin, level, timestamp) {
+            error!("Failed to record {:?} edge: {}", pin, e);
+        }
+    }
+}
+
+fn write_edge<W: Write + ?Sized>(
+    writer: &mut W,
+    pin: Pin,
+    level: u8,
+    timestamp: Duration,
+) -> io::Result<()> {
+    let pin = match pin {
+        Pin::Dt => "dt",
+        Pin::Clk => "clk",
+    };
+    writeln!(writer, "{} {} {}", pin, level, timestamp.as_micros())?;
+    writer.flush()
+}
+
+/// Read a capture written by an [`EdgeRecorder`]. Empty lines are skipped.
+///
+/// Returns the edges in their original order, or the first read or parse error.
+pub fn read_capture<R: Read>(reader: R) -> io::Result<Vec<(Pin, u8, Duration)>> {
+    let mut edges = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        edges.push(parse_edge(&line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid edge in capture: {:?}", line),
+            )
+        })?);
+    }
+    Ok(edges)
+}
+
+fn parse_edge(line: &str) -> Option<(Pin, u8, Duration)> {
+    let mut fields = line.split_whitespace();
+    let pin = match fields.next()? {
+        "dt" => Pin::Dt,
+        "clk" => Pin::Clk,
+        _ => return None,
+    };
+    let level = fields.next()?.parse().ok().filter(|level| *level <= 1)?;
+    let timestamp = Duration::from_micros(fields.next()?.parse().ok()?);
+    fields.next().is_none().then_some((pin, level, timestamp))
+}
+
+/// Run a capture through a decoder at rest, see [`QuadratureDecoder::replay`]
+pub fn replay_capture<R: Read>(reader: R) -> io::Result<Vec<(Direction, bool)>> {
+    let edges: Vec<(Pin, u8)> = read_capture(reader)?
+        .into_iter()
+        .map(|(pin, level, _)| (pin, level))
+        .collect();
+    Ok(QuadratureDecoder::replay(&edges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Writer whose lines stay readable after it was moved into the recorder
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let buffer = SharedBuffer::default();
+        let recorder = EdgeRecorder::new(buffer.clone());
+        let edges = [(Pin::Clk, 1), (Pin::Dt, 1), (Pin::Clk, 0), (Pin::Dt, 0)];
+        for (i, (pin, level)) in edges.iter().enumerate() {
+            recorder.record(*pin, *level, Duration::from_micros(1000 + i as u64));
+        }
+        let capture = buffer.0.lock().unwrap().clone();
+        assert_eq!(
+            String::from_utf8_lossy(&capture).lines().next(),
+            Some("clk 1 1000")
+        );
+
+        let read = read_capture(capture.as_slice()).unwrap();
+        assert_eq!(read.len(), 4);
+        assert_eq!(read[3], (Pin::Dt, 0, Duration::from_micros(1003)));
+        assert_eq!(
+            replay_capture(capture.as_slice()).unwrap().last(),
+            Some(&(Direction::Clockwise, true))
+        );
+    }
+
+    #[test]
+    fn test_read_capture_rejects_garbage() {
+        for capture in ["dt 1\n", "sw 1 10\n", "clk 2 10\n", "clk 1 10 extra\n"] {
+            let err = read_capture(capture.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", capture);
+        }
+        assert!(read_capture(&b"\ndt 0 5\n"[..]).unwrap().len() == 1);
+    }
+}
@@ -1,37 +1,460 @@
 use rppal::gpio::{Event, Gpio, InputPin, Level, Trigger};
 
+use crate::accel::AccelProfile;
+use crate::clock::SystemClock;
+use crate::coalesce::Coalescer;
+use crate::dispatch::{self, DispatchQueue, Dispatcher};
+pub use crate::dispatch::{DISPATCH_QUEUE_CAPACITY, OverflowPolicy};
+use crate::error::EncoderError;
+use crate::polling::Poller;
+use crate::quadrature::{self, QuadratureDecoder};
+pub use crate::quadrature::{
+    AtomicDirection, Direction, InvalidDirection, Pin, QUADRATURE_TABLE, Transition,
+    verify_decode_table,
+};
+#[cfg(feature = "record")]
+use crate::record::EdgeRecorder;
+use crate::shift::SharedShift;
+use crate::util::{catch_panic, log_non_edge, trigger_to_level};
+
 use anyhow::{Result, anyhow};
-use atomic_enum::atomic_enum;
-use log::{error, trace};
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU8, Ordering};
-
-/// Direction of rotation
-#[atomic_enum]
-#[derive(PartialEq)]
-pub enum Direction {
-    Clockwise,
-    CounterClockwise,
+use atomic_time::AtomicOptionDuration;
+use log::{debug, error, trace};
+use std::any::Any;
+use std::fmt;
+use std::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicU8, AtomicU16, AtomicU32, AtomicU64, Ordering,
+};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+
+/// How DT and CLK edges are turned into detents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodeMode {
+    /// Interrupts on both edges of DT and CLK, fed through the quadrature state machine.
+    /// Tolerates bounce best, as every edge is validated against the previous state.
+    #[default]
+    DualInterrupt,
+    /// A single interrupt on the falling edge of CLK, reading DT's level in the handler:
+    /// DT still high means clockwise, DT already low counter-clockwise.
+    /// Needs a quarter of the interrupts and no ordering between two interrupt threads,
+    /// but relies on debouncing CLK, as every CLK bounce is reported as a detent.
+    ClkInterruptDtRead,
+}
+
+/// Where the callbacks of an encoder are run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dispatch {
+    /// In the interrupt thread, right when a detent completes. Lowest latency, but further
+    /// edges are only handled once all callbacks have returned, so callbacks must be fast.
+    #[default]
+    Inline,
+    /// In a dedicated worker thread, fed through a queue of [`DISPATCH_QUEUE_CAPACITY`] detents.
+    /// Slow callbacks no longer delay decoding, at the cost of a thread hand-over per detent.
+    /// If the queue is full, further detents are dropped, see [`EncoderBuilder::dispatch_queue`].
+    Threaded,
+}
+
+/// Event emitted by a rotary encoder
+#[derive(Debug, Clone, PartialEq)]
+pub enum RotaryEvent {
+    /// The encoder was turned by one detent,
+    /// `timestamp` is the time of the completing edge since system boot
+    Turned {
+        name: String,
+        direction: Direction,
+        timestamp: Duration,
+    },
+    /// The encoder was turned by one detent while its switch was held down.
+    /// Only emitted instead of `Turned` if enabled via [`EncoderBuilder::pressed_turn_events`].
+    PressedTurn {
+        name: String,
+        direction: Direction,
+        timestamp: Duration,
+    },
+    /// The encoder received `edges` edges without completing a detent, likely a wiring fault
+    Fault { name: String, edges: u32 },
+    /// The encoder was turned by `steps` detents in the same direction in quick succession.
+    /// Only emitted instead of `Turned` if enabled via [`EncoderBuilder::coalesce_window`].
+    Batched {
+        name: String,
+        direction: Direction,
+        steps: u32,
+    },
+}
+
+impl RotaryEvent {
+    /// Turn a detent into the event reported to event based consumers
+    /// # Arguments
+    /// * `base_name` - Unshifted name of the encoder
+    /// * `detent` - The completed detent
+    /// * `pressed_turn_events` - Report detents while pressed as [`RotaryEvent::PressedTurn`]
+    pub(crate) fn from_detent(base_name: &str, detent: &Detent, pressed_turn_events: bool) -> Self {
+        if pressed_turn_events && detent.pressed {
+            RotaryEvent::PressedTurn {
+                name: base_name.to_owned(),
+                direction: detent.direction,
+                timestamp: detent.timestamp,
+            }
+        } else {
+            RotaryEvent::Turned {
+                name: detent.name.to_owned(),
+                direction: detent.direction,
+                timestamp: detent.timestamp,
+            }
+        }
+    }
+
+    /// Signed number of detents the event moved the encoder, e.g. `-3` for three batched
+    /// counter-clockwise detents and `0` for faults
+    pub fn delta(&self) -> i32 {
+        match self {
+            RotaryEvent::Turned { direction, .. } | RotaryEvent::PressedTurn { direction, .. } => {
+                direction.as_delta().into()
+            }
+            RotaryEvent::Batched {
+                direction, steps, ..
+            } => i32::from(direction.as_delta()).saturating_mul(*steps as i32),
+            RotaryEvent::Fault { .. } => 0,
+        }
+    }
+}
+
+/// What happens when the position reaches a bound, see [`Encoder::set_bounds`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundMode {
+    /// Stay at the bound, e.g. for a volume
+    Clamp,
+    /// Continue at the opposite bound, e.g. for a menu
+    Wrap,
+}
+
+/// Inclusive range the position is kept in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    min: i32,
+    max: i32,
+    mode: BoundMode,
+}
+
+/// Position after moving `delta` detents from `position`, kept within `bounds` if set
+fn step_position(position: i32, delta: i32, bounds: Option<Bounds>) -> i32 {
+    let Some(Bounds { min, max, mode }) = bounds else {
+        return position.wrapping_add(delta);
+    };
+    let target = i64::from(position) + i64::from(delta);
+    let bounded = match mode {
+        BoundMode::Clamp => target.clamp(i64::from(min), i64::from(max)),
+        BoundMode::Wrap => {
+            let span = i64::from(max) - i64::from(min) + 1;
+            i64::from(min) + (target - i64::from(min)).rem_euclid(span)
+        }
+    };
+    // within min..=max, so it fits
+    bounded as i32
+}
+
+/// GPIO pin number of an encoder's data (DT) output. A type of its own, so DT and CLK
+/// cannot be swapped by accident, which would invert every reported direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct DtPin(pub u8);
+
+/// GPIO pin number of an encoder's clock (CLK) output, see [`DtPin`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct ClkPin(pub u8);
+
+/// GPIO pin number of an encoder's built-in switch (SW)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct SwPin(pub u8);
+
+impl From<u8> for DtPin {
+    fn from(pin: u8) -> Self {
+        Self(pin)
+    }
+}
+
+impl From<u8> for ClkPin {
+    fn from(pin: u8) -> Self {
+        Self(pin)
+    }
+}
+
+impl From<u8> for SwPin {
+    fn from(pin: u8) -> Self {
+        Self(pin)
+    }
+}
+
+/// GPIO pin numbers of an encoder, see [`Encoder::pins`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderPins {
+    pub dt: u8,
+    pub clk: u8,
+    pub sw: Option<u8>,
+    pub index: Option<u8>,
+}
+
+/// Snapshot of an encoder's counters, see [`Encoder::metrics`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderMetrics {
+    /// Edges received on DT and CLK
+    pub total_triggers: u64,
+    /// Detents turned clockwise, after applying `reversed`
+    pub clockwise: u64,
+    /// Detents turned counter-clockwise, after applying `reversed`
+    pub counter_clockwise: u64,
+    /// Edges rejected by the decoder as invalid transitions
+    pub rejected_transitions: u64,
+    /// Time of the most recent edge since system boot
+    pub last_event: Option<Duration>,
+    /// Detents dropped because the queue of [`Dispatch::Threaded`] was full
+    pub dropped_detents: u64,
+}
+
+/// Edges and detents seen while turning an encoder by one revolution, see
+/// [`Encoder::calibrate`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalibrationReport {
+    /// Edges received on DT and CLK, four per full quadrature cycle
+    pub edges: u64,
+    /// Full quadrature cycles decoded, i.e. the detents this crate reports per revolution
+    pub detents: u64,
+    /// Edges rejected by the decoder, a high share makes the other numbers unreliable
+    pub rejected: u64,
+}
+
+impl CalibrationReport {
+    /// Counters that changed from `before` to `after`
+    fn between(before: &EncoderMetrics, after: &EncoderMetrics) -> Self {
+        Self {
+            edges: after.total_triggers - before.total_triggers,
+            detents: (after.clockwise + after.counter_clockwise)
+                - (before.clockwise + before.counter_clockwise),
+            rejected: after.rejected_transitions - before.rejected_transitions,
+        }
+    }
+
+    /// Edges per reported detent, 4 for a clean signal. `None` if no detent was seen.
+    pub fn edges_per_detent(&self) -> Option<f64> {
+        (self.detents > 0).then(|| self.edges as f64 / self.detents as f64)
+    }
+
+    /// Edges per mechanical click, given the number of clicks felt during the revolution:
+    /// 4 if the encoder completes a quadrature cycle per click, 2 or 1 if it completes one
+    /// every second or fourth click. `None` if no click was given.
+    pub fn edges_per_click(&self, clicks: u32) -> Option<f64> {
+        (clicks > 0).then(|| self.edges as f64 / f64::from(clicks))
+    }
+}
+
+/// A completed detent as passed to the internal callbacks
+pub(crate) struct Detent<'a> {
+    /// Encoder name, the shifted name if the switch is held and a shifted name is configured
+    pub(crate) name: &'a str,
+    pub(crate) direction: Direction,
+    /// Time of the completing edge since system boot
+    pub(crate) timestamp: Duration,
+    /// Whether the switch was held down
+    pub(crate) pressed: bool,
+    /// Position after the detent, see [`Encoder::position`]
+    pub(crate) position: i32,
+    /// Steps the detent counts for, see [`EncoderBuilder::acceleration`]
+    pub(crate) steps: u8,
+}
+
+/// Callback invoked on every detent
+pub(crate) type Callback = Arc<dyn Fn(&Detent) + Send + Sync>;
+
+/// Diagnostic callback receiving every raw phase, see [`Encoder::enable_phase_trace`]
+type PhaseTrace = Arc<dyn Fn(&str, u8) + Send + Sync>;
+
+/// Keeps a phase trace installed, see [`Encoder::enable_phase_trace`].
+/// Dropping the guard removes the trace again.
+#[must_use = "dropping the guard removes the phase trace right away"]
+pub struct PhaseTraceGuard {
+    shared: Weak<Shared>,
+    trace: PhaseTrace,
+}
+
+impl Drop for PhaseTraceGuard {
+    fn drop(&mut self) {
+        // nothing to remove once the encoder is gone
+        if let Some(shared) = self.shared.upgrade() {
+            shared.remove_phase_trace(&self.trace);
+        }
+    }
+}
+
+pub(crate) fn untimed(callback: fn(&str, Direction)) -> Callback {
+    Arc::new(move |detent| callback(detent.name, detent.direction))
+}
+
+/// Callback additionally receiving whether the switch was held during the detent
+pub(crate) fn shift_aware(callback: fn(&str, Direction, bool)) -> Callback {
+    Arc::new(move |detent| callback(detent.name, detent.direction, detent.pressed))
+}
+
+/// Application data handed to callbacks registered with a context, downcast by the callback
+pub type Context = Arc<dyn Any + Send + Sync>;
+
+/// Callback additionally receiving `context`, see [`EncoderBuilder::context_callback`]
+fn with_context(context: Context, callback: fn(&str, Direction, &Context)) -> Callback {
+    Arc::new(move |detent| callback(detent.name, detent.direction, &context))
+}
+
+/// Callback receiving the signed steps of the detent instead of its direction
+fn stepped(callback: fn(&str, i8)) -> Callback {
+    Arc::new(move |detent| {
+        // at most 32 steps, see `AccelProfile`
+        callback(
+            detent.name,
+            detent.direction.as_delta() * detent.steps as i8,
+        )
+    })
+}
+
+/// Callback invoked only for detents in `direction`
+fn directional(direction: Direction, callback: fn(&str)) -> Callback {
+    Arc::new(move |detent| {
+        if detent.direction == direction {
+            callback(detent.name)
+        }
+    })
+}
+
+/// Sink receiving the events of an encoder, used to bridge them into channels
+pub(crate) type EventSink = Arc<dyn Fn(RotaryEvent) + Send + Sync>;
+
+/// Callback invoked with the encoder name and the number of edges seen without a detent
+pub(crate) type FaultCallback = Arc<dyn Fn(&str, u32) + Send + Sync>;
+
+/// Default number of edges without a detent before a fault is reported
+pub const DEFAULT_FAULT_THRESHOLD: u32 = 32;
+
+/// A rotary encoder on DT and CLK, with an optional switch for shifting.
+///
+/// `Encoder` is `Send` and `Sync`. The decoder state and counters are atomics and the
+/// callbacks sit behind a lock, so `&self` methods may be called from any thread while edges
+/// are decoded. Callbacks run in rppal's interrupt threads (or the dispatch thread, see
+/// [`Dispatch`]), so closures passed to the encoder must be `Send + Sync`.
+pub struct Encoder {
+    source: EdgeSource,
+    shift: Arc<Shift>,
+    index_pin: Option<InputPin>,
+    pins: EncoderPins,
+    debounce: Option<Duration>,
+    shared: Arc<Shared>,
+}
+
+/// Where an encoder learns whether its switch is held
+#[derive(Debug)]
+pub(crate) enum Shift {
+    /// No switch, so never shifted
     None,
+    /// The encoder's own switch pin, pulled low while pressed
+    Pin(InputPin),
+    /// A button shared with other encoders, see [`SharedShift`]
+    Shared(Arc<AtomicBool>),
+}
+
+impl Shift {
+    /// Whether the switch is held, `None` without a switch
+    pub(crate) fn pressed(&self) -> Option<bool> {
+        match self {
+            Shift::None => None,
+            Shift::Pin(pin) => Some(pin.read() == Level::Low),
+            Shift::Shared(pressed) => Some(pressed.load(Ordering::Acquire)),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
-pub enum Pin {
-    Dt,
-    Clk,
+/// Where the decoder's edges come from
+#[derive(Debug)]
+enum EdgeSource {
+    /// rppal interrupts on DT and CLK, see [`DecodeMode::DualInterrupt`]
+    Interrupts { dt_pin: InputPin, clk_pin: InputPin },
+    /// An rppal interrupt on CLK reading DT, see [`DecodeMode::ClkInterruptDtRead`]
+    ClkInterrupt {
+        dt_pin: Arc<InputPin>,
+        clk_pin: InputPin,
+    },
+    /// A thread reading DT and CLK at a fixed interval, stopped when dropped
+    Polling(#[allow(dead_code)] Poller),
 }
 
+/// Names reported for detents, swapped as a whole by [`Encoder::set_names`]
 #[derive(Debug)]
-pub struct Encoder {
-    name: Arc<String>,
-    name_shifted: Arc<Option<String>>,
-    dt_pin: InputPin,
-    clk_pin: InputPin,
-    sw_pin: Arc<Option<InputPin>>,
-    state: Arc<AtomicU8>,
-    direction: Arc<AtomicDirection>,
-    callback: Arc<fn(&str, Direction)>,
+struct Names {
+    name: Arc<str>,
+    shifted: Option<Arc<str>>,
+}
+
+/// Decoder state shared between the encoder and its interrupt handlers
+pub(crate) struct Shared {
+    names: RwLock<Arc<Names>>,
+    /// Quadrature state and latched direction, see [`pack`]. The state is relative to
+    /// `rest_state`, so the decoder always sees a detent ending at `0b00`.
+    decoder: AtomicU8,
+    /// Pin state at a detent, see [`EncoderBuilder::rest_state`]
+    rest_state: u8,
+    reversed: bool,
+    callbacks: RwLock<Vec<Callback>>,
+    on_error: Option<fn(&str, anyhow::Error)>,
+    on_detent: Option<fn(&str)>,
+    on_phase: Option<fn(&str, u8)>,
+    /// Installed by [`Encoder::enable_phase_trace`] for as long as their guard lives
+    phase_traces: RwLock<Vec<PhaseTrace>>,
+    /// Receives every edge instead of the callbacks receiving detents, see
+    /// [`EncoderBuilder::raw_edges`]
+    raw_edges: Option<fn(&str, Pin, u8)>,
+    #[cfg(feature = "record")]
+    recorder: Option<EdgeRecorder>,
+    on_trigger_pin: Option<fn(&str, Direction, Pin)>,
+    on_index: Option<fn(&str)>,
+    index_home: Option<i32>,
+    on_panic: Option<fn(&str, &str)>,
+    log_target: String,
+    dispatcher: Option<Dispatcher>,
+    edges_since_detent: AtomicU32,
+    fault_threshold: u32,
+    on_fault: Option<FaultCallback>,
+    skipped_states: AtomicU32,
+    reset_after: Option<Duration>,
+    last_edge: AtomicOptionDuration,
+    min_detent_interval: Option<Duration>,
+    last_detent: AtomicOptionDuration,
+    accel: AccelProfile,
+    /// Time of the last reported detent, the base of the acceleration
+    last_step: AtomicOptionDuration,
+    /// Direction of the last detent passed to the callbacks
+    last_triggered: AtomicDirection,
+    reversal_confirm: u8,
+    /// Direction of the spin and the opposite detents seen since, see [`next_spin`]
+    spin: AtomicU16,
+    enabled: AtomicBool,
+    position: AtomicI32,
+    bounds: RwLock<Option<Bounds>>,
+    total_triggers: AtomicU64,
+    clockwise: AtomicU64,
+    counter_clockwise: AtomicU64,
+    rejected_transitions: AtomicU64,
 }
 
 impl Encoder {
@@ -42,373 +465,2845 @@ impl Encoder {
     /// * `gpio` - Gpio instance to use for the encoder
     /// * `dt_pin` - GPIO pin number for data (DT) encoder signal
     /// * `clk_pin` - GPIO pin number for clock (CLK) encoder signal
+    /// * `sw_pin` - Optional GPIO pin number for the built-in switch used for shifting
     /// * `callback` - Function to call when the encoder is turned
+    ///
+    /// See [`Encoder::builder`] for a more readable way to configure an encoder. Encoders
+    /// wired the other way round are flipped with [`EncoderBuilder::reversed`], rejected
+    /// state transitions are reported to [`EncoderBuilder::on_error`].
     pub fn new(
         encoder_name: &str,
         encoder_name_shifted: Option<&str>,
         gpio: &Gpio,
-        dt_pin: u8,
-        clk_pin: u8,
-        sw_pin: Option<u8>,
+        dt_pin: DtPin,
+        clk_pin: ClkPin,
+        sw_pin: Option<SwPin>,
         callback: fn(&str, Direction),
-    ) -> Result<Self> {
-        trace!(
-            "Initializing GPIO for rotary encoder {}/{:?}",
-            encoder_name, encoder_name_shifted
-        );
+    ) -> Result<Self, EncoderError> {
+        let mut builder = Encoder::builder()
+            .name(encoder_name)
+            .dt_pin(dt_pin)
+            .clk_pin(clk_pin)
+            .callback(callback);
+        if let Some(name_shifted) = encoder_name_shifted {
+            builder = builder.shifted_name(name_shifted);
+        }
+        if let Some(sw_pin) = sw_pin {
+            builder = builder.switch_pin(sw_pin);
+        }
+        builder.build(gpio)
+    }
 
-        let dt = gpio.get(dt_pin)?.into_input_pullup();
-        let clk = gpio.get(clk_pin)?.into_input_pullup();
-        let sw = match sw_pin {
-            None => None,
-            Some(p) => Some(gpio.get(p)?.into_input_pullup()),
+    /// Create a new rotary encoder on already configured pins, e.g. with a bias other than
+    /// the pull-up [`Encoder::new`] applies. Only the interrupts are set up; the pins are
+    /// released when the encoder is dropped. The decoder expects the pins to read high
+    /// while at rest, as with pull-ups and contacts switching to ground.
+    /// # Arguments
+    /// * `encoder_name` - Name of the encoder
+    /// * `encoder_name_shifted` - Name of the encoder when pressed
+    /// * `dt_pin` - Input pin of the data (DT) encoder signal
+    /// * `clk_pin` - Input pin of the clock (CLK) encoder signal
+    /// * `sw_pin` - Optional input pin of the built-in switch used for shifting
+    /// * `callback` - Function to call when the encoder is turned
+    pub fn from_pins(
+        encoder_name: &str,
+        encoder_name_shifted: Option<&str>,
+        dt_pin: InputPin,
+        clk_pin: InputPin,
+        sw_pin: Option<InputPin>,
+        callback: fn(&str, Direction),
+    ) -> Result<Self, EncoderError> {
+        let mut builder = Encoder::builder()
+            .name(encoder_name)
+            .dt_pin(dt_pin.pin())
+            .clk_pin(clk_pin.pin())
+            .callback(callback);
+        if let Some(name_shifted) = encoder_name_shifted {
+            builder = builder.shifted_name(name_shifted);
+        }
+        if let Some(sw_pin) = &sw_pin {
+            builder = builder.switch_pin(sw_pin.pin());
+        }
+        builder.build_on(dt_pin, clk_pin, sw_pin, None)
+    }
+
+    /// Create a new rotary encoder that polls DT and CLK instead of using interrupts,
+    /// for systems where GPIO interrupts are unavailable. Polling costs CPU time even while
+    /// the knob rests and misses edges that are closer together than `interval`.
+    /// # Arguments
+    /// * `encoder_name` - Name of the encoder
+    /// * `encoder_name_shifted` - Name of the encoder when pressed
+    /// * `gpio` - Gpio instance to use for the encoder
+    /// * `dt_pin` - GPIO pin number for data (DT) encoder signal
+    /// * `clk_pin` - GPIO pin number for clock (CLK) encoder signal
+    /// * `sw_pin` - Optional GPIO pin number for the built-in switch used for shifting
+    /// * `callback` - Function to call when the encoder is turned
+    /// * `interval` - Time between two reads of the pins, e.g. 1ms
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_polling(
+        encoder_name: &str,
+        encoder_name_shifted: Option<&str>,
+        gpio: &Gpio,
+        dt_pin: DtPin,
+        clk_pin: ClkPin,
+        sw_pin: Option<SwPin>,
+        callback: fn(&str, Direction),
+        interval: Duration,
+    ) -> Result<Self, EncoderError> {
+        let mut builder = Encoder::builder()
+            .name(encoder_name)
+            .dt_pin(dt_pin)
+            .clk_pin(clk_pin)
+            .callback(callback)
+            .poll_interval(interval);
+        if let Some(name_shifted) = encoder_name_shifted {
+            builder = builder.shifted_name(name_shifted);
+        }
+        if let Some(sw_pin) = sw_pin {
+            builder = builder.switch_pin(sw_pin);
+        }
+        builder.build(gpio)
+    }
+
+    /// Register an additional function to call when the encoder is turned.
+    /// All callbacks are invoked in registration order.
+    pub fn add_callback(&mut self, callback: fn(&str, Direction)) {
+        self.shared.add_callback(untimed(callback));
+    }
+
+    /// Register a closure to call when the encoder is turned, e.g. to forward detents
+    pub(crate) fn add_detent_callback(&self, callback: Callback) {
+        self.shared.add_callback(callback);
+    }
+
+    /// Replace the callback the encoder was created with, keeping all added ones
+    pub(crate) fn replace_callback(&self, callback: Callback) {
+        self.shared.replace_callback(callback);
+    }
+
+    /// Register an additional function to call when the encoder is turned, also receiving
+    /// whether the switch was held, independent of the (shifted) name passed along
+    pub fn add_shifted_callback(&mut self, callback: fn(&str, Direction, bool)) {
+        self.shared.add_callback(shift_aware(callback));
+    }
+
+    /// Register an additional function to call when the encoder is turned, also receiving
+    /// `context`, see [`EncoderBuilder::context_callback`]
+    pub fn add_context_callback(
+        &mut self,
+        context: Context,
+        callback: fn(&str, Direction, &Context),
+    ) {
+        self.shared.add_callback(with_context(context, callback));
+    }
+
+    /// Register an additional function to call when the encoder is turned, receiving the
+    /// signed steps of the detent, see [`EncoderBuilder::delta_callback`]
+    pub fn add_delta_callback(&mut self, callback: fn(&str, i8)) {
+        self.shared.add_callback(stepped(callback));
+    }
+
+    /// Register a function to call only when the encoder is turned clockwise
+    pub fn on_clockwise(&mut self, callback: fn(&str)) {
+        self.shared
+            .add_callback(directional(Direction::Clockwise, callback));
+    }
+
+    /// Register a function to call only when the encoder is turned counter-clockwise
+    pub fn on_counter_clockwise(&mut self, callback: fn(&str)) {
+        self.shared
+            .add_callback(directional(Direction::CounterClockwise, callback));
+    }
+
+    /// Whether the built-in switch (or the shared shift button) is currently held down, read
+    /// directly from the pin. Returns `None` if no switch is configured.
+    pub fn is_shifted(&self) -> Option<bool> {
+        self.shift.pressed()
+    }
+
+    /// Name of the encoder. Shared with the interrupt handlers rather than borrowed, as
+    /// [`Encoder::set_names`] may swap it meanwhile.
+    pub fn name(&self) -> Arc<str> {
+        self.shared.name()
+    }
+
+    /// GPIO pin numbers the encoder was built with, e.g. to log a summary of a configuration
+    pub fn pins(&self) -> EncoderPins {
+        self.pins
+    }
+
+    /// Name of the encoder while the switch is pressed, if configured
+    pub fn shifted_name(&self) -> Option<Arc<str>> {
+        self.shared.shifted_name()
+    }
+
+    /// Report further detents under `name`, and under `shifted` while the switch is held,
+    /// without touching the GPIO, e.g. to switch between input layers of a modal UI.
+    /// Detents being reported meanwhile keep the names they started with.
+    /// `shifted` requires a switch pin, like the shifted name given at creation.
+    pub fn set_names(&self, name: &str, shifted: Option<&str>) {
+        self.shared.set_names(name, shifted);
+    }
+
+    /// Restart decoding from the levels DT and CLK are at right now, discarding a half
+    /// completed detent. Call it after the decoder may have missed edges it cannot detect,
+    /// e.g. when waking from a sleep mode with interrupts masked or after switching the
+    /// edge source; the next valid transition resyncs it as well, but may drop a detent.
+    pub fn resync(&self) {
+        let (dt, clk) = match &self.source {
+            EdgeSource::Interrupts { dt_pin, clk_pin } => (dt_pin.read(), clk_pin.read()),
+            EdgeSource::ClkInterrupt { dt_pin, clk_pin } => (dt_pin.read(), clk_pin.read()),
+            EdgeSource::Polling(poller) => poller.levels(),
         };
+        self.shared.resync(dt, clk);
+    }
+
+    /// Enable or disable the callbacks without releasing the pins.
+    /// The decoder keeps following the pins while disabled and stays in sync.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.shared.set_enabled(enabled);
+    }
 
-        let mut encoder = Self {
-            name: Arc::new(encoder_name.to_owned()),
-            name_shifted: Arc::new(encoder_name_shifted.map(|s| s.to_owned())),
-            dt_pin: dt,
-            clk_pin: clk,
-            sw_pin: Arc::new(sw),
-            state: Arc::new(AtomicU8::new(0)),
-            direction: Arc::new(AtomicDirection::new(Direction::None)),
-            callback: Arc::new(callback),
+    /// Position counted from the detents since creation, clockwise counting up.
+    /// Detents while disabled are not counted.
+    pub fn position(&self) -> i32 {
+        self.shared.position()
+    }
+
+    /// Direction of the detent currently being decoded, read without locking.
+    ///
+    /// This is the direction the decoder latched from the edges since the last rest position:
+    /// it changes as soon as the knob starts turning, before the detent is complete, and is
+    /// [`Direction::None`] once the decoder lost track, e.g. after bouncing back. It may
+    /// therefore differ from the last detent reported, see
+    /// [`Encoder::last_triggered_direction`].
+    pub fn current_direction(&self) -> Direction {
+        self.shared.current_direction()
+    }
+
+    /// Direction of the last detent passed to the callbacks, [`Direction::None`] before
+    /// the first one. Detents ignored while disabled or filtered out are not taken into account.
+    pub fn last_triggered_direction(&self) -> Direction {
+        self.shared.last_triggered_direction()
+    }
+
+    /// Move the position to `position`, e.g. to restore a menu selection.
+    /// Kept within the bounds if set.
+    pub fn set_position(&self, position: i32) {
+        let bounds = self.shared.bounds();
+        self.shared
+            .position
+            .store(step_position(position, 0, bounds), Ordering::Release);
+    }
+
+    /// Keep the position within `min..=max` (given in either order), so the encoder
+    /// directly selects e.g. one of 10 menu items with `set_bounds(0, 9, BoundMode::Wrap)`.
+    /// The current position is moved into the bounds.
+    pub fn set_bounds(&self, min: i32, max: i32, mode: BoundMode) {
+        let bounds = Bounds {
+            min: min.min(max),
+            max: min.max(max),
+            mode,
         };
+        *self
+            .shared
+            .bounds
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(bounds);
+        let _ =
+            self.shared
+                .position
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |position| {
+                    Some(step_position(position, 0, Some(bounds)))
+                });
+    }
 
-        encoder
-            .enable_callbacks()
-            .map_err(|e| anyhow!("Failed to enable callbacks: {}", e))?;
-        trace!(
-            "Rotary encoder {}/{:?} initialized",
-            encoder.name, encoder_name_shifted
+    /// Let the position count without bounds again
+    pub fn clear_bounds(&self) {
+        *self
+            .shared
+            .bounds
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Register a function to call with the name and the new position on every detent
+    pub fn add_position_callback(&mut self, callback: fn(&str, i32)) {
+        self.shared.add_callback(Arc::new(move |detent| {
+            callback(detent.name, detent.position)
+        }));
+    }
+
+    /// Number of edges that repeated the pin's previous level, i.e. the opposite edge was missed.
+    /// A growing count under rapid rotation means the interrupt handler is not keeping up.
+    ///
+    /// This counts lost edges on one pin, not double steps recovered by the decoder: the state
+    /// is updated one pin at a time, so a transition changing both bits never occurs.
+    pub fn skipped_count(&self) -> u32 {
+        self.shared.skipped_count()
+    }
+
+    /// Snapshot of the edges and detents seen so far, e.g. to monitor input health.
+    /// Detents are counted while disabled as well.
+    pub fn metrics(&self) -> EncoderMetrics {
+        self.shared.metrics()
+    }
+
+    /// Count the edges and detents of one revolution, blocking the calling thread for
+    /// `timeout` while the user turns the knob once around, e.g. to find out how many
+    /// quadrature cycles an unknown encoder completes per click.
+    pub fn calibrate(&self, timeout: Duration) -> CalibrationReport {
+        let before = self.metrics();
+        std::thread::sleep(timeout);
+        let report = CalibrationReport::between(&before, &self.metrics());
+        debug!(
+            target: &self.shared.log_target,
+            "Rotary encoder {} calibrated: {:?}",
+            self.shared.name(),
+            report
         );
-        Ok(encoder)
+        report
     }
 
-    fn update_state(
-        old_state: u8,
-        old_direction: Direction,
-        pin: Pin,
-        level: u8,
-    ) -> Result<(u8, Direction, bool)> {
-        let mut trigger = false;
-        let new_state = match pin {
-            Pin::Clk => (old_state & 0b10) + level,
-            Pin::Dt => (old_state & 0b01) + (level << 1),
-        };
-        let trans_state = (old_state << 2) + new_state;
-
-        let direction = match trans_state {
-            0b0001 => Direction::Clockwise, // Resting position & Turned right 1
-            0b0010 => Direction::CounterClockwise, // Resting position & Turned left 1
-            0b0111 => Direction::Clockwise, // R1 or L3 position & Turned right 1
-            0b0100 if old_direction == Direction::CounterClockwise => {
-                // R1 or L3 position & Turned left  1
-                trigger = true;
-                Direction::CounterClockwise
-            }
-            0b1011 => Direction::CounterClockwise, // R3 or L1 position & Turned left 1
-            0b1000 if old_direction == Direction::Clockwise => {
-                // R3 or L1 position & Turned right 1
-                trigger = true;
-                Direction::Clockwise
+    /// Call `trace` on every DT or CLK edge with the raw phase, like
+    /// [`EncoderBuilder::on_phase`], for as long as the returned guard lives. Dropping the
+    /// guard removes the trace again, so a diagnostic session does not leave a per-edge
+    /// callback installed.
+    pub fn enable_phase_trace(&self, trace: fn(&str, u8)) -> PhaseTraceGuard {
+        self.shared.add_phase_trace(trace)
+    }
+
+    /// Edges per second seen on DT and CLK during `window`, blocking the calling thread
+    /// meanwhile. Call it while nobody touches the knob: a resting encoder produces no edges,
+    /// so any rate above zero points at a marginal contact, a loose wire or interference
+    /// that better wiring or a debounce may cure.
+    pub fn measure_jitter(&self, window: Duration) -> f32 {
+        self.shared.measure_jitter(window)
+    }
+
+    /// Block until the next detent and return it as [`RotaryEvent::Turned`], or `Ok(None)`
+    /// once `timeout` passed without one; `None` waits forever. Meant for sequential flows
+    /// and interactive diagnostics where a persistent callback is overkill. The registered
+    /// callbacks are called as usual. Must not be called from within a callback.
+    pub fn wait_event(&self, timeout: Option<Duration>) -> Result<Option<RotaryEvent>> {
+        self.shared.wait_event(timeout)
+    }
+
+    /// Open the GPIO peripheral and create a single rotary encoder without switch.
+    /// The claimed pins keep the peripheral open for as long as the encoder lives.
+    ///
+    /// For several encoders open one `Gpio` and pass it to [`Encoder::new`] or
+    /// [`EncoderBuilder::build`] instead.
+    /// # Arguments
+    /// * `encoder_name` - Name of the encoder
+    /// * `dt_pin` - GPIO pin number for data (DT) encoder signal
+    /// * `clk_pin` - GPIO pin number for clock (CLK) encoder signal
+    /// * `callback` - Function to call when the encoder is turned
+    pub fn standalone(
+        encoder_name: &str,
+        dt_pin: DtPin,
+        clk_pin: ClkPin,
+        callback: fn(&str, Direction),
+    ) -> Result<Self, EncoderError> {
+        Encoder::builder()
+            .name(encoder_name)
+            .dt_pin(dt_pin)
+            .clk_pin(clk_pin)
+            .callback(callback)
+            .build(&Gpio::new()?)
+    }
+
+    /// Start configuring a new rotary encoder
+    pub fn builder() -> EncoderBuilder {
+        EncoderBuilder::default()
+    }
+
+    /// Ensure DT, CLK and the optional switch pin are all distinct,
+    /// rppal would otherwise fail with a less helpful "pin already in use" error
+    fn validate_pins(
+        dt_pin: u8,
+        clk_pin: u8,
+        sw_pin: Option<u8>,
+        index_pin: Option<u8>,
+    ) -> Result<(), EncoderError> {
+        let pins = [
+            (Some(dt_pin), "DT"),
+            (Some(clk_pin), "CLK"),
+            (sw_pin, "SW"),
+            (index_pin, "INDEX"),
+        ];
+        for (i, (pin, second)) in pins.iter().enumerate() {
+            let Some(pin) = *pin else {
+                continue;
+            };
+            if let Some((_, first)) = pins[..i].iter().find(|(p, _)| *p == Some(pin)) {
+                return Err(EncoderError::DuplicatePin {
+                    pin,
+                    first: (*first).to_owned(),
+                    second: (*second).to_owned(),
+                });
             }
-            0b1101 => Direction::CounterClockwise, // R2 or L2 position & Turned left 1
-            0b1110 => Direction::Clockwise,        // R2 or L2 position & Turned right 1
-            // this should not be possible with single pin transitions
-            // 0b1100 if old_direction != Direction::None => {
-            //     // R2 or L2 & Skipped an intermediate 01 or 10 state
-            //     trigger = true;
-            //     old_direction
-            // }
-            _ => Err(anyhow!(
-                "Invalid state transition: from {:04b} / {:?} -> {:04b}",
-                old_state,
-                old_direction,
-                trans_state
-            ))?,
-        };
-        Ok((new_state, direction, trigger))
+        }
+        Ok(())
     }
 
-    fn enable_callbacks(&mut self) -> Result<()> {
+    fn enable_callbacks(&mut self) -> Result<(), rppal::gpio::Error> {
         trace!(
+            target: &self.shared.log_target,
             "Enabling callbacks for rotary encoder {}/{:?}",
-            self.name, self.name_shifted
-        );
-
-        let state = HashMap::from([
-            (Pin::Dt, Arc::clone(&self.state)),
-            (Pin::Clk, Arc::clone(&self.state)),
-        ]);
-        let callback = HashMap::from([
-            (Pin::Dt, Arc::clone(&self.callback)),
-            (Pin::Clk, Arc::clone(&self.callback)),
-        ]);
-        let direction = HashMap::from([
-            (Pin::Dt, Arc::clone(&self.direction)),
-            (Pin::Clk, Arc::clone(&self.direction)),
-        ]);
-        let name = HashMap::from([
-            (Pin::Dt, Arc::clone(&self.name)),
-            (Pin::Clk, Arc::clone(&self.name)),
-        ]);
-        let name_shifted = HashMap::from([
-            (Pin::Dt, Arc::clone(&self.name_shifted)),
-            (Pin::Clk, Arc::clone(&self.name_shifted)),
-        ]);
-        let sw_pin = HashMap::from([
-            (Pin::Dt, Arc::clone(&self.sw_pin)),
-            (Pin::Clk, Arc::clone(&self.sw_pin)),
-        ]);
-
-        let interrupt_handler = Arc::new(move |event_trigger: Trigger, pin: Pin| {
-            let old_state = state[&pin].load(Ordering::SeqCst);
-            let old_direction = direction[&pin].load(Ordering::SeqCst);
-            if let Ok((new_state, new_direction, trigger)) = Encoder::update_state(
-                old_state,
-                old_direction,
-                pin,
-                match event_trigger {
-                    Trigger::RisingEdge => 0,
-                    Trigger::FallingEdge => 1,
-                    _ => {
-                        error!("Unexpected event trigger: {:?}", event_trigger);
-                        return;
-                    }
-                } as u8,
-            ) {
-                state[&pin].store(new_state, Ordering::SeqCst);
-                direction[&pin].store(new_direction, Ordering::SeqCst);
-                if trigger {
-                    match (name_shifted[&pin].as_ref(), sw_pin[&pin].as_ref()) {
-                        (None, None) => {
-                            trace!(
-                                "Rotary encoder {} turned {:?}, triggering callback (shift not sonfigured)",
-                                name[&pin], new_direction
-                            );
-                            callback[&pin](&name[&pin], new_direction);
-                        }
-                        (Some(name_shift), Some(sp)) => match sp.read() == Level::High {
-                            false => {
-                                trace!(
-                                    "Rotary encoder {:?} turned {:?}, triggering shifted callback",
-                                    name_shift, new_direction
-                                );
-                                callback[&pin](name_shift, new_direction);
-                            }
-                            true => {
-                                trace!(
-                                    "Rotary encoder {} turned {:?}, triggering callback",
-                                    name[&pin], new_direction
-                                );
-                                callback[&pin](&name[&pin], new_direction);
-                            }
-                        },
-                        (_, _) => {
-                            error!(
-                                "Both sw_pin (is {:?}) and name shifted (is {:?}) must be defined!",
-                                *name_shifted[&pin], *sw_pin[&pin]
-                            )
-                        }
-                    }
-                }
-            }
-        });
-        let handler_clone = Arc::clone(&interrupt_handler);
+            self.shared.name(),
+            self.shared.names().shifted
+        );
 
-        self.dt_pin
-            .set_async_interrupt(Trigger::Both, None, move |event: Event| {
-                handler_clone(event.trigger, Pin::Dt);
+        if let Some(index_pin) = &mut self.index_pin {
+            let shared = Arc::clone(&self.shared);
+            // the index output pulls the pin low once per revolution
+            index_pin.set_async_interrupt(Trigger::FallingEdge, None, move |_: Event| {
+                shared.handle_index();
             })?;
-
-        self.clk_pin
-            .set_async_interrupt(Trigger::Both, None, move |event: Event| {
-                interrupt_handler(event.trigger, Pin::Clk);
+        }
+        let (dt_pin, clk_pin) = match &mut self.source {
+            EdgeSource::Interrupts { dt_pin, clk_pin } => (dt_pin, clk_pin),
+            EdgeSource::ClkInterrupt { dt_pin, clk_pin } => {
+                let shared = Arc::clone(&self.shared);
+                let shift = Arc::clone(&self.shift);
+                let dt_pin = Arc::clone(dt_pin);
+                return clk_pin.set_async_interrupt(
+                    Trigger::FallingEdge,
+                    self.debounce,
+                    move |event: Event| {
+                        shared.handle_clk_edge(dt_pin.read(), shift.pressed(), event.timestamp);
+                    },
+                );
+            }
+            EdgeSource::Polling(_) => return Ok(()),
+        };
+        for (pin, input_pin) in [(Pin::Dt, dt_pin), (Pin::Clk, clk_pin)] {
+            let shared = Arc::clone(&self.shared);
+            let shift = Arc::clone(&self.shift);
+            input_pin.set_async_interrupt(Trigger::Both, self.debounce, move |event: Event| {
+                shared.handle_edge(pin, event.trigger, shift.pressed(), event.timestamp);
             })?;
+        }
 
         Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Shared {
+    pub(crate) fn new(
+        name: String,
+        name_shifted: Option<String>,
+        reversed: bool,
+        callback: Callback,
+    ) -> Self {
+        Self {
+            names: RwLock::new(Arc::new(Names {
+                name: name.into(),
+                shifted: name_shifted.map(Arc::from),
+            })),
+            decoder: AtomicU8::new(pack(0b00, Direction::None)),
+            reversed,
+            callbacks: RwLock::new(vec![callback]),
+            on_error: None,
+            on_detent: None,
+            on_phase: None,
+            phase_traces: RwLock::new(Vec::new()),
+            raw_edges: None,
+            #[cfg(feature = "record")]
+            recorder: None,
+            rest_state: 0b00,
+            on_trigger_pin: None,
+            on_index: None,
+            index_home: None,
+            on_panic: None,
+            log_target: module_path!().to_owned(),
+            dispatcher: None,
+            edges_since_detent: AtomicU32::new(0),
+            fault_threshold: DEFAULT_FAULT_THRESHOLD,
+            on_fault: None,
+            skipped_states: AtomicU32::new(0),
+            reset_after: None,
+            last_edge: AtomicOptionDuration::new(None),
+            min_detent_interval: None,
+            last_detent: AtomicOptionDuration::new(None),
+            accel: AccelProfile::None,
+            last_step: AtomicOptionDuration::new(None),
+            last_triggered: AtomicDirection::new(Direction::None),
+            reversal_confirm: 0,
+            spin: AtomicU16::new(spin_of(Direction::None)),
+            enabled: AtomicBool::new(true),
+            position: AtomicI32::new(0),
+            bounds: RwLock::new(None),
+            total_triggers: AtomicU64::new(0),
+            clockwise: AtomicU64::new(0),
+            counter_clockwise: AtomicU64::new(0),
+            rejected_transitions: AtomicU64::new(0),
+        }
+    }
 
-    #[test]
-    fn test_update_state_from_rest_clockwise() {
-        // From resting state (00), CLK goes high -> transition 0001
-        let result = Encoder::update_state(0b00, Direction::None, Pin::Clk, 1);
-        assert!(result.is_ok());
-        let (new_state, direction, trigger) = result.unwrap();
-        assert_eq!(new_state, 0b01);
-        assert_eq!(direction, Direction::Clockwise);
-        assert_eq!(trigger, false);
+    /// Start decoding from the phase the encoder is parked at instead of assuming rest,
+    /// so the first edges after startup are not rejected as invalid transitions
+    pub(crate) fn seed(&mut self, dt: Level, clk: Level) {
+        let state = phase(dt, clk) ^ self.rest_state;
+        if state != 0b00 {
+            debug!(
+                target: &self.log_target,
+                "Rotary encoder {} starts between detents (state {:02b})",
+                self.name(), state
+            );
+        }
+        *self.decoder.get_mut() = pack(state, Direction::None);
     }
 
-    #[test]
-    fn test_update_state_from_rest_counterclockwise() {
-        // From resting state (00), DT goes high -> transition 0010
-        let result = Encoder::update_state(0b00, Direction::None, Pin::Dt, 1);
-        assert!(result.is_ok());
-        let (new_state, direction, trigger) = result.unwrap();
-        assert_eq!(new_state, 0b10);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert_eq!(trigger, false);
+    /// Jump to the phase of the given levels with no direction latched, see [`Encoder::resync`]
+    pub(crate) fn resync(&self, dt: Level, clk: Level) {
+        let state = phase(dt, clk) ^ self.rest_state;
+        let (old_state, old_direction) = unpack(
+            self.decoder
+                .swap(pack(state, Direction::None), Ordering::AcqRel),
+        );
+        self.edges_since_detent.store(0, Ordering::Relaxed);
+        debug!(
+            target: &self.log_target,
+            "Rotary encoder {} resynced from state {:02b} ({:?}) to {:02b}",
+            self.name(), old_state, old_direction, state
+        );
     }
 
-    #[test]
-    fn test_update_state_clockwise_complete_rotation() {
-        // Simulate a complete clockwise rotation sequence: 00 -> 01 -> 11 -> 10 -> 00
+    /// Log under `target` instead of the module path
+    pub(crate) fn set_log_target(&mut self, target: &str) {
+        self.log_target = target.to_owned();
+    }
 
-        // Step 1: 00 -> 01 (CLK high)
-        let (state, direction, trigger) =
-            Encoder::update_state(0b00, Direction::None, Pin::Clk, 1).unwrap();
-        assert_eq!(state, 0b01);
-        assert_eq!(direction, Direction::Clockwise);
-        assert!(!trigger);
+    pub(crate) fn set_on_phase(&mut self, on_phase: fn(&str, u8)) {
+        self.on_phase = Some(on_phase);
+    }
 
-        // Step 2: 01 -> 11 (DT high) - transition 0111
-        let (state, direction, trigger) =
-            Encoder::update_state(0b01, Direction::Clockwise, Pin::Dt, 1).unwrap();
-        assert_eq!(state, 0b11);
-        assert_eq!(direction, Direction::Clockwise);
-        assert!(!trigger);
+    /// Decode relative to `rest_state`, must be set before [`Shared::seed`]
+    pub(crate) fn set_rest_state(&mut self, rest_state: u8) {
+        self.rest_state = rest_state & 0b11;
+    }
 
-        // Step 3: 11 -> 10 (CLK low) - transition 1110
-        let (state, direction, trigger) =
-            Encoder::update_state(0b11, Direction::Clockwise, Pin::Clk, 0).unwrap();
-        assert_eq!(state, 0b10);
-        assert_eq!(direction, Direction::Clockwise);
-        assert!(!trigger);
+    pub(crate) fn set_raw_edges(&mut self, raw_edges: fn(&str, Pin, u8)) {
+        self.raw_edges = Some(raw_edges);
+    }
 
-        // Step 4: 10 -> 00 (DT low) - transition 1000, should trigger
-        let (state, direction, trigger) =
-            Encoder::update_state(0b10, Direction::Clockwise, Pin::Dt, 0).unwrap();
-        assert_eq!(state, 0b00);
-        assert_eq!(direction, Direction::Clockwise);
-        assert!(trigger, "Should trigger callback on complete rotation");
+    pub(crate) fn set_on_trigger_pin(&mut self, on_trigger_pin: fn(&str, Direction, Pin)) {
+        self.on_trigger_pin = Some(on_trigger_pin);
     }
 
-    #[test]
-    fn test_update_state_counterclockwise_complete_rotation() {
-        // Simulate a complete counter-clockwise rotation: 00 -> 10 -> 11 -> 01 -> 00
+    pub(crate) fn set_on_detent(&mut self, on_detent: fn(&str)) {
+        self.on_detent = Some(on_detent);
+    }
 
-        // Step 1: 00 -> 10 (DT high)
-        let (state, direction, trigger) =
-            Encoder::update_state(0b00, Direction::None, Pin::Dt, 1).unwrap();
-        assert_eq!(state, 0b10);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert!(!trigger);
+    pub(crate) fn add_callback(&self, callback: Callback) {
+        self.callbacks
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(callback);
+    }
 
-        // Step 2: 10 -> 11 (CLK high) - transition 1011
-        let (state, direction, trigger) =
-            Encoder::update_state(0b10, Direction::CounterClockwise, Pin::Clk, 1).unwrap();
-        assert_eq!(state, 0b11);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert!(!trigger);
+    /// Replace the callback passed to [`Shared::new`], which always comes first
+    pub(crate) fn replace_callback(&self, callback: Callback) {
+        self.callbacks.write().unwrap_or_else(|e| e.into_inner())[0] = callback;
+    }
 
-        // Step 3: 11 -> 01 (DT low) - transition 1101
-        let (state, direction, trigger) =
-            Encoder::update_state(0b11, Direction::CounterClockwise, Pin::Dt, 0).unwrap();
-        assert_eq!(state, 0b01);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert!(!trigger);
+    /// Call `trace` with the raw phase of every edge until the returned guard is dropped
+    pub(crate) fn add_phase_trace(self: &Arc<Self>, trace: fn(&str, u8)) -> PhaseTraceGuard {
+        let trace: PhaseTrace = Arc::new(trace);
+        self.phase_traces
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::clone(&trace));
+        PhaseTraceGuard {
+            shared: Arc::downgrade(self),
+            trace,
+        }
+    }
 
-        // Step 4: 01 -> 00 (CLK low) - transition 0100, should trigger
-        let (state, direction, trigger) =
-            Encoder::update_state(0b01, Direction::CounterClockwise, Pin::Clk, 0).unwrap();
-        assert_eq!(state, 0b00);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert!(trigger, "Should trigger callback on complete rotation");
+    fn remove_phase_trace(&self, trace: &PhaseTrace) {
+        self.phase_traces
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|t| !Arc::ptr_eq(t, trace));
     }
 
-    #[test]
-    fn test_update_state_transition_0111() {
-        // Transition 0111: from state 01, DT goes high
-        let (new_state, direction, trigger) =
-            Encoder::update_state(0b01, Direction::Clockwise, Pin::Dt, 1).unwrap();
-        assert_eq!(new_state, 0b11);
-        assert_eq!(direction, Direction::Clockwise);
-        assert!(!trigger);
+    /// Unregister a callback added with [`Shared::add_callback`]
+    fn remove_callback(&self, callback: &Callback) {
+        self.callbacks
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|c| !Arc::ptr_eq(c, callback));
     }
 
-    #[test]
-    fn test_update_state_transition_0100_trigger() {
-        // Transition 0100 with CCW direction should trigger
-        let (new_state, direction, trigger) =
-            Encoder::update_state(0b01, Direction::CounterClockwise, Pin::Clk, 0).unwrap();
-        assert_eq!(new_state, 0b00);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert!(trigger);
+    /// Wait for the next detent through a temporary callback, see [`Encoder::wait_event`]
+    pub(crate) fn wait_event(&self, timeout: Option<Duration>) -> Result<Option<RotaryEvent>> {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let callback: Callback = Arc::new(move |detent| {
+            // only the first detent is waited for, later ones find the channel full
+            let _ = sender.try_send(RotaryEvent::from_detent(detent.name, detent, false));
+        });
+        self.add_callback(Arc::clone(&callback));
+        let received = match timeout {
+            Some(timeout) => receiver.recv_timeout(timeout),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        self.remove_callback(&callback);
+        match received {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!(
+                "Rotary encoder {} stopped reporting detents",
+                self.name()
+            )),
+        }
     }
 
-    #[test]
-    fn test_update_state_transition_1011() {
-        // Transition 1011: from state 10, CLK goes high
-        let (new_state, direction, trigger) =
-            Encoder::update_state(0b10, Direction::CounterClockwise, Pin::Clk, 1).unwrap();
-        assert_eq!(new_state, 0b11);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert!(!trigger);
+    /// Queue detents for a worker thread instead of running the callbacks, see
+    /// [`Dispatch::Threaded`]. The worker is started with [`Shared::spawn_dispatcher`].
+    fn queue_detents(&mut self, capacity: usize, policy: OverflowPolicy) -> Arc<DispatchQueue> {
+        let queue = Arc::new(DispatchQueue::new(capacity, policy, &self.log_target));
+        self.dispatcher = Some(Dispatcher::new(&queue));
+        queue
     }
 
-    #[test]
-    fn test_update_state_transition_1000_trigger() {
-        // Transition 1000 with CW direction should trigger
-        let (new_state, direction, trigger) =
-            Encoder::update_state(0b10, Direction::Clockwise, Pin::Dt, 0).unwrap();
-        assert_eq!(new_state, 0b00);
-        assert_eq!(direction, Direction::Clockwise);
-        assert!(trigger);
+    /// Start the worker running the callbacks of detents queued by [`Shared::queue_detents`]
+    fn spawn_dispatcher(shared: &Arc<Shared>, queue: Arc<DispatchQueue>) {
+        dispatch::spawn_worker(&shared.name(), Arc::downgrade(shared), queue);
     }
 
-    #[test]
-    fn test_update_state_transition_1101() {
-        // Transition 1101: from state 11, DT goes low
-        let (new_state, direction, trigger) =
-            Encoder::update_state(0b11, Direction::CounterClockwise, Pin::Dt, 0).unwrap();
-        assert_eq!(new_state, 0b01);
-        assert_eq!(direction, Direction::CounterClockwise);
-        assert!(!trigger);
+    /// Invoke all registered callbacks in registration order
+    pub(crate) fn notify(&self, detent: &Detent) {
+        for callback in self
+            .callbacks
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            // the decoder state is already updated, a panic only skips this callback
+            self.guarded(detent.name, || callback(detent));
+        }
     }
 
-    #[test]
-    fn test_update_state_transition_1110() {
-        // Transition 1110: from state 11, CLK goes low
-        let (new_state, direction, trigger) =
-            Encoder::update_state(0b11, Direction::Clockwise, Pin::Clk, 0).unwrap();
-        assert_eq!(new_state, 0b10);
-        assert_eq!(direction, Direction::Clockwise);
-        assert!(!trigger);
+    /// Call a user callback or hook, catching and reporting a panic, see [`catch_panic`]
+    fn guarded(&self, name: &str, callback: impl FnOnce()) {
+        if let Some(message) = catch_panic(&self.log_target, name, callback)
+            && let Some(on_panic) = self.on_panic
+        {
+            on_panic(name, &message);
+        }
     }
 
-    #[test]
-    fn test_update_state_invalid_transition() {
-        // Test an invalid state transition (e.g., 0000)
-        let result = Encoder::update_state(0b00, Direction::None, Pin::Clk, 0);
-        assert!(result.is_err(), "Transition 0000 should be invalid");
+    /// Current names, kept alive by the `Arc` even if they are swapped meanwhile
+    fn names(&self) -> Arc<Names> {
+        Arc::clone(&self.names.read().unwrap_or_else(|e| e.into_inner()))
     }
 
-    #[test]
-    fn test_update_state_pin_dt_updates_correct_bits() {
-        // DT pin should update bit 1 (second bit)
-        let (new_state, _, _) = Encoder::update_state(0b00, Direction::None, Pin::Dt, 1).unwrap();
-        assert_eq!(new_state, 0b10, "DT=1 should set bit 1");
+    pub(crate) fn name(&self) -> Arc<str> {
+        Arc::clone(&self.names().name)
+    }
 
-        let (new_state, _, _) =
-            Encoder::update_state(0b11, Direction::Clockwise, Pin::Dt, 0).unwrap();
-        assert_eq!(new_state, 0b01, "DT=0 should clear bit 1");
+    pub(crate) fn shifted_name(&self) -> Option<Arc<str>> {
+        self.names().shifted.clone()
     }
 
-    #[test]
-    fn test_update_state_pin_clk_updates_correct_bits() {
-        // CLK pin should update bit 0 (first bit)
-        let (new_state, _, _) = Encoder::update_state(0b00, Direction::None, Pin::Clk, 1).unwrap();
-        assert_eq!(new_state, 0b01, "CLK=1 should set bit 0");
+    pub(crate) fn set_names(&self, name: &str, shifted: Option<&str>) {
+        *self.names.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(Names {
+            name: Arc::from(name),
+            shifted: shifted.map(Arc::from),
+        });
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        *self.bounds.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn log_target(&self) -> &str {
+        &self.log_target
+    }
+
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
 
-        let (new_state, _, _) =
-            Encoder::update_state(0b11, Direction::Clockwise, Pin::Clk, 0).unwrap();
-        assert_eq!(new_state, 0b10, "CLK=0 should clear bit 0");
+    pub(crate) fn skipped_count(&self) -> u32 {
+        self.skipped_states.load(Ordering::Relaxed)
+    }
+
+    /// Count the edges of the interrupt handlers during `window`, see [`Encoder::measure_jitter`]
+    pub(crate) fn measure_jitter(&self, window: Duration) -> f32 {
+        let before = self.total_triggers.load(Ordering::Relaxed);
+        std::thread::sleep(window);
+        let edges = self.total_triggers.load(Ordering::Relaxed) - before;
+        if edges > 0 {
+            debug!(
+                target: &self.log_target,
+                "Rotary encoder {} jittered at rest: {} edges in {:?}",
+                self.name(), edges, window
+            );
+        }
+        edge_rate(edges, window)
+    }
+
+    pub(crate) fn metrics(&self) -> EncoderMetrics {
+        // independent counters, so a snapshot taken during an edge may be off by one
+        EncoderMetrics {
+            total_triggers: self.total_triggers.load(Ordering::Relaxed),
+            clockwise: self.clockwise.load(Ordering::Relaxed),
+            counter_clockwise: self.counter_clockwise.load(Ordering::Relaxed),
+            rejected_transitions: self.rejected_transitions.load(Ordering::Relaxed),
+            last_event: self.last_edge.load(Ordering::Acquire),
+            dropped_detents: self.dispatcher.as_ref().map_or(0, Dispatcher::dropped),
+        }
+    }
+
+    /// Count edges since the last detent and report a fault once the threshold is reached,
+    /// as a decoder that never returns to rest produces no detents at all
+    fn watchdog(&self, triggered: bool) {
+        // a plain counter that publishes no other data, so no ordering is required
+        if triggered {
+            self.edges_since_detent.store(0, Ordering::Relaxed);
+            return;
+        }
+        let edges = self.edges_since_detent.fetch_add(1, Ordering::Relaxed) + 1;
+        if edges >= self.fault_threshold {
+            self.edges_since_detent.store(0, Ordering::Relaxed);
+            error!(
+                target: &self.log_target,
+                "Rotary encoder {} received {} edges without a detent, check the wiring",
+                self.name(), edges
+            );
+            if let Some(on_fault) = &self.on_fault {
+                let names = self.names();
+                self.guarded(&names.name, || on_fault(&names.name, edges));
+            }
+        }
+    }
+
+    /// Forget a partial turn if no edge arrived for `reset_after`, so a knob left between
+    /// detents cannot complete a detent in the latched direction on the next jitter.
+    /// Checked lazily on the next edge, which needs no timer thread.
+    fn reset_if_stalled(&self, timestamp: Duration) {
+        let last_edge = self.last_edge.swap(Some(timestamp), Ordering::AcqRel);
+        let Some(reset_after) = self.reset_after else {
+            return;
+        };
+        if last_edge.is_some_and(|last| timestamp.saturating_sub(last) >= reset_after) {
+            trace!(
+                target: &self.log_target,
+                "Rotary encoder {} stalled, resetting decoder",
+                self.name()
+            );
+            self.decoder
+                .store(pack(0b00, Direction::None), Ordering::Release);
+        }
+    }
+
+    /// Feed a single edge of `pin` into the decoder and fire the callback on a completed detent
+    /// # Arguments
+    /// * `pin` - Pin the edge occurred on
+    /// * `event_trigger` - Edge reported by rppal
+    /// * `shifted` - Whether the switch is pressed, `None` if no switch pin is configured
+    /// * `timestamp` - Time of the edge since system boot
+    pub(crate) fn handle_edge(
+        &self,
+        pin: Pin,
+        event_trigger: Trigger,
+        shifted: Option<bool>,
+        timestamp: Duration,
+    ) {
+        let Some(level) = trigger_to_level(event_trigger) else {
+            // no edge, so the decoder state stays as it is
+            log_non_edge(&self.log_target, event_trigger);
+            return;
+        };
+        self.total_triggers.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "record")]
+        if let Some(recorder) = &self.recorder {
+            recorder.record(pin, level, timestamp);
+        }
+        // level relative to the rest state, the decoder expects `0` at a detent
+        let decoded = match pin {
+            Pin::Clk => level ^ (self.rest_state & 0b01),
+            Pin::Dt => level ^ (self.rest_state >> 1),
+        };
+        self.reset_if_stalled(timestamp);
+        let traces = self.phase_traces.read().unwrap_or_else(|e| e.into_inner());
+        if self.on_phase.is_some() || !traces.is_empty() {
+            let (old_state, _) = unpack(self.decoder.load(Ordering::Acquire));
+            let phase = quadrature::next_state(old_state, pin, decoded) ^ self.rest_state;
+            let names = self.names();
+            if let Some(on_phase) = self.on_phase {
+                self.guarded(&names.name, || on_phase(&names.name, phase));
+            }
+            for trace in traces.iter() {
+                self.guarded(&names.name, || trace(&names.name, phase));
+            }
+        }
+        drop(traces);
+        // DT and CLK edges are handled by different interrupt threads. State and direction
+        // share one atomic and are updated in a compare-and-swap loop, so two edges arriving
+        // at once are applied one after the other instead of one overwriting the other.
+        // Acquire/Release suffices, a total order across all atomics (SeqCst) is not needed.
+        let mut outcome = Ok((Direction::None, false));
+        let update = self
+            .decoder
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |packed| {
+                let (old_state, old_direction) = unpack(packed);
+                let mut decoder = QuadratureDecoder::from_parts(old_state, old_direction);
+                match decoder.edge(pin, decoded == 1) {
+                    Ok(detent) => {
+                        outcome = Ok((decoder.direction(), detent.is_some()));
+                        Some(pack(decoder.state(), decoder.direction()))
+                    }
+                    Err(e) => {
+                        outcome = Err(e);
+                        None
+                    }
+                }
+            });
+        if let Some(raw_edges) = self.raw_edges {
+            // the decoder still follows the pins, but only the edge itself is reported
+            let names = self.names();
+            self.guarded(&names.name, || raw_edges(&names.name, pin, level));
+            return;
+        }
+        let (new_direction, trigger) = match outcome {
+            Ok(result) => result,
+            Err(e) => {
+                self.rejected_transitions.fetch_add(1, Ordering::Relaxed);
+                let (Ok(previous) | Err(previous)) = update;
+                let (old_state, _) = unpack(previous);
+                let current = match pin {
+                    Pin::Clk => old_state & 0b01,
+                    Pin::Dt => (old_state >> 1) & 0b01,
+                };
+                if current == decoded {
+                    // the pin reported the same level twice, so its previous edge was lost
+                    let skipped = self.skipped_states.fetch_add(1, Ordering::Relaxed) + 1;
+                    debug!(
+                        target: &self.log_target,
+                        "Rotary encoder {} missed an edge on {:?} ({} so far)",
+                        self.name(), pin, skipped
+                    );
+                }
+                // keep the old state, the next valid transition resyncs the decoder
+                if let Some(on_error) = self.on_error {
+                    let names = self.names();
+                    self.guarded(&names.name, || on_error(&names.name, e.into()));
+                }
+                self.watchdog(false);
+                return;
+            }
+        };
+        self.watchdog(trigger);
+        if trigger {
+            self.report(new_direction, pin, shifted, timestamp);
+        }
+    }
+
+    /// Feed a falling CLK edge into the decoder, the direction being given by the level
+    /// of DT at that time, see [`DecodeMode::ClkInterruptDtRead`]
+    /// # Arguments
+    /// * `dt_level` - Level of DT read in the CLK interrupt handler
+    /// * `shifted` - Whether the switch is pressed, `None` if no switch pin is configured
+    /// * `timestamp` - Time of the edge since system boot
+    pub(crate) fn handle_clk_edge(
+        &self,
+        dt_level: Level,
+        shifted: Option<bool>,
+        timestamp: Duration,
+    ) {
+        self.total_triggers.fetch_add(1, Ordering::Relaxed);
+        self.last_edge.store(Some(timestamp), Ordering::Release);
+        #[cfg(feature = "record")]
+        if let Some(recorder) = &self.recorder {
+            recorder.record(Pin::Clk, 1, timestamp);
+        }
+        if let Some(raw_edges) = self.raw_edges {
+            let names = self.names();
+            self.guarded(&names.name, || raw_edges(&names.name, Pin::Clk, 1));
+            return;
+        }
+        let direction = match dt_level {
+            Level::High => Direction::Clockwise,
+            Level::Low => Direction::CounterClockwise,
+        };
+        self.report(direction, Pin::Clk, shifted, timestamp);
+    }
+
+    /// Handle a pulse of the index output, see [`EncoderBuilder::index_pin`]
+    pub(crate) fn handle_index(&self) {
+        trace!(
+            target: &self.log_target,
+            "Rotary encoder {} passed its index",
+            self.name()
+        );
+        if let Some(home) = self.index_home {
+            let bounds = self.bounds();
+            self.position
+                .store(step_position(home, 0, bounds), Ordering::Release);
+        }
+        if let Some(on_index) = self.on_index {
+            let names = self.names();
+            self.guarded(&names.name, || on_index(&names.name));
+        }
+    }
+
+    /// Whether a detent at `timestamp` follows the last reported one too closely, see
+    /// [`EncoderBuilder::min_detent_interval`]. Records `timestamp` if not.
+    fn too_soon(&self, timestamp: Duration) -> bool {
+        let Some(min_interval) = self.min_detent_interval else {
+            return false;
+        };
+        // check and record in one step, the DT and CLK interrupts may both complete a detent
+        self.last_detent
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |last| match last {
+                Some(last) if timestamp.saturating_sub(last) < min_interval => None,
+                _ => Some(Some(timestamp)),
+            })
+            .is_err()
+    }
+
+    /// Steps of a detent completed at `timestamp`, see [`EncoderBuilder::acceleration`]
+    fn accel_steps(&self, timestamp: Duration) -> u8 {
+        if self.accel == AccelProfile::None {
+            return 1;
+        }
+        let previous = self.last_step.swap(Some(timestamp), Ordering::AcqRel);
+        self.accel
+            .steps(previous.map(|previous| timestamp.saturating_sub(previous)))
+    }
+
+    /// Whether a detent in `direction` continues the spin or confirms a reversal, see
+    /// [`EncoderBuilder::reversal_confirm`]
+    fn confirm_reversal(&self, direction: Direction) -> bool {
+        if self.reversal_confirm <= 1 {
+            return true;
+        }
+        let mut accepted = true;
+        // both interrupt threads may report, so the spin is updated in one atomic step
+        let _ = self
+            .spin
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |spin| {
+                let (next, accept) = next_spin(spin, direction, self.reversal_confirm);
+                accepted = accept;
+                Some(next)
+            });
+        accepted
+    }
+
+    pub(crate) fn position(&self) -> i32 {
+        self.position.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn last_triggered_direction(&self) -> Direction {
+        self.last_triggered.load(Ordering::Acquire)
+    }
+
+    /// Direction latched by the decoder, as reported to the callbacks
+    fn current_direction(&self) -> Direction {
+        let (_, direction) = unpack(self.decoder.load(Ordering::Acquire));
+        self.orient(direction)
+    }
+
+    /// Flip `direction` if the encoder is [`EncoderBuilder::reversed`]
+    fn orient(&self, direction: Direction) -> Direction {
+        match (self.reversed, direction) {
+            (true, Direction::Clockwise) => Direction::CounterClockwise,
+            (true, Direction::CounterClockwise) => Direction::Clockwise,
+            (_, direction) => direction,
+        }
+    }
+
+    /// Report a completed detent turned in `new_direction` to the callbacks,
+    /// `pin` being the pin whose edge completed it
+    fn report(
+        &self,
+        new_direction: Direction,
+        pin: Pin,
+        shifted: Option<bool>,
+        timestamp: Duration,
+    ) {
+        let reported = self.orient(new_direction);
+        if let Some(on_trigger_pin) = self.on_trigger_pin {
+            let names = self.names();
+            self.guarded(&names.name, || on_trigger_pin(&names.name, reported, pin));
+        }
+        match reported {
+            Direction::Clockwise => self.clockwise.fetch_add(1, Ordering::Relaxed),
+            _ => self.counter_clockwise.fetch_add(1, Ordering::Relaxed),
+        };
+        // while disabled the decoder keeps tracking the state, so it is in sync when re-enabled
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if !self.confirm_reversal(reported) {
+            debug!(
+                target: &self.log_target,
+                "Rotary encoder {} turned {:?} against its spin, waiting for confirmation",
+                self.name(), reported
+            );
+            return;
+        }
+        if self.too_soon(timestamp) {
+            debug!(
+                target: &self.log_target,
+                "Rotary encoder {} turned {:?} too soon after the last detent, ignoring it",
+                self.name(), reported
+            );
+            return;
+        }
+        let names = self.names();
+        if names.shifted.is_some() && shifted.is_none() {
+            error!(
+                target: &self.log_target,
+                "Both sw_pin (is {:?}) and name shifted (is {:?}) must be defined!",
+                shifted, names.shifted
+            );
+            return;
+        }
+        let pressed = shifted.unwrap_or(false);
+        let name = match (names.shifted.as_ref(), pressed) {
+            (Some(name_shift), true) => {
+                trace!(
+                    target: &self.log_target,
+                    "Rotary encoder {:?} turned {:?}, triggering shifted callback",
+                    name_shift, reported
+                );
+                name_shift
+            }
+            (_, _) => {
+                trace!(
+                    target: &self.log_target,
+                    "Rotary encoder {} turned {:?} (pressed: {}), triggering callback",
+                    names.name, reported, pressed
+                );
+                &names.name
+            }
+        };
+        if let Some(on_detent) = self.on_detent {
+            self.guarded(name, || on_detent(name));
+        }
+        self.last_triggered.store(reported, Ordering::Release);
+        let steps = self.accel_steps(timestamp);
+        let delta = i32::from(reported.as_delta()) * i32::from(steps);
+        let bounds = self.bounds();
+        let previous =
+            self.position
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |position| {
+                    Some(step_position(position, delta, bounds))
+                });
+        let (Ok(previous) | Err(previous)) = previous;
+        let detent = Detent {
+            name,
+            direction: reported,
+            timestamp,
+            pressed,
+            position: step_position(previous, delta, bounds),
+            steps,
+        };
+        match &self.dispatcher {
+            Some(dispatcher) => dispatcher.enqueue(&detent),
+            None => self.notify(&detent),
+        }
+    }
+}
+
+/// Spin in `direction` with no opposite detents seen
+const fn spin_of(direction: Direction) -> u16 {
+    (direction as u16) << 8
+}
+
+/// Spin after a detent in `direction` and whether to report it. A spin holds the direction
+/// of the last reported detent in the high byte and the number of consecutive detents
+/// against it in the low byte; the `confirm`th of them reports the reversal.
+fn next_spin(spin: u16, direction: Direction, confirm: u8) -> (u16, bool) {
+    let spinning = Direction::try_from((spin >> 8) as u8).unwrap_or(Direction::None);
+    let against = (spin as u8).saturating_add(1);
+    if spinning == Direction::None || spinning == direction || against >= confirm {
+        (spin_of(direction), true)
+    } else {
+        ((spin & 0xff00) | u16::from(against), false)
+    }
+}
+
+/// Edges per second of `edges` counted during `window`, 0 for an empty window
+fn edge_rate(edges: u64, window: Duration) -> f32 {
+    match window.as_secs_f32() {
+        secs if secs > 0.0 => edges as f32 / secs,
+        _ => 0.0,
+    }
+}
+
+/// Quadrature state of the given pin levels, bit 1 being DT and bit 0 CLK
+fn phase(dt: Level, clk: Level) -> u8 {
+    quadrature::phase_bits(dt == Level::Low, clk == Level::Low)
+}
+
+/// Pack the quadrature state (bits 0-1) and the direction (bits 2-3) into one byte,
+/// so both can be updated by a single atomic operation
+const fn pack(state: u8, direction: Direction) -> u8 {
+    // `u8::from` is not const
+    (direction as u8) << 2 | state
+}
+
+/// Split a byte created by [`pack`] into state and direction
+fn unpack(packed: u8) -> (u8, Direction) {
+    let direction = Direction::try_from(packed >> 2).unwrap_or(Direction::None);
+    (packed & 0b11, direction)
+}
+
+/// Builder for [`Encoder`], avoiding long positional argument lists
+#[derive(Default)]
+pub struct EncoderBuilder {
+    name: Option<String>,
+    name_shifted: Option<String>,
+    dt_pin: Option<u8>,
+    clk_pin: Option<u8>,
+    sw_pin: Option<u8>,
+    shared_shift: Option<Arc<AtomicBool>>,
+    debounce: Option<Duration>,
+    reversed: bool,
+    callback: Option<Callback>,
+    on_error: Option<fn(&str, anyhow::Error)>,
+    on_detent: Option<fn(&str)>,
+    on_phase: Option<fn(&str, u8)>,
+    raw_edges: Option<fn(&str, Pin, u8)>,
+    #[cfg(feature = "record")]
+    recorder: Option<EdgeRecorder>,
+    on_trigger_pin: Option<fn(&str, Direction, Pin)>,
+    index_pin: Option<u8>,
+    on_index: Option<fn(&str)>,
+    index_home: Option<i32>,
+    on_panic: Option<fn(&str, &str)>,
+    log_target: Option<String>,
+    dispatch: Dispatch,
+    dispatch_capacity: Option<usize>,
+    overflow_policy: OverflowPolicy,
+    fault_threshold: Option<u32>,
+    on_fault: Option<FaultCallback>,
+    pressed_turn_events: bool,
+    reset_after: Option<Duration>,
+    min_detent_interval: Option<Duration>,
+    accel: AccelProfile,
+    reversal_confirm: u8,
+    coalesce_window: Option<Duration>,
+    poll_interval: Option<Duration>,
+    decode_mode: DecodeMode,
+    rest_state: u8,
+}
+
+impl EncoderBuilder {
+    /// Name of the encoder (required)
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Name of the encoder while its switch is pressed, requires a switch pin
+    pub fn shifted_name(mut self, name: &str) -> Self {
+        self.name_shifted = Some(name.to_owned());
+        self
+    }
+
+    /// GPIO pin number for data (DT) encoder signal (required)
+    pub fn dt_pin(mut self, pin: impl Into<DtPin>) -> Self {
+        self.dt_pin = Some(pin.into().0);
+        self
+    }
+
+    /// GPIO pin number for clock (CLK) encoder signal (required)
+    pub fn clk_pin(mut self, pin: impl Into<ClkPin>) -> Self {
+        self.clk_pin = Some(pin.into().0);
+        self
+    }
+
+    /// GPIO pin number for the built-in switch used for shifting
+    pub fn switch_pin(mut self, pin: impl Into<SwPin>) -> Self {
+        self.sw_pin = Some(pin.into().0);
+        self
+    }
+
+    /// Shift with a button shared with other encoders instead of a built-in switch.
+    /// Ignored if a switch pin is set.
+    pub fn shared_shift(mut self, shift: &SharedShift) -> Self {
+        self.shared_shift = Some(shift.state());
+        self
+    }
+
+    /// rppal software debounce for the DT and CLK interrupts, disabled by default
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = Some(debounce);
+        self
+    }
+
+    /// Flip the reported direction, for encoders whose DT and CLK are wired the other way round
+    pub fn reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+
+    /// Function to call when the encoder is turned (required)
+    pub fn callback(mut self, callback: fn(&str, Direction)) -> Self {
+        self.callback = Some(untimed(callback));
+        self
+    }
+
+    /// Function to call when the encoder is turned, additionally receiving the time
+    /// of the completing edge since system boot (replaces [`EncoderBuilder::callback`])
+    pub fn timed_callback(mut self, callback: fn(&str, Direction, Duration)) -> Self {
+        self.callback = Some(Arc::new(move |detent| {
+            callback(detent.name, detent.direction, detent.timestamp)
+        }));
+        self
+    }
+
+    /// Function to call when the encoder is turned, additionally receiving whether the
+    /// switch was held (replaces [`EncoderBuilder::callback`])
+    pub fn shifted_callback(mut self, callback: fn(&str, Direction, bool)) -> Self {
+        self.callback = Some(shift_aware(callback));
+        self
+    }
+
+    /// Function to call when the encoder is turned, additionally receiving `context`, e.g. an
+    /// application handle the callback downcasts with [`Arc::downcast`] or
+    /// [`downcast_ref`](std::any::Any#method.downcast_ref), instead of reaching it through a
+    /// global (replaces [`EncoderBuilder::callback`])
+    pub fn context_callback(
+        mut self,
+        context: Context,
+        callback: fn(&str, Direction, &Context),
+    ) -> Self {
+        self.callback = Some(with_context(context, callback));
+        self
+    }
+
+    /// Function to call when the encoder is turned, receiving [`Direction::as_delta`] instead
+    /// of the direction, e.g. to add it to a scroll position. With
+    /// [`EncoderBuilder::acceleration`] it is multiplied by the steps of the detent.
+    /// (replaces [`EncoderBuilder::callback`])
+    pub fn delta_callback(mut self, callback: fn(&str, i8)) -> Self {
+        self.callback = Some(stepped(callback));
+        self
+    }
+
+    /// Function to call when a state transition is rejected
+    pub fn on_error(mut self, on_error: fn(&str, anyhow::Error)) -> Self {
+        self.on_error = Some(on_error);
+        self
+    }
+
+    /// Function to call on every detent regardless of direction, before the callbacks,
+    /// e.g. to pulse a buzzer or haptic motor
+    pub fn on_detent(mut self, on_detent: fn(&str)) -> Self {
+        self.on_detent = Some(on_detent);
+        self
+    }
+
+    /// Function to call on every DT or CLK edge with the raw phase before decoding, to
+    /// diagnose wiring. The phase holds the DT level in bit 1 and the CLK level in bit 0,
+    /// `1` meaning pulled low, see [`QUADRATURE_TABLE`].
+    pub fn on_phase(mut self, on_phase: fn(&str, u8)) -> Self {
+        self.on_phase = Some(on_phase);
+        self
+    }
+
+    /// Raw mode: call `raw_edges` on every DT or CLK edge with the pin and its new level
+    /// (`1` meaning pulled low) instead of decoding detents, e.g. to log the raw quadrature
+    /// signal or decode it externally. The decoder keeps following the pins, but no detent
+    /// is reported, so the other callbacks and the position stay idle and
+    /// [`EncoderBuilder::callback`] becomes optional.
+    /// With [`DecodeMode::ClkInterruptDtRead`] only the falling CLK edges are seen.
+    pub fn raw_edges(mut self, raw_edges: fn(&str, Pin, u8)) -> Self {
+        self.raw_edges = Some(raw_edges);
+        self
+    }
+
+    /// Record every DT and CLK edge to a capture, e.g. to attach it to a bug report and
+    /// replay it with [`QuadratureDecoder::replay`], see [`crate::record`]
+    #[cfg(feature = "record")]
+    pub fn record_edges(mut self, recorder: EdgeRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Function to call on every decoded detent with its direction and the pin whose edge
+    /// completed it, even while disabled. A healthy encoder completes its detents on both
+    /// pins, depending on the direction; one pin never completing any points at a marginal
+    /// connection of the other.
+    pub fn on_trigger_pin(mut self, on_trigger_pin: fn(&str, Direction, Pin)) -> Self {
+        self.on_trigger_pin = Some(on_trigger_pin);
+        self
+    }
+
+    /// GPIO pin number of the index (Z) output some encoders pulse once per revolution, read
+    /// with a pull-up and an interrupt on the falling edge, also when polling DT and CLK.
+    /// See [`EncoderBuilder::on_index`] and [`EncoderBuilder::index_home`].
+    pub fn index_pin(mut self, pin: u8) -> Self {
+        self.index_pin = Some(pin);
+        self
+    }
+
+    /// Function to call on every pulse of the index pin
+    pub fn on_index(mut self, on_index: fn(&str)) -> Self {
+        self.on_index = Some(on_index);
+        self
+    }
+
+    /// Position to move to on every pulse of the index pin, making [`Encoder::position`]
+    /// an absolute shaft angle after the first revolution
+    pub fn index_home(mut self, home: i32) -> Self {
+        self.index_home = Some(home);
+        self
+    }
+
+    /// Function to call with the encoder name and the panic message when a callback panics.
+    /// Panics are caught and logged either way, so the encoder keeps decoding; this does not
+    /// work with `panic = "abort"`.
+    pub fn on_panic(mut self, on_panic: fn(&str, &str)) -> Self {
+        self.on_panic = Some(on_panic);
+        self
+    }
+
+    /// Target of this encoder's log messages, e.g. `myapp::volume_knob` to filter them with
+    /// `RUST_LOG=myapp::volume_knob=trace`. Defaults to the module path.
+    pub fn log_target(mut self, target: &str) -> Self {
+        self.log_target = Some(target.to_owned());
+        self
+    }
+
+    /// Configured log target, or the module path if none was set
+    pub(crate) fn target(&self) -> &str {
+        self.log_target.as_deref().unwrap_or(module_path!())
+    }
+
+    /// Where the callbacks are run, [`Dispatch::Inline`] by default.
+    /// [`EncoderBuilder::on_detent`] is always called inline for the lowest latency.
+    pub fn dispatch(mut self, dispatch: Dispatch) -> Self {
+        self.dispatch = dispatch;
+        self
+    }
+
+    /// Queue up to `capacity` detents (at least one) for [`Dispatch::Threaded`] instead of
+    /// [`DISPATCH_QUEUE_CAPACITY`], handling a full queue according to `policy`.
+    /// Dropped detents are counted in [`EncoderMetrics::dropped_detents`].
+    pub fn dispatch_queue(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        self.dispatch_capacity = Some(capacity);
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Number of edges without a detent before a fault is reported,
+    /// defaults to [`DEFAULT_FAULT_THRESHOLD`]
+    pub fn fault_threshold(mut self, edges: u32) -> Self {
+        self.fault_threshold = Some(edges);
+        self
+    }
+
+    /// Function to call when the encoder stops producing detents, e.g. due to wrong wiring
+    pub fn on_fault(mut self, on_fault: fn(&str, u32)) -> Self {
+        self.on_fault = Some(Arc::new(on_fault));
+        self
+    }
+
+    /// Emit [`RotaryEvent::PressedTurn`] instead of [`RotaryEvent::Turned`] for detents
+    /// while the switch is held, as an alternative to a shifted name.
+    /// Only affects event based consumers, callbacks keep receiving the (shifted) name.
+    pub fn pressed_turn_events(mut self, enabled: bool) -> Self {
+        self.pressed_turn_events = enabled;
+        self
+    }
+
+    /// Report detents in the same direction as a single [`RotaryEvent::Batched`] once no
+    /// further detent arrived for `window`, e.g. for fast scrolling. A reversal flushes the
+    /// batch immediately. Runs a small timer thread and only affects event based consumers,
+    /// callbacks keep receiving every detent.
+    pub fn coalesce_window(mut self, window: Duration) -> Self {
+        self.coalesce_window = Some(window);
+        self
+    }
+
+    /// How DT and CLK edges are decoded, [`DecodeMode::DualInterrupt`] by default.
+    /// Has no effect when polling.
+    pub fn decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.decode_mode = mode;
+        self
+    }
+
+    /// Pin state of the encoder at a detent, DT in bit 1 and CLK in bit 0, `1` meaning
+    /// pulled low as in [`QUADRATURE_TABLE`]. Defaults to `0b00`, both pins high. Encoders
+    /// resting with both pins low need `0b11`, ones with an inverted phase `0b01` or `0b10`;
+    /// otherwise they never leave the resting transitions and nothing decodes.
+    /// [`DecodeMode::ClkInterruptDtRead`] only supports `0b00`.
+    pub fn rest_state(mut self, rest_state: u8) -> Self {
+        self.rest_state = rest_state;
+        self
+    }
+
+    /// Poll DT and CLK every `interval` from a thread instead of using interrupts,
+    /// see [`Encoder::new_polling`]. [`EncoderBuilder::debounce`] has no effect when polling.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = Some(interval);
+        self
+    }
+
+    /// Reset the decoder to the resting state when no edge arrives for `timeout`,
+    /// discarding a partial turn. Disabled by default.
+    pub fn reset_after(mut self, timeout: Duration) -> Self {
+        self.reset_after = Some(timeout);
+        self
+    }
+
+    /// Ignore detents that follow the last reported detent within `interval`, filtering the
+    /// chatter of worn encoders after decoding. Unlike `debounce`, which filters single edges,
+    /// this limits how often the callbacks fire. Disabled by default.
+    pub fn min_detent_interval(mut self, interval: Duration) -> Self {
+        self.min_detent_interval = Some(interval);
+        self
+    }
+
+    /// Count fast detents as several steps, see [`AccelProfile`]. The steps move the
+    /// position and are passed to [`EncoderBuilder::delta_callback`]; other callbacks are
+    /// still called once per detent. No acceleration by default.
+    pub fn acceleration(mut self, profile: AccelProfile) -> Self {
+        self.accel = profile;
+        self
+    }
+
+    /// Require `detents` consecutive detents against the current spin before reporting a
+    /// reversal, so a single spurious detent of a worn encoder does not flip the direction
+    /// mid-spin. The detents confirming a reversal are dropped except the last, which also
+    /// costs a deliberate single click back. `0` and `1` report every detent (the default).
+    pub fn reversal_confirm(mut self, detents: u8) -> Self {
+        self.reversal_confirm = detents;
+        self
+    }
+
+    /// Check that all required fields are set and return them
+    fn required(&self) -> Result<(String, u8, u8, Callback), EncoderError> {
+        let missing = |field| EncoderError::MissingField {
+            builder: "rotary encoder",
+            field,
+        };
+        let single_interrupt =
+            self.poll_interval.is_none() && self.decode_mode == DecodeMode::ClkInterruptDtRead;
+        if self.rest_state > 0b11 || (single_interrupt && self.rest_state != 0b00) {
+            return Err(EncoderError::InvalidRestState(self.rest_state));
+        }
+        Ok((
+            self.name.clone().ok_or_else(|| missing("name"))?,
+            self.dt_pin.ok_or_else(|| missing("dt_pin"))?,
+            self.clk_pin.ok_or_else(|| missing("clk_pin"))?,
+            match (&self.callback, self.raw_edges) {
+                (Some(callback), _) => Arc::clone(callback),
+                // raw mode reports no detents
+                (None, Some(_)) => Arc::new(|_: &Detent| {}),
+                (None, None) => return Err(missing("callback")),
+            },
+        ))
+    }
+
+    /// Claim the GPIO pins and enable the interrupts, additionally reporting all detents
+    /// and faults as [`RotaryEvent`]s to `sink`. A configured callback is optional.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn build_with_events(
+        mut self,
+        gpio: &Gpio,
+        sink: EventSink,
+    ) -> Result<Encoder, EncoderError> {
+        let base_name = self.name.clone().unwrap_or_default();
+        let pressed_turn_events = self.pressed_turn_events;
+        let sink = match self.coalesce_window {
+            Some(window) => Coalescer::spawn(
+                &base_name,
+                window,
+                self.target(),
+                Arc::new(SystemClock),
+                sink,
+            ),
+            None => sink,
+        };
+        let fault_sink = Arc::clone(&sink);
+
+        let user_callback = self.callback.take();
+        self.callback = Some(Arc::new(move |detent| {
+            if let Some(callback) = &user_callback {
+                callback(detent);
+            }
+            sink(RotaryEvent::from_detent(
+                &base_name,
+                detent,
+                pressed_turn_events,
+            ));
+        }));
+        let user_on_fault = self.on_fault.take();
+        self.on_fault = Some(Arc::new(move |name, edges| {
+            if let Some(on_fault) = &user_on_fault {
+                on_fault(name, edges);
+            }
+            fault_sink(RotaryEvent::Fault {
+                name: name.to_owned(),
+                edges,
+            });
+        }));
+        self.build(gpio)
+    }
+
+    /// Claim the GPIO pins and enable the interrupts
+    pub fn build(self, gpio: &Gpio) -> Result<Encoder, EncoderError> {
+        let (encoder_name, dt_pin, clk_pin, _) = self.required()?;
+        trace!(
+            target: self.target(),
+            "Initializing GPIO for rotary encoder {}/{:?}",
+            encoder_name, self.name_shifted
+        );
+
+        Encoder::validate_pins(dt_pin, clk_pin, self.sw_pin, self.index_pin)?;
+
+        let dt = gpio.get(dt_pin)?.into_input_pullup();
+        let clk = gpio.get(clk_pin)?.into_input_pullup();
+        let sw = match self.sw_pin {
+            None => None,
+            Some(p) => Some(gpio.get(p)?.into_input_pullup()),
+        };
+        let index = match self.index_pin {
+            None => None,
+            Some(p) => Some(gpio.get(p)?.into_input_pullup()),
+        };
+        self.build_on(dt, clk, sw, index)
+    }
+
+    /// Enable the interrupts on the claimed pins, whose numbers must match the configured ones
+    fn build_on(
+        self,
+        dt: InputPin,
+        clk: InputPin,
+        sw: Option<InputPin>,
+        index: Option<InputPin>,
+    ) -> Result<Encoder, EncoderError> {
+        let (encoder_name, dt_pin, clk_pin, callback) = self.required()?;
+        debug_assert!(verify_decode_table(), "Quadrature decode table is broken");
+
+        let mut shared = Shared::new(encoder_name, self.name_shifted, self.reversed, callback);
+        if let Some(log_target) = &self.log_target {
+            shared.set_log_target(log_target);
+        }
+        shared.set_rest_state(self.rest_state);
+        shared.seed(dt.read(), clk.read());
+        shared.on_error = self.on_error;
+        if let Some(on_detent) = self.on_detent {
+            shared.set_on_detent(on_detent);
+        }
+        if let Some(on_phase) = self.on_phase {
+            shared.set_on_phase(on_phase);
+        }
+        if let Some(raw_edges) = self.raw_edges {
+            shared.set_raw_edges(raw_edges);
+        }
+        #[cfg(feature = "record")]
+        {
+            shared.recorder = self.recorder;
+        }
+        if let Some(on_trigger_pin) = self.on_trigger_pin {
+            shared.set_on_trigger_pin(on_trigger_pin);
+        }
+        shared.fault_threshold = self.fault_threshold.unwrap_or(DEFAULT_FAULT_THRESHOLD);
+        shared.on_fault = self.on_fault;
+        shared.reset_after = self.reset_after;
+        shared.min_detent_interval = self.min_detent_interval;
+        shared.accel = self.accel;
+        shared.reversal_confirm = self.reversal_confirm;
+        shared.on_index = self.on_index;
+        shared.index_home = self.index_home;
+        shared.on_panic = self.on_panic;
+        let queue = match self.dispatch {
+            Dispatch::Inline => None,
+            Dispatch::Threaded => Some(shared.queue_detents(
+                self.dispatch_capacity.unwrap_or(DISPATCH_QUEUE_CAPACITY),
+                self.overflow_policy,
+            )),
+        };
+        let shared = Arc::new(shared);
+        if let Some(queue) = queue {
+            Shared::spawn_dispatcher(&shared, queue);
+        }
+        let shift = Arc::new(match (sw, self.shared_shift) {
+            (Some(pin), _) => Shift::Pin(pin),
+            (None, Some(pressed)) => Shift::Shared(pressed),
+            (None, None) => Shift::None,
+        });
+        let source = match (self.poll_interval, self.decode_mode) {
+            (Some(interval), _) => EdgeSource::Polling(Poller::spawn(
+                dt,
+                clk,
+                Arc::clone(&shift),
+                Arc::clone(&shared),
+                interval,
+            )),
+            (None, DecodeMode::DualInterrupt) => EdgeSource::Interrupts {
+                dt_pin: dt,
+                clk_pin: clk,
+            },
+            (None, DecodeMode::ClkInterruptDtRead) => EdgeSource::ClkInterrupt {
+                dt_pin: Arc::new(dt),
+                clk_pin: clk,
+            },
+        };
+        let mut encoder = Encoder {
+            source,
+            shift,
+            index_pin: index,
+            pins: EncoderPins {
+                dt: dt_pin,
+                clk: clk_pin,
+                sw: self.sw_pin,
+                index: self.index_pin,
+            },
+            debounce: self.debounce,
+            shared,
+        };
+
+        encoder
+            .enable_callbacks()
+            .map_err(EncoderError::InterruptFailed)?;
+        trace!(
+            target: &encoder.shared.log_target,
+            "Rotary encoder {}/{:?} initialized",
+            encoder.shared.name(),
+            encoder.shared.names().shifted
+        );
+        Ok(encoder)
+    }
+}
+
+impl fmt::Debug for Encoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encoder")
+            .field("names", &self.shared.names())
+            .field("pins", &self.pins)
+            .field("source", &self.source)
+            .field(
+                "decoder",
+                &unpack(self.shared.decoder.load(Ordering::Relaxed)),
+            )
+            .field("debounce", &self.debounce)
+            .field("reversed", &self.shared.reversed)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_pins_distinct() {
+        assert!(Encoder::validate_pins(9, 10, None, None).is_ok());
+        assert!(Encoder::validate_pins(9, 10, Some(11), None).is_ok());
+        assert!(Encoder::validate_pins(9, 10, None, Some(11)).is_ok());
+        assert!(Encoder::validate_pins(9, 10, Some(11), Some(12)).is_ok());
+    }
+
+    #[test]
+    fn test_validate_pins_dt_clk_collision() {
+        let err = Encoder::validate_pins(9, 9, None, None).unwrap_err();
+        assert!(
+            matches!(&err, EncoderError::DuplicatePin { pin: 9, first, second } if first == "DT" && second == "CLK")
+        );
+        assert_eq!(err.to_string(), "GPIO pin 9 is used by both DT and CLK");
+    }
+
+    #[test]
+    fn test_validate_pins_dt_sw_collision() {
+        let err = Encoder::validate_pins(9, 10, Some(9), None).unwrap_err();
+        assert!(
+            matches!(&err, EncoderError::DuplicatePin { pin: 9, first, second } if first == "DT" && second == "SW")
+        );
+        assert_eq!(err.to_string(), "GPIO pin 9 is used by both DT and SW");
+    }
+
+    #[test]
+    fn test_validate_pins_clk_sw_collision() {
+        let err = Encoder::validate_pins(9, 10, Some(10), None).unwrap_err();
+        assert!(
+            matches!(&err, EncoderError::DuplicatePin { pin: 10, first, second } if first == "CLK" && second == "SW")
+        );
+        assert_eq!(err.to_string(), "GPIO pin 10 is used by both CLK and SW");
+    }
+
+    #[test]
+    fn test_validate_pins_index_collision() {
+        let err = Encoder::validate_pins(9, 10, None, Some(10)).unwrap_err();
+        assert_eq!(err.to_string(), "GPIO pin 10 is used by both CLK and INDEX");
+        let err = Encoder::validate_pins(9, 10, Some(11), Some(11)).unwrap_err();
+        assert_eq!(err.to_string(), "GPIO pin 11 is used by both SW and INDEX");
+    }
+
+    fn builder_error(builder: EncoderBuilder) -> String {
+        builder.required().map(|_| ()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_builder_requires_fields() {
+        fn cb(_: &str, _: Direction) {}
+
+        assert_eq!(
+            builder_error(Encoder::builder().dt_pin(9).clk_pin(10).callback(cb)),
+            "rotary encoder builder: missing required field `name`"
+        );
+        assert_eq!(
+            builder_error(Encoder::builder().name("knob").clk_pin(10).callback(cb)),
+            "rotary encoder builder: missing required field `dt_pin`"
+        );
+        assert_eq!(
+            builder_error(Encoder::builder().name("knob").dt_pin(9).callback(cb)),
+            "rotary encoder builder: missing required field `clk_pin`"
+        );
+        assert_eq!(
+            builder_error(Encoder::builder().name("knob").dt_pin(9).clk_pin(10)),
+            "rotary encoder builder: missing required field `callback`"
+        );
+    }
+
+    #[test]
+    fn test_builder_complete() {
+        let builder = Encoder::builder()
+            .name("knob")
+            .shifted_name("knob_shifted")
+            .dt_pin(9)
+            .clk_pin(10)
+            .switch_pin(11)
+            .debounce(Duration::from_millis(2))
+            .callback(|_, _| {});
+        let (name, dt_pin, clk_pin, _) = builder.required().unwrap();
+        assert_eq!(name, "knob");
+        assert_eq!(dt_pin, 9);
+        assert_eq!(clk_pin, 10);
+    }
+
+    #[test]
+    fn test_direction_display() {
+        assert_eq!(Direction::Clockwise.to_string(), "CW");
+        assert_eq!(Direction::CounterClockwise.to_string(), "CCW");
+        assert_eq!(Direction::None.to_string(), "None");
+    }
+
+    #[test]
+    fn test_direction_u8_round_trip() {
+        for (direction, value) in [
+            (Direction::Clockwise, 0),
+            (Direction::CounterClockwise, 1),
+            (Direction::None, 2),
+        ] {
+            assert_eq!(u8::from(direction), value);
+            assert_eq!(Direction::try_from(value), Ok(direction));
+        }
+        assert_eq!(Direction::try_from(3), Err(InvalidDirection(3)));
+        assert_eq!(
+            InvalidDirection(3).to_string(),
+            "3 is not a valid direction, expected 0-2"
+        );
+    }
+
+    #[test]
+    fn test_direction_as_delta() {
+        assert_eq!(Direction::Clockwise.as_delta(), 1);
+        assert_eq!(Direction::CounterClockwise.as_delta(), -1);
+        assert_eq!(Direction::None.as_delta(), 0);
+    }
+
+    #[test]
+    fn test_direction_hash() {
+        let directions: std::collections::HashSet<Direction> = [
+            Direction::Clockwise,
+            Direction::CounterClockwise,
+            Direction::Clockwise,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(directions.len(), 2);
+    }
+
+    const CLOCKWISE: [(Pin, Trigger); 4] = [
+        (Pin::Clk, Trigger::FallingEdge),
+        (Pin::Dt, Trigger::FallingEdge),
+        (Pin::Clk, Trigger::RisingEdge),
+        (Pin::Dt, Trigger::RisingEdge),
+    ];
+
+    const COUNTER_CLOCKWISE: [(Pin, Trigger); 4] = [
+        (Pin::Dt, Trigger::FallingEdge),
+        (Pin::Clk, Trigger::FallingEdge),
+        (Pin::Dt, Trigger::RisingEdge),
+        (Pin::Clk, Trigger::RisingEdge),
+    ];
+
+    type Log = Arc<std::sync::Mutex<Vec<(String, Direction)>>>;
+
+    /// Decoder recording all callbacks instead of requiring GPIO hardware
+    fn recording_shared(name_shifted: Option<&str>, reversed: bool) -> (Shared, Log) {
+        let log: Log = Arc::default();
+        let log_clone = Arc::clone(&log);
+        let shared = Shared::new(
+            "knob".to_owned(),
+            name_shifted.map(|s| s.to_owned()),
+            reversed,
+            Arc::new(move |detent| {
+                log_clone
+                    .lock()
+                    .unwrap()
+                    .push((detent.name.to_owned(), detent.direction))
+            }),
+        );
+        (shared, log)
+    }
+
+    fn drive(shared: &Shared, edges: &[(Pin, Trigger)], shifted: Option<bool>) {
+        for (pin, trigger) in edges {
+            shared.handle_edge(*pin, *trigger, shifted, Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_handle_edge_clockwise() {
+        let (shared, log) = recording_shared(None, false);
+        drive(&shared, &CLOCKWISE, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![("knob".to_owned(), Direction::Clockwise)]
+        );
+    }
+
+    #[test]
+    fn test_current_and_last_triggered_direction() {
+        let (shared, _) = recording_shared(None, false);
+        assert_eq!(shared.current_direction(), Direction::None);
+        drive(&shared, &CLOCKWISE, None);
+        assert_eq!(
+            shared.last_triggered.load(Ordering::Acquire),
+            Direction::Clockwise
+        );
+        // turning back latches the new direction before the detent completes
+        drive(&shared, &COUNTER_CLOCKWISE[..2], None);
+        assert_eq!(shared.current_direction(), Direction::CounterClockwise);
+        assert_eq!(
+            shared.last_triggered.load(Ordering::Acquire),
+            Direction::Clockwise
+        );
+        drive(&shared, &COUNTER_CLOCKWISE[2..], None);
+        assert_eq!(
+            shared.last_triggered.load(Ordering::Acquire),
+            Direction::CounterClockwise
+        );
+
+        let (reversed, _) = recording_shared(None, true);
+        drive(&reversed, &CLOCKWISE[..1], None);
+        assert_eq!(reversed.current_direction(), Direction::CounterClockwise);
+    }
+
+    /// `edges` with the pins swapped, as if DT and CLK were wired the other way round
+    fn swapped(edges: &[(Pin, Trigger)]) -> Vec<(Pin, Trigger)> {
+        edges
+            .iter()
+            .map(|&(pin, trigger)| match pin {
+                Pin::Dt => (Pin::Clk, trigger),
+                Pin::Clk => (Pin::Dt, trigger),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_completing_pin_does_not_change_output() {
+        static TRIGGER_PINS: std::sync::Mutex<Vec<(bool, Pin)>> = std::sync::Mutex::new(Vec::new());
+        // the same clockwise motion, once completed by DT and once, with the pins swapped
+        // and the encoder reversed, completed by CLK
+        let mut outputs = Vec::new();
+        for (edges, reversed) in [(CLOCKWISE.to_vec(), false), (swapped(&CLOCKWISE), true)] {
+            let (mut shared, log) = recording_shared(None, reversed);
+            shared.set_on_trigger_pin(|name, _, pin| {
+                TRIGGER_PINS.lock().unwrap().push((name == "knob", pin))
+            });
+            drive(&shared, &edges, None);
+            drive(&shared, &edges, None);
+            outputs.push((
+                log.lock().unwrap().clone(),
+                shared.position.load(Ordering::Acquire),
+            ));
+        }
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(
+            outputs[0].0,
+            vec![("knob".to_owned(), Direction::Clockwise); 2]
+        );
+        assert_eq!(
+            *TRIGGER_PINS.lock().unwrap(),
+            vec![
+                (true, Pin::Dt),
+                (true, Pin::Dt),
+                (true, Pin::Clk),
+                (true, Pin::Clk)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_reversed() {
+        let (shared, log) = recording_shared(None, true);
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::CounterClockwise),
+                ("knob".to_owned(), Direction::Clockwise)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_shifted_name() {
+        let (shared, log) = recording_shared(Some("knob_shifted"), false);
+        drive(&shared, &CLOCKWISE, Some(false));
+        drive(&shared, &CLOCKWISE, Some(true));
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::Clockwise),
+                ("knob_shifted".to_owned(), Direction::Clockwise)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_multiple_callbacks() {
+        let (shared, log) = recording_shared(None, false);
+        let log_clone = Arc::clone(&log);
+        shared.add_callback(Arc::new(move |detent| {
+            log_clone
+                .lock()
+                .unwrap()
+                .push((format!("{}_second", detent.name), detent.direction))
+        }));
+        drive(&shared, &CLOCKWISE, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::Clockwise),
+                ("knob_second".to_owned(), Direction::Clockwise)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_watchdog() {
+        let (mut shared, log) = recording_shared(None, false);
+        let faults = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let faults_clone = Arc::clone(&faults);
+        shared.fault_threshold = 4;
+        shared.on_fault = Some(Arc::new(move |name, edges| {
+            faults_clone.lock().unwrap().push((name.to_owned(), edges))
+        }));
+
+        // DT bouncing never completes a detent
+        let bounce = [
+            (Pin::Dt, Trigger::FallingEdge),
+            (Pin::Dt, Trigger::RisingEdge),
+        ];
+        drive(&shared, &bounce, None);
+        drive(&shared, &bounce[..1], None);
+        assert!(faults.lock().unwrap().is_empty());
+        drive(&shared, &bounce[1..], None);
+        assert_eq!(*faults.lock().unwrap(), vec![("knob".to_owned(), 4)]);
+
+        // a completed detent resets the counter
+        shared
+            .decoder
+            .store(pack(0b00, Direction::None), Ordering::SeqCst);
+        drive(&shared, &bounce[..1], None);
+        drive(&shared, &COUNTER_CLOCKWISE[1..], None);
+        drive(&shared, &bounce, None);
+        assert_eq!(faults.lock().unwrap().len(), 1);
+        assert_eq!(log.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_handle_edge_counts_skipped_states() {
+        let (shared, _) = recording_shared(None, false);
+        drive(&shared, &CLOCKWISE, None);
+        assert_eq!(shared.skipped_count(), 0);
+
+        // the rising CLK edge of the next detent is lost, so CLK reports falling twice in a row
+        drive(&shared, &CLOCKWISE[..2], None);
+        drive(&shared, &CLOCKWISE[3..], None);
+        drive(&shared, &CLOCKWISE[..1], None);
+        assert_eq!(shared.skipped_count(), 1);
+    }
+
+    #[test]
+    fn test_calibration_report() {
+        let (shared, _) = recording_shared(None, false);
+        drive(&shared, &CLOCKWISE, None);
+        let before = shared.metrics();
+        for _ in 0..20 {
+            drive(&shared, &CLOCKWISE, None);
+        }
+        // a bounce of CLK
+        drive(&shared, &CLOCKWISE[2..3], None);
+        let report = CalibrationReport::between(&before, &shared.metrics());
+        assert_eq!(
+            report,
+            CalibrationReport {
+                edges: 81,
+                detents: 20,
+                rejected: 1
+            }
+        );
+        assert_eq!(report.edges_per_detent(), Some(4.05));
+        assert_eq!(report.edges_per_click(40), Some(2.025));
+        assert_eq!(report.edges_per_click(0), None);
+    }
+
+    #[test]
+    fn test_handle_edge_metrics() {
+        let (shared, _) = recording_shared(None, true);
+        assert_eq!(shared.metrics(), EncoderMetrics::default());
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        shared.handle_edge(Pin::Dt, Trigger::RisingEdge, None, Duration::from_millis(5));
+        assert_eq!(
+            shared.metrics(),
+            EncoderMetrics {
+                total_triggers: 13,
+                clockwise: 2,
+                counter_clockwise: 1,
+                rejected_transitions: 1,
+                last_event: Some(Duration::from_millis(5)),
+                dropped_detents: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_clk_edge() {
+        let (shared, log) = recording_shared(Some("knob_shifted"), false);
+        shared.handle_clk_edge(Level::High, Some(false), Duration::ZERO);
+        shared.handle_clk_edge(Level::Low, Some(true), Duration::from_millis(5));
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::Clockwise),
+                ("knob_shifted".to_owned(), Direction::CounterClockwise)
+            ]
+        );
+        let metrics = shared.metrics();
+        assert_eq!(metrics.total_triggers, 2);
+        assert_eq!(metrics.last_event, Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn test_set_names() {
+        let (shared, log) = recording_shared(Some("knob_shifted"), false);
+        drive(&shared, &CLOCKWISE, Some(false));
+        shared.set_names("menu", Some("menu_fine"));
+        drive(&shared, &CLOCKWISE, Some(false));
+        drive(&shared, &CLOCKWISE, Some(true));
+        assert_eq!(&*shared.name(), "menu");
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::Clockwise),
+                ("menu".to_owned(), Direction::Clockwise),
+                ("menu_fine".to_owned(), Direction::Clockwise)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_index() {
+        static INDEXED: AtomicU32 = AtomicU32::new(0);
+        let (mut shared, _) = recording_shared(None, false);
+        shared.on_index = Some(|_| {
+            INDEXED.fetch_add(1, Ordering::SeqCst);
+        });
+        drive(&shared, &CLOCKWISE, None);
+        shared.handle_index();
+        assert_eq!(shared.position.load(Ordering::SeqCst), 1);
+
+        shared.index_home = Some(90);
+        shared.handle_index();
+        assert_eq!(shared.position.load(Ordering::SeqCst), 90);
+        assert_eq!(INDEXED.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_min_detent_interval() {
+        let (mut shared, log) = recording_shared(None, false);
+        shared.min_detent_interval = Some(Duration::from_millis(30));
+        for ms in [0, 10, 29, 30, 100] {
+            shared.handle_clk_edge(Level::High, None, Duration::from_millis(ms));
+        }
+        assert_eq!(log.lock().unwrap().len(), 3);
+        assert_eq!(shared.position.load(Ordering::SeqCst), 3);
+        assert_eq!(shared.metrics().clockwise, 5);
+    }
+
+    #[test]
+    fn test_min_detent_interval_across_threads() {
+        let (mut shared, _log) = recording_shared(None, false);
+        shared.min_detent_interval = Some(Duration::from_secs(60));
+        let barrier = std::sync::Barrier::new(2);
+        let passed = std::sync::atomic::AtomicUsize::new(0);
+
+        // both pins' interrupt threads complete detents within the same interval
+        std::thread::scope(|scope| {
+            for offset in 0..2 {
+                let (shared, barrier, passed) = (&shared, &barrier, &passed);
+                scope.spawn(move || {
+                    barrier.wait();
+                    for ms in (offset..1000).step_by(2) {
+                        if !shared.too_soon(Duration::from_millis(ms)) {
+                            passed.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(passed.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_reversal_confirm_suppresses_single_reverse_detent() {
+        let (mut shared, log) = recording_shared(None, false);
+        shared.reversal_confirm = 2;
+        // a clockwise spin with one spurious counter-clockwise pulse in the middle
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        drive(&shared, &CLOCKWISE, None);
+        let directions: Vec<Direction> = log.lock().unwrap().iter().map(|(_, d)| *d).collect();
+        assert_eq!(directions, vec![Direction::Clockwise; 3]);
+        assert_eq!(shared.position.load(Ordering::SeqCst), 3);
+
+        // a real reversal is reported with its second detent
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(log.lock().unwrap().len(), 5);
+        assert_eq!(shared.position.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_reversal_confirm_disabled() {
+        let (shared, log) = recording_shared(None, false);
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        drive(&shared, &CLOCKWISE, None);
+        assert_eq!(log.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_handle_edge_reset_after_stall() {
+        let (mut shared, log) = recording_shared(None, false);
+        shared.reset_after = Some(Duration::from_millis(100));
+
+        // half a clockwise turn, then the knob rests until the timeout passes
+        shared.handle_edge(
+            Pin::Clk,
+            Trigger::FallingEdge,
+            None,
+            Duration::from_millis(0),
+        );
+        shared.handle_edge(
+            Pin::Dt,
+            Trigger::FallingEdge,
+            None,
+            Duration::from_millis(10),
+        );
+        shared.handle_edge(
+            Pin::Clk,
+            Trigger::RisingEdge,
+            None,
+            Duration::from_millis(500),
+        );
+        assert_eq!(
+            unpack(shared.decoder.load(Ordering::SeqCst)),
+            (0b00, Direction::None)
+        );
+
+        // without the reset the next DT edge would have completed a clockwise detent
+        shared.handle_edge(
+            Pin::Dt,
+            Trigger::RisingEdge,
+            None,
+            Duration::from_millis(510),
+        );
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_handle_edge_long_mixed_direction_sequence() {
+        let (shared, log) = recording_shared(None, false);
+        // every third detent turns back, across 40000 edges on a single thread
+        for i in 0..10_000 {
+            match i % 3 {
+                0 => drive(&shared, &COUNTER_CLOCKWISE, None),
+                _ => drive(&shared, &CLOCKWISE, None),
+            }
+        }
+        let log = log.lock().unwrap();
+        let clockwise = log
+            .iter()
+            .filter(|(_, direction)| *direction == Direction::Clockwise)
+            .count();
+        assert_eq!(log.len(), 10_000);
+        assert_eq!(clockwise, 6_666);
+        assert_eq!(shared.skipped_count(), 0);
+    }
+
+    #[test]
+    fn test_seed_from_pin_levels() {
+        let (mut shared, _) = recording_shared(None, false);
+        shared.seed(Level::High, Level::High);
+        assert_eq!(
+            unpack(shared.decoder.load(Ordering::SeqCst)),
+            (0b00, Direction::None)
+        );
+
+        // parked half way through a clockwise turn, with both pins pulled low
+        shared.seed(Level::Low, Level::Low);
+        assert_eq!(
+            unpack(shared.decoder.load(Ordering::SeqCst)),
+            (0b11, Direction::None)
+        );
+        drive(&shared, &CLOCKWISE[2..], None);
+        assert_eq!(shared.metrics().rejected_transitions, 0);
+        assert_eq!(unpack(shared.decoder.load(Ordering::SeqCst)).0, 0b00);
+    }
+
+    #[test]
+    fn test_resync_discards_half_detent() {
+        let (shared, log) = recording_shared(None, false);
+        drive(&shared, &CLOCKWISE[..2], None);
+        assert_eq!(
+            unpack(shared.decoder.load(Ordering::SeqCst)),
+            (0b11, Direction::Clockwise)
+        );
+
+        // the knob was turned back to rest while the interrupts were masked
+        shared.resync(Level::High, Level::High);
+        assert_eq!(
+            unpack(shared.decoder.load(Ordering::SeqCst)),
+            (0b00, Direction::None)
+        );
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![("knob".to_owned(), Direction::CounterClockwise)]
+        );
+        assert_eq!(shared.metrics().rejected_transitions, 0);
+    }
+
+    #[test]
+    fn test_handle_edge_on_detent_before_callback() {
+        static ORDER: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+        let mut shared = Shared::new(
+            "knob".to_owned(),
+            None,
+            false,
+            untimed(|name, direction| ORDER.lock().unwrap().push(format!("{name} {direction}"))),
+        );
+        shared.set_on_detent(|name| ORDER.lock().unwrap().push(format!("{name} click")));
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE[..2], None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(
+            *ORDER.lock().unwrap(),
+            vec!["knob click", "knob CW", "knob click", "knob CCW"]
+        );
+    }
+
+    #[test]
+    fn test_threaded_dispatch_does_not_stall_edges() {
+        let (mut shared, _) = recording_shared(None, false);
+        let (sender, done) = std::sync::mpsc::channel();
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = std::sync::Mutex::new(released);
+        // the callback blocks until the edges were handled, which would stall them inline
+        shared.add_callback(Arc::new(move |detent| {
+            let _ = released
+                .lock()
+                .unwrap()
+                .recv_timeout(Duration::from_secs(5));
+            sender.send(detent.direction).unwrap();
+        }));
+        let queue = shared.queue_detents(DISPATCH_QUEUE_CAPACITY, OverflowPolicy::DropNewest);
+        let shared = Arc::new(shared);
+        Shared::spawn_dispatcher(&shared, queue);
+
+        for _ in 0..3 {
+            drive(&shared, &CLOCKWISE, None);
+        }
+        assert!(done.try_recv().is_err());
+        drop(release);
+        for _ in 0..3 {
+            assert_eq!(
+                done.recv_timeout(Duration::from_secs(1)),
+                Ok(Direction::Clockwise)
+            );
+        }
+    }
+
+    #[test]
+    fn test_handle_edge_on_phase() {
+        static PHASES: std::sync::Mutex<Vec<u8>> = std::sync::Mutex::new(Vec::new());
+        let (mut shared, _) = recording_shared(None, false);
+        shared.set_on_phase(|_, phase| PHASES.lock().unwrap().push(phase));
+        drive(&shared, &CLOCKWISE, None);
+        // a repeated CLK edge is reported although the decoder rejects it
+        drive(&shared, &CLOCKWISE[2..3], None);
+        assert_eq!(*PHASES.lock().unwrap(), vec![0b01, 0b11, 0b10, 0b00, 0b00]);
+    }
+
+    #[test]
+    fn test_handle_edge_raw_edges() {
+        static EDGES: std::sync::Mutex<Vec<(Pin, u8)>> = std::sync::Mutex::new(Vec::new());
+        let (mut shared, log) = recording_shared(None, false);
+        shared.set_raw_edges(|_, pin, level| EDGES.lock().unwrap().push((pin, level)));
+        drive(&shared, &CLOCKWISE, None);
+        // a repeated edge the decoder rejects is passed on as well
+        drive(&shared, &CLOCKWISE[3..], None);
+        assert_eq!(
+            *EDGES.lock().unwrap(),
+            vec![
+                (Pin::Clk, 1),
+                (Pin::Dt, 1),
+                (Pin::Clk, 0),
+                (Pin::Dt, 0),
+                (Pin::Dt, 0)
+            ]
+        );
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(shared.position.load(Ordering::Acquire), 0);
+        // the decoder followed the pins back to rest
+        assert_eq!(unpack(shared.decoder.load(Ordering::Acquire)).0, 0b00);
+    }
+
+    #[test]
+    fn test_raw_edges_make_callback_optional() {
+        let builder = || Encoder::builder().name("knob").dt_pin(17).clk_pin(27);
+        assert!(matches!(
+            builder().required(),
+            Err(EncoderError::MissingField {
+                field: "callback",
+                ..
+            })
+        ));
+        assert!(builder().raw_edges(|_, _, _| {}).required().is_ok());
+    }
+
+    #[test]
+    fn test_rest_state_both_low() {
+        static PHASES: std::sync::Mutex<Vec<u8>> = std::sync::Mutex::new(Vec::new());
+        // resting with both pins low, every edge of a clockwise detent is inverted
+        let inverted: Vec<(Pin, Trigger)> = CLOCKWISE
+            .iter()
+            .map(|&(pin, trigger)| match trigger {
+                Trigger::FallingEdge => (pin, Trigger::RisingEdge),
+                _ => (pin, Trigger::FallingEdge),
+            })
+            .collect();
+
+        let (shared, log) = recording_shared(None, false);
+        drive(&shared, &inverted, None);
+        assert!(log.lock().unwrap().is_empty());
+
+        let (mut shared, log) = recording_shared(None, false);
+        shared.set_rest_state(0b11);
+        shared.seed(Level::Low, Level::Low);
+        shared.set_on_phase(|_, phase| PHASES.lock().unwrap().push(phase));
+        drive(&shared, &inverted, None);
+        drive(&shared, &inverted, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![("knob".to_owned(), Direction::Clockwise); 2]
+        );
+        assert_eq!(shared.position.load(Ordering::Acquire), 2);
+        // phases are reported as read from the pins
+        assert_eq!(PHASES.lock().unwrap()[..4], [0b10, 0b00, 0b01, 0b11]);
+    }
+
+    #[test]
+    fn test_rest_state_validated() {
+        let builder = || {
+            Encoder::builder()
+                .name("knob")
+                .dt_pin(17)
+                .clk_pin(27)
+                .callback(|_, _| {})
+        };
+        assert!(builder().rest_state(0b11).required().is_ok());
+        for invalid in [
+            builder().rest_state(0b100),
+            builder()
+                .rest_state(0b01)
+                .decode_mode(DecodeMode::ClkInterruptDtRead),
+        ] {
+            assert!(matches!(
+                invalid.required(),
+                Err(EncoderError::InvalidRestState(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_phase_trace_guard() {
+        static PHASES: std::sync::Mutex<Vec<u8>> = std::sync::Mutex::new(Vec::new());
+        let (shared, _) = recording_shared(None, false);
+        let shared = Arc::new(shared);
+        let guard = shared.add_phase_trace(|_, phase| PHASES.lock().unwrap().push(phase));
+        drive(&shared, &CLOCKWISE[..2], None);
+        drop(guard);
+        drive(&shared, &CLOCKWISE[2..], None);
+        assert_eq!(*PHASES.lock().unwrap(), vec![0b01, 0b11]);
+        assert!(shared.phase_traces.read().unwrap().is_empty());
+
+        // a guard outliving the encoder has nothing left to remove
+        let guard = shared.add_phase_trace(|_, _| {});
+        drop(shared);
+        drop(guard);
+    }
+
+    #[test]
+    fn test_measure_jitter() {
+        let (shared, _) = recording_shared(None, false);
+        assert_eq!(shared.measure_jitter(Duration::from_millis(10)), 0.0);
+        assert_eq!(shared.measure_jitter(Duration::ZERO), 0.0);
+
+        // CLK bouncing while the knob rests, counted like within a measurement window
+        let before = shared.total_triggers.load(Ordering::Relaxed);
+        drive(&shared, &CLOCKWISE[..1], None);
+        drive(&shared, &CLOCKWISE[2..3], None);
+        let edges = shared.total_triggers.load(Ordering::Relaxed) - before;
+        assert_eq!(edges, 2);
+        assert_eq!(edge_rate(edges, Duration::from_millis(200)), 10.0);
+        assert_eq!(edge_rate(edges, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_wait_event() {
+        let (shared, log) = recording_shared(None, false);
+        let shared = Arc::new(shared);
+        assert_eq!(
+            shared.wait_event(Some(Duration::from_millis(10))).unwrap(),
+            None
+        );
+
+        let turning = Arc::clone(&shared);
+        let turner = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            drive(&turning, &COUNTER_CLOCKWISE, None);
+            drive(&turning, &CLOCKWISE, None);
+        });
+        let event = shared.wait_event(None).unwrap();
+        turner.join().unwrap();
+        assert_eq!(
+            event,
+            Some(RotaryEvent::Turned {
+                name: "knob".to_owned(),
+                direction: Direction::CounterClockwise,
+                timestamp: Duration::ZERO,
+            })
+        );
+        // the temporary callback is gone, the registered one saw both detents
+        assert_eq!(shared.callbacks.read().unwrap().len(), 1);
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_context_callback() {
+        let turns = Arc::new(AtomicI32::new(0));
+        let shared = Shared::new(
+            "knob".to_owned(),
+            None,
+            false,
+            with_context(Arc::clone(&turns) as Context, |name, direction, context| {
+                assert_eq!(name, "knob");
+                let turns = context.downcast_ref::<AtomicI32>().unwrap();
+                match direction {
+                    Direction::Clockwise => turns.fetch_add(1, Ordering::SeqCst),
+                    _ => turns.fetch_sub(1, Ordering::SeqCst),
+                };
+            }),
+        );
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(turns.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_panicking_callback_keeps_decoding() {
+        static PANICS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+        let (mut shared, log) = recording_shared(None, false);
+        shared.on_panic = Some(|name, message| {
+            PANICS
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", name, message))
+        });
+        shared.add_callback(Arc::new(|detent| {
+            if detent.direction == Direction::Clockwise {
+                panic!("clockwise is not supported");
+            }
+        }));
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::Clockwise),
+                ("knob".to_owned(), Direction::CounterClockwise)
+            ]
+        );
+        assert_eq!(
+            *PANICS.lock().unwrap(),
+            vec!["knob: clockwise is not supported"]
+        );
+    }
+
+    #[test]
+    fn test_panicking_hook_keeps_decoding() {
+        let (mut shared, log) = recording_shared(None, false);
+        shared.set_on_detent(|_| panic!("no buzzer attached"));
+        shared.on_trigger_pin = Some(|_, _, _| panic!("no pin display attached"));
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_handle_edge_on_trigger_pin() {
+        static PINS: std::sync::Mutex<Vec<(Direction, Pin)>> = std::sync::Mutex::new(Vec::new());
+        let (mut shared, _) = recording_shared(None, false);
+        shared.set_on_trigger_pin(|_, direction, pin| PINS.lock().unwrap().push((direction, pin)));
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        shared.handle_clk_edge(Level::High, None, Duration::ZERO);
+        assert_eq!(
+            *PINS.lock().unwrap(),
+            vec![
+                (Direction::Clockwise, Pin::Dt),
+                (Direction::CounterClockwise, Pin::Clk),
+                (Direction::Clockwise, Pin::Clk)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_ignores_disabled_trigger() {
+        let (shared, log) = recording_shared(None, false);
+        drive(&shared, &CLOCKWISE[..2], None);
+        shared.handle_edge(Pin::Clk, Trigger::Disabled, None, Duration::ZERO);
+        drive(&shared, &CLOCKWISE[2..], None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::Clockwise),
+                ("knob".to_owned(), Direction::CounterClockwise)
+            ]
+        );
+        let metrics = shared.metrics();
+        assert_eq!(metrics.total_triggers, 8);
+        assert_eq!(metrics.rejected_transitions, 0);
+    }
+
+    #[test]
+    fn test_step_position() {
+        let clamp = Some(Bounds {
+            min: 0,
+            max: 9,
+            mode: BoundMode::Clamp,
+        });
+        let wrap = Some(Bounds {
+            min: 0,
+            max: 9,
+            mode: BoundMode::Wrap,
+        });
+        assert_eq!(step_position(5, 1, None), 6);
+        assert_eq!(step_position(0, -1, None), -1);
+        assert_eq!(step_position(9, 1, clamp), 9);
+        assert_eq!(step_position(0, -1, clamp), 0);
+        assert_eq!(step_position(9, 1, wrap), 0);
+        assert_eq!(step_position(0, -1, wrap), 9);
+        assert_eq!(step_position(42, 0, wrap), 2);
+        assert_eq!(step_position(i32::MAX, 1, clamp), 9);
+    }
+
+    #[test]
+    fn test_handle_edge_counts_position() {
+        let (shared, _) = recording_shared(None, false);
+        *shared.bounds.write().unwrap() = Some(Bounds {
+            min: 0,
+            max: 2,
+            mode: BoundMode::Wrap,
+        });
+        let positions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let positions_clone = Arc::clone(&positions);
+        shared.add_callback(Arc::new(move |detent| {
+            positions_clone.lock().unwrap().push(detent.position)
+        }));
+        for _ in 0..3 {
+            drive(&shared, &CLOCKWISE, None);
+        }
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(*positions.lock().unwrap(), vec![1, 2, 0, 2]);
+        assert_eq!(shared.position.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_log_target() {
+        let (mut shared, _) = recording_shared(None, false);
+        assert_eq!(shared.log_target(), "rotary_switch_helper::rotary_encoder");
+        shared.set_log_target("myapp::volume_knob");
+        assert_eq!(shared.log_target(), "myapp::volume_knob");
+    }
+
+    #[test]
+    fn test_pack_roundtrip() {
+        for state in 0..4 {
+            for direction in [
+                Direction::Clockwise,
+                Direction::CounterClockwise,
+                Direction::None,
+            ] {
+                assert_eq!(unpack(pack(state, direction)), (state, direction));
+            }
+        }
+    }
+
+    #[test]
+    fn test_handle_edge_concurrent_pins() {
+        let (shared, log) = recording_shared(None, false);
+        let edges: Vec<(Pin, Trigger)> = (0..2_000)
+            .flat_map(|i| match i % 4 {
+                0 => COUNTER_CLOCKWISE,
+                _ => CLOCKWISE,
+            })
+            .collect();
+        let next = std::sync::atomic::AtomicUsize::new(0);
+
+        // every edge is handled on the thread of its pin, like rppal's interrupt threads
+        std::thread::scope(|s| {
+            for pin in [Pin::Dt, Pin::Clk] {
+                let (shared, edges, next) = (&shared, &edges, &next);
+                s.spawn(move || {
+                    for (i, (_, trigger)) in edges.iter().enumerate().filter(|(_, e)| e.0 == pin) {
+                        while next.load(Ordering::Acquire) != i {
+                            std::thread::yield_now();
+                        }
+                        shared.handle_edge(pin, *trigger, None, Duration::ZERO);
+                        next.store(i + 1, Ordering::Release);
+                    }
+                });
+            }
+        });
+
+        let log = log.lock().unwrap();
+        let net: i32 = log
+            .iter()
+            .map(|(_, direction)| match direction {
+                Direction::Clockwise => 1,
+                _ => -1,
+            })
+            .sum();
+        assert_eq!(log.len(), 2_000);
+        assert_eq!(net, 1_000);
+        assert_eq!(shared.metrics().rejected_transitions, 0);
+    }
+
+    #[test]
+    fn test_handle_edge_disabled() {
+        let (shared, log) = recording_shared(None, false);
+        drive(&shared, &CLOCKWISE, None);
+        shared.set_enabled(false);
+        drive(&shared, &CLOCKWISE[..2], None);
+        shared.set_enabled(true);
+        // the half turn made while disabled is completed without losing sync
+        drive(&shared, &CLOCKWISE[2..], None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), Direction::Clockwise),
+                ("knob".to_owned(), Direction::Clockwise),
+                ("knob".to_owned(), Direction::CounterClockwise)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_directional_callbacks() {
+        static CLOCKWISE_LOG: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+        let (shared, log) = recording_shared(None, true);
+        shared.add_callback(directional(Direction::Clockwise, |name| {
+            CLOCKWISE_LOG.lock().unwrap().push(name.to_owned())
+        }));
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        // reversed, so only the physically counter-clockwise turn is reported as clockwise
+        assert_eq!(*CLOCKWISE_LOG.lock().unwrap(), vec!["knob".to_owned()]);
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_handle_edge_shifted_flag() {
+        static SHIFTED_LOG: std::sync::Mutex<Vec<(String, bool)>> =
+            std::sync::Mutex::new(Vec::new());
+
+        let (shared, _) = recording_shared(Some("knob_shifted"), false);
+        shared.add_callback(shift_aware(|name, _, shifted| {
+            SHIFTED_LOG.lock().unwrap().push((name.to_owned(), shifted))
+        }));
+        drive(&shared, &CLOCKWISE, Some(false));
+        drive(&shared, &CLOCKWISE, Some(true));
+        assert_eq!(
+            *SHIFTED_LOG.lock().unwrap(),
+            vec![
+                ("knob".to_owned(), false),
+                ("knob_shifted".to_owned(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_passes_timestamp() {
+        let (shared, _) = recording_shared(None, false);
+        let timestamps = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let timestamps_clone = Arc::clone(&timestamps);
+        shared.add_callback(Arc::new(move |detent| {
+            timestamps_clone.lock().unwrap().push(detent.timestamp)
+        }));
+        for (i, (pin, trigger)) in CLOCKWISE.iter().enumerate() {
+            shared.handle_edge(*pin, *trigger, None, Duration::from_millis(i as u64));
+        }
+        assert_eq!(*timestamps.lock().unwrap(), vec![Duration::from_millis(3)]);
+    }
+
+    #[test]
+    fn test_rotary_event_from_detent() {
+        let detent = Detent {
+            name: "knob_shifted",
+            direction: Direction::Clockwise,
+            timestamp: Duration::from_millis(5),
+            pressed: true,
+            position: 1,
+            steps: 1,
+        };
+        assert_eq!(
+            RotaryEvent::from_detent("knob", &detent, false),
+            RotaryEvent::Turned {
+                name: "knob_shifted".to_owned(),
+                direction: Direction::Clockwise,
+                timestamp: Duration::from_millis(5)
+            }
+        );
+        assert_eq!(
+            RotaryEvent::from_detent("knob", &detent, true),
+            RotaryEvent::PressedTurn {
+                name: "knob".to_owned(),
+                direction: Direction::Clockwise,
+                timestamp: Duration::from_millis(5)
+            }
+        );
+    }
+
+    #[test]
+    fn test_rotary_event_delta() {
+        let timestamp = Duration::ZERO;
+        let name = "knob".to_owned();
+        let turned = |direction| RotaryEvent::Turned {
+            name: name.clone(),
+            direction,
+            timestamp,
+        };
+        assert_eq!(turned(Direction::Clockwise).delta(), 1);
+        assert_eq!(turned(Direction::CounterClockwise).delta(), -1);
+        let pressed = RotaryEvent::PressedTurn {
+            name: name.clone(),
+            direction: Direction::CounterClockwise,
+            timestamp,
+        };
+        assert_eq!(pressed.delta(), -1);
+        let batched = RotaryEvent::Batched {
+            name: name.clone(),
+            direction: Direction::CounterClockwise,
+            steps: 3,
+        };
+        assert_eq!(batched.delta(), -3);
+        assert_eq!(RotaryEvent::Fault { name, edges: 9 }.delta(), 0);
+    }
+
+    #[test]
+    fn test_acceleration_profiles() {
+        // detents 200ms, 50ms, 20ms and 10ms after the previous one
+        let timings = [0, 200, 250, 270, 280];
+        for (profile, deltas) in [
+            (AccelProfile::None, [1, 1, 1, 1, 1]),
+            (AccelProfile::Linear, [1, 1, 2, 5, 10]),
+            (AccelProfile::Exponential, [1, 1, 4, 16, 16]),
+        ] {
+            static DELTAS: std::sync::Mutex<Vec<i8>> = std::sync::Mutex::new(Vec::new());
+            DELTAS.lock().unwrap().clear();
+            let (mut shared, log) = recording_shared(None, false);
+            shared.accel = profile;
+            shared.add_callback(stepped(|_, delta| DELTAS.lock().unwrap().push(delta)));
+            for ms in timings {
+                shared.handle_clk_edge(Level::High, None, Duration::from_millis(ms));
+            }
+            assert_eq!(*DELTAS.lock().unwrap(), deltas, "{:?}", profile);
+            assert_eq!(
+                shared.position.load(Ordering::SeqCst),
+                deltas.iter().map(|&d| i32::from(d)).sum::<i32>()
+            );
+            // the other callbacks are called once per detent
+            assert_eq!(log.lock().unwrap().len(), 5);
+        }
+    }
+
+    #[test]
+    fn test_delta_callback_accumulates() {
+        static SCROLL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+        let (shared, _) = recording_shared(None, false);
+        shared.add_callback(stepped(|_, delta| {
+            SCROLL.fetch_add(delta.into(), Ordering::SeqCst);
+        }));
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &CLOCKWISE, None);
+        drive(&shared, &COUNTER_CLOCKWISE, None);
+        assert_eq!(SCROLL.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_handle_edge_switch_without_shifted_name() {
+        let (shared, _) = recording_shared(None, false);
+        let pressed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pressed_clone = Arc::clone(&pressed);
+        shared.add_callback(Arc::new(move |detent| {
+            pressed_clone
+                .lock()
+                .unwrap()
+                .push((detent.name.to_owned(), detent.pressed))
+        }));
+        drive(&shared, &CLOCKWISE, Some(true));
+        drive(&shared, &CLOCKWISE, Some(false));
+        assert_eq!(
+            *pressed.lock().unwrap(),
+            vec![("knob".to_owned(), true), ("knob".to_owned(), false)]
+        );
     }
 }
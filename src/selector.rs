@@ -0,0 +1,281 @@
+//! Absolute position selector switches, e.g. 12-position rotary selector knobs.
+//!
+//! Unlike quadrature encoders, a selector grounds one pin per position (break before make),
+//! so the active position is simply the pin that was pulled low last.
+//! [`GrayEncoder`] covers knobs that instead output their position as a gray code.
+
+use crate::error::EncoderError;
+use crate::util::{SharedPins, catch_panic};
+
+use log::trace;
+use rppal::gpio::{Event, Gpio, InputPin, Level, Trigger};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Marker for a position that has not been determined yet
+const UNKNOWN: usize = usize::MAX;
+
+pub struct Selector {
+    pins: Vec<InputPin>,
+    shared: Arc<Shared>,
+}
+
+/// Selector state shared between the selector and its interrupt handlers
+pub(crate) struct Shared {
+    name: String,
+    position: AtomicUsize,
+    callback: fn(&str, usize),
+}
+
+impl Selector {
+    /// Create a new selector switch
+    /// # Arguments
+    /// * `name` - Name of the selector
+    /// * `gpio` - Gpio instance to use for the selector
+    /// * `pins` - GPIO pin numbers, one per position; the index into `pins` is the reported position
+    /// * `callback` - Function to call with the new position whenever it changes
+    pub fn new(
+        name: &str,
+        gpio: &Gpio,
+        pins: &[u8],
+        callback: fn(&str, usize),
+    ) -> Result<Self, EncoderError> {
+        trace!("Initializing GPIO for selector {}", name);
+        validate_pins(name, pins)?;
+
+        let pins = pins
+            .iter()
+            .map(|&p| Ok(gpio.get(p)?.into_input_pullup()))
+            .collect::<Result<Vec<InputPin>, EncoderError>>()?;
+
+        let shared = Shared::new(name, callback);
+        // start from the current position without reporting it as a change
+        if let Some(position) = pins.iter().position(|p| p.read() == Level::Low) {
+            shared.position.store(position, Ordering::Release);
+        }
+
+        let mut selector = Self {
+            pins,
+            shared: Arc::new(shared),
+        };
+        selector
+            .enable_callbacks()
+            .map_err(EncoderError::InterruptFailed)?;
+        trace!("Selector {} initialized", name);
+        Ok(selector)
+    }
+
+    /// Name of the selector
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    /// Currently selected position, `None` if no position has been detected yet
+    pub fn position(&self) -> Option<usize> {
+        self.shared.position()
+    }
+
+    fn enable_callbacks(&mut self) -> Result<(), rppal::gpio::Error> {
+        trace!("Enabling callbacks for selector {}", self.shared.name);
+        for (position, pin) in self.pins.iter_mut().enumerate() {
+            let shared = Arc::clone(&self.shared);
+            pin.set_async_interrupt(Trigger::FallingEdge, None, move |_: Event| {
+                shared.select(position);
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Absolute encoder reporting its position as a gray code across several pins,
+/// e.g. a 16-position knob with 4 pins. Pin `i` carries bit `i` of the code,
+/// a pin pulled low reads as `1`.
+pub struct GrayEncoder {
+    pins: SharedPins<Vec<InputPin>>,
+    shared: Arc<Shared>,
+}
+
+impl GrayEncoder {
+    /// Create a new gray code encoder
+    /// # Arguments
+    /// * `name` - Name of the encoder
+    /// * `gpio` - Gpio instance to use for the encoder
+    /// * `pins` - GPIO pin numbers, least significant bit first (at most 8)
+    /// * `callback` - Function to call with the decoded position whenever it changes
+    pub fn new(
+        name: &str,
+        gpio: &Gpio,
+        pins: &[u8],
+        callback: fn(&str, usize),
+    ) -> Result<Self, EncoderError> {
+        trace!("Initializing GPIO for gray encoder {}", name);
+        validate_pins(name, pins)?;
+        if pins.len() > 8 {
+            return Err(EncoderError::TooManyPins {
+                name: name.to_owned(),
+                count: pins.len(),
+                max: 8,
+            });
+        }
+
+        let pins = pins
+            .iter()
+            .map(|&p| Ok(gpio.get(p)?.into_input_pullup()))
+            .collect::<Result<Vec<InputPin>, EncoderError>>()?;
+
+        let shared = Shared::new(name, callback);
+        shared.position.store(
+            gray_to_binary(read_code(&pins) as u8) as usize,
+            Ordering::Release,
+        );
+
+        let encoder = Self {
+            pins: SharedPins::new(pins),
+            shared: Arc::new(shared),
+        };
+        encoder
+            .enable_callbacks()
+            .map_err(EncoderError::InterruptFailed)?;
+        trace!("Gray encoder {} initialized", name);
+        Ok(encoder)
+    }
+
+    /// Name of the encoder
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    /// Currently decoded position
+    pub fn position(&self) -> Option<usize> {
+        self.shared.position()
+    }
+
+    fn enable_callbacks(&self) -> Result<(), rppal::gpio::Error> {
+        trace!("Enabling callbacks for gray encoder {}", self.shared.name);
+        self.pins.with(|pins| {
+            for pin in pins.iter_mut() {
+                let all_pins = self.pins.handle();
+                let shared = Arc::clone(&self.shared);
+                pin.set_async_interrupt(Trigger::Both, None, move |_: Event| {
+                    // several bits may change almost at once, so read all pins instead of
+                    // applying the single edge that triggered this interrupt
+                    if let Some(code) = all_pins.with(|pins| read_code(pins)) {
+                        shared.select(gray_to_binary(code as u8) as usize);
+                    }
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Convert a gray code into the plain binary number it encodes
+pub fn gray_to_binary(code: u8) -> u8 {
+    let mut binary = code;
+    let mut shifted = code >> 1;
+    while shifted != 0 {
+        binary ^= shifted;
+        shifted >>= 1;
+    }
+    binary
+}
+
+/// Combine the pin levels into a code, pin `i` being bit `i` and low meaning `1`
+pub(crate) fn read_code(pins: &[InputPin]) -> u32 {
+    pins.iter()
+        .enumerate()
+        .filter(|(_, pin)| pin.read() == Level::Low)
+        .fold(0, |code, (i, _)| code | 1 << i)
+}
+
+pub(crate) fn validate_pins(name: &str, pins: &[u8]) -> Result<(), EncoderError> {
+    if pins.is_empty() {
+        return Err(EncoderError::NoPins(name.to_owned()));
+    }
+    for (i, pin) in pins.iter().enumerate() {
+        if let Some(j) = pins[..i].iter().position(|p| p == pin) {
+            return Err(EncoderError::DuplicatePin {
+                pin: *pin,
+                first: format!("{} position {}", name, j),
+                second: format!("{} position {}", name, i),
+            });
+        }
+    }
+    Ok(())
+}
+
+impl Shared {
+    pub(crate) fn new(name: &str, callback: fn(&str, usize)) -> Self {
+        Self {
+            name: name.to_owned(),
+            position: AtomicUsize::new(UNKNOWN),
+            callback,
+        }
+    }
+
+    fn position(&self) -> Option<usize> {
+        match self.position.load(Ordering::Acquire) {
+            UNKNOWN => None,
+            position => Some(position),
+        }
+    }
+
+    /// Record that the pin of `position` was pulled low, reporting only actual changes
+    /// so contact bounce on the same position is ignored
+    pub(crate) fn select(&self, position: usize) {
+        if self.position.swap(position, Ordering::AcqRel) != position {
+            trace!("Selector {} moved to position {}", self.name, position);
+            catch_panic(module_path!(), &self.name, || {
+                (self.callback)(&self.name, position)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    static LOG: Mutex<Vec<(String, usize)>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_select_reports_changes_only() {
+        let shared = Shared::new("mode", |name, position| {
+            LOG.lock().unwrap().push((name.to_owned(), position))
+        });
+        assert_eq!(shared.position(), None);
+
+        shared.select(3);
+        shared.select(3);
+        shared.select(4);
+        assert_eq!(shared.position(), Some(4));
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec![("mode".to_owned(), 3), ("mode".to_owned(), 4)]
+        );
+    }
+
+    #[test]
+    fn test_gray_to_binary() {
+        let gray: Vec<u8> = (0..16u8).map(|n| n ^ (n >> 1)).collect();
+        assert_eq!(gray[..4], [0b0000, 0b0001, 0b0011, 0b0010]);
+        for (n, code) in gray.into_iter().enumerate() {
+            assert_eq!(gray_to_binary(code) as usize, n);
+        }
+        assert_eq!(gray_to_binary(0b1000_0000), 0b1111_1111);
+    }
+
+    #[test]
+    fn test_validate_pins() {
+        assert!(validate_pins("mode", &[5, 6, 13]).is_ok());
+        assert_eq!(
+            validate_pins("mode", &[]).unwrap_err().to_string(),
+            "mode needs at least one pin"
+        );
+        assert_eq!(
+            validate_pins("mode", &[5, 6, 5]).unwrap_err().to_string(),
+            "GPIO pin 5 is used by both mode position 0 and mode position 2"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! A single shift button shared by several rotary encoders, e.g. one SHIFT key on a panel
+//! with three knobs: while it is held, all knobs report their shifted names.
+//!
+//! The button's state is kept in one atomic flag, updated by the button's interrupt and read
+//! by the encoders whenever they complete a detent. Encoders with their own switch pin keep
+//! using that instead.
+
+use crate::error::EncoderError;
+use crate::util::{log_non_edge, trigger_to_pressed};
+
+use log::trace;
+use rppal::gpio::{Event, Gpio, InputPin, Level, Trigger};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub struct SharedShift {
+    /// Keeps the interrupt registered, `None` for a simulated button
+    #[allow(dead_code)]
+    pin: Option<InputPin>,
+    pressed: Arc<AtomicBool>,
+}
+
+impl SharedShift {
+    /// Watch the shift button on `pin_number`, which pulls the pin low while pressed
+    /// # Arguments
+    /// * `gpio` - Gpio instance to use for the button
+    /// * `pin_number` - GPIO pin number of the button, read with a pull-up
+    pub fn new(gpio: &Gpio, pin_number: u8) -> Result<Self, EncoderError> {
+        Self::new_with_log_target(gpio, pin_number, module_path!())
+    }
+
+    /// Watch the shift button on `pin_number` like [`SharedShift::new`], logging under
+    /// `log_target`, e.g. `myapp::shift` to filter its messages with `RUST_LOG=myapp::shift=trace`
+    /// # Arguments
+    /// * `gpio` - Gpio instance to use for the button
+    /// * `pin_number` - GPIO pin number of the button, read with a pull-up
+    /// * `log_target` - Target of the button's log messages
+    pub fn new_with_log_target(
+        gpio: &Gpio,
+        pin_number: u8,
+        log_target: &str,
+    ) -> Result<Self, EncoderError> {
+        trace!(target: log_target, "Initializing GPIO for shared shift on pin {}", pin_number);
+        let mut pin = gpio.get(pin_number)?.into_input_pullup();
+        let pressed = Arc::new(AtomicBool::new(pin.read() == Level::Low));
+        let state = Arc::clone(&pressed);
+        let log_target = log_target.to_owned();
+        pin.set_async_interrupt(
+            Trigger::Both,
+            None,
+            move |event: Event| match trigger_to_pressed(event.trigger, true) {
+                Some(pressed) => state.store(pressed, Ordering::Release),
+                None => log_non_edge(&log_target, event.trigger),
+            },
+        )
+        .map_err(EncoderError::InterruptFailed)?;
+        Ok(Self {
+            pin: Some(pin),
+            pressed,
+        })
+    }
+
+    /// Shift button without GPIO, pressed and released with
+    /// [`SharedShift::simulate_press`] and [`SharedShift::simulate_release`]
+    #[cfg(feature = "sim")]
+    pub fn simulated() -> Self {
+        Self {
+            pin: None,
+            pressed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Simulate pressing the shift button
+    #[cfg(feature = "sim")]
+    pub fn simulate_press(&self) {
+        self.pressed.store(true, Ordering::Release);
+    }
+
+    /// Simulate releasing the shift button
+    #[cfg(feature = "sim")]
+    pub fn simulate_release(&self) {
+        self.pressed.store(false, Ordering::Release);
+    }
+
+    /// Whether the shift button is held down
+    pub fn is_pressed(&self) -> bool {
+        self.pressed.load(Ordering::Acquire)
+    }
+
+    /// Flag read by the encoders sharing this button
+    pub(crate) fn state(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pressed)
+    }
+}
@@ -0,0 +1,452 @@
+//! Simulated encoder for testing callback logic without a Raspberry Pi.
+//!
+//! [`SimulatedEncoder`] feeds synthetic edges through the same decoder and switch handling
+//! the GPIO backed encoders use, so downstream crates can test against the real event semantics.
+
+use crate::clock::{Clock, SystemClock};
+use crate::rotary_encoder::{self, Direction, EncoderMetrics, Pin};
+use crate::shift::SharedShift;
+use crate::switch_encoder::{self, SwitchMetrics, SwitchMode};
+
+use rppal::gpio::Trigger;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const CLOCKWISE: [(Pin, Trigger); 4] = [
+    (Pin::Clk, Trigger::FallingEdge),
+    (Pin::Dt, Trigger::FallingEdge),
+    (Pin::Clk, Trigger::RisingEdge),
+    (Pin::Dt, Trigger::RisingEdge),
+];
+
+const COUNTER_CLOCKWISE: [(Pin, Trigger); 4] = [
+    (Pin::Dt, Trigger::FallingEdge),
+    (Pin::Clk, Trigger::FallingEdge),
+    (Pin::Dt, Trigger::RisingEdge),
+    (Pin::Clk, Trigger::RisingEdge),
+];
+
+/// Rotary encoder with optional built-in switch, driven programmatically instead of by GPIO
+pub struct SimulatedEncoder {
+    rotary: rotary_encoder::Shared,
+    switch: Option<SimulatedSwitch>,
+    shift_configured: bool,
+    pressed: Arc<AtomicBool>,
+    clock: Arc<dyn Clock>,
+    started: Instant,
+}
+
+/// Switch driven programmatically instead of by GPIO
+pub struct SimulatedSwitch {
+    switch: switch_encoder::Shared,
+    pressed: AtomicBool,
+    clock: Arc<dyn Clock>,
+    started: Instant,
+}
+
+impl SimulatedEncoder {
+    /// Create a new simulated rotary encoder
+    /// # Arguments
+    /// * `encoder_name` - Name of the encoder
+    /// * `encoder_name_shifted` - Name of the encoder while the switch is pressed
+    /// * `callback` - Function to call when the encoder is turned
+    pub fn new(
+        encoder_name: &str,
+        encoder_name_shifted: Option<&str>,
+        callback: fn(&str, Direction),
+    ) -> Self {
+        Self::from_parts(
+            encoder_name,
+            encoder_name_shifted,
+            encoder_name_shifted.is_some(),
+            false,
+            rotary_encoder::untimed(callback),
+        )
+    }
+
+    /// Create a new simulated rotary encoder like a GPIO backed one
+    /// # Arguments
+    /// * `has_switch` - Whether the encoder has a switch, i.e. shifting is tracked
+    /// * `reversed` - Flip the reported direction
+    pub(crate) fn from_parts(
+        encoder_name: &str,
+        encoder_name_shifted: Option<&str>,
+        has_switch: bool,
+        reversed: bool,
+        callback: rotary_encoder::Callback,
+    ) -> Self {
+        Self {
+            rotary: rotary_encoder::Shared::new(
+                encoder_name.to_owned(),
+                encoder_name_shifted.map(|s| s.to_owned()),
+                reversed,
+                callback,
+            ),
+            switch: None,
+            shift_configured: has_switch,
+            pressed: Arc::new(AtomicBool::new(false)),
+            clock: Arc::new(SystemClock),
+            started: Instant::now(),
+        }
+    }
+
+    /// Report presses of the built-in switch like a [`switch_encoder::Encoder`]
+    /// # Arguments
+    /// * `switch_name` - Name of the switch
+    /// * `switch_name_long_press` - Name of the switch for long presses
+    /// * `time_threshold`- timer to hold a press before considered a long press, `None`
+    ///   disables long press detection
+    /// * `callback` - Function to call when the switch is pressed or released
+    pub fn with_switch(
+        mut self,
+        switch_name: &str,
+        switch_name_long_press: Option<&str>,
+        time_threshold: Option<Duration>,
+        callback: fn(&str, bool),
+    ) -> Self {
+        self.switch = Some(
+            SimulatedSwitch::new(
+                switch_name,
+                switch_name_long_press,
+                time_threshold,
+                callback,
+            )
+            .with_clock(Arc::clone(&self.clock)),
+        );
+        self
+    }
+
+    /// Shift with a button shared with other encoders, e.g. [`SharedShift::simulated`],
+    /// instead of the encoder's own switch. Pressing the encoder's switch presses it as well.
+    pub fn with_shared_shift(mut self, shift: &SharedShift) -> Self {
+        self.pressed = shift.state();
+        self.shift_configured = true;
+        self
+    }
+
+    /// Take the time of simulated edges from `clock`, e.g. a [`crate::clock::MockClock`]
+    /// to step through timing without sleeping. Also applies to the switch.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.started = clock.now();
+        self.switch = self
+            .switch
+            .map(|switch| switch.with_clock(Arc::clone(&clock)));
+        self.clock = clock;
+        self
+    }
+
+    /// Name of the encoder
+    pub fn name(&self) -> Arc<str> {
+        self.rotary.name()
+    }
+
+    /// Name of the encoder while the switch is pressed, if configured
+    pub fn shifted_name(&self) -> Option<Arc<str>> {
+        self.rotary.shifted_name()
+    }
+
+    /// Report further detents under other names, like [`rotary_encoder::Encoder::set_names`]
+    pub fn set_names(&self, name: &str, shifted: Option<&str>) {
+        self.rotary.set_names(name, shifted);
+    }
+
+    /// Position counted from the simulated detents, like [`rotary_encoder::Encoder::position`]
+    pub fn position(&self) -> i32 {
+        self.rotary.position()
+    }
+
+    /// Direction of the last simulated detent, like
+    /// [`rotary_encoder::Encoder::last_triggered_direction`]
+    pub fn last_triggered_direction(&self) -> Direction {
+        self.rotary.last_triggered_direction()
+    }
+
+    /// Snapshot of the simulated edges and detents, like [`rotary_encoder::Encoder::metrics`]
+    pub fn metrics(&self) -> EncoderMetrics {
+        self.rotary.metrics()
+    }
+
+    /// Log under `target` instead of the module path
+    #[cfg(feature = "mock-gpio")]
+    pub(crate) fn log_target(mut self, target: &str) -> Self {
+        self.rotary.set_log_target(target);
+        self
+    }
+
+    /// Function to call on every detent before the callbacks
+    #[cfg(feature = "mock-gpio")]
+    pub(crate) fn on_detent(mut self, on_detent: fn(&str)) -> Self {
+        self.rotary.set_on_detent(on_detent);
+        self
+    }
+
+    /// Replace the callback the encoder was created with, keeping all added ones
+    #[cfg(feature = "mock-gpio")]
+    pub(crate) fn replace_callback(&self, callback: rotary_encoder::Callback) {
+        self.rotary.replace_callback(callback);
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    pub(crate) fn add_detent_callback(&self, callback: rotary_encoder::Callback) {
+        self.rotary.add_callback(callback);
+    }
+
+    /// Simulate turning the encoder clockwise by one detent
+    pub fn simulate_clockwise(&self) {
+        self.drive(&CLOCKWISE);
+    }
+
+    /// Simulate turning the encoder counter-clockwise by one detent
+    pub fn simulate_counter_clockwise(&self) {
+        self.drive(&COUNTER_CLOCKWISE);
+    }
+
+    /// Simulate pressing the switch, shifting subsequent turns
+    pub fn simulate_press(&self) {
+        self.pressed.store(true, Ordering::SeqCst);
+        if let Some(switch) = &self.switch {
+            switch.simulate_press();
+        }
+    }
+
+    /// Simulate releasing the switch
+    pub fn simulate_release(&self) {
+        self.pressed.store(false, Ordering::SeqCst);
+        if let Some(switch) = &self.switch {
+            switch.simulate_release();
+        }
+    }
+
+    fn drive(&self, edges: &[(Pin, Trigger)]) {
+        let shifted = self
+            .shift_configured
+            .then(|| self.pressed.load(Ordering::SeqCst));
+        for (pin, trigger) in edges {
+            self.rotary
+                .handle_edge(*pin, *trigger, shifted, self.elapsed());
+        }
+    }
+
+    /// Time since creation according to the clock, the timestamp of simulated edges
+    fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.started)
+    }
+}
+
+impl SimulatedSwitch {
+    /// Create a new simulated switch
+    /// # Arguments
+    /// * `switch_name` - Name of the switch
+    /// * `switch_name_long_press` - Name of the switch for long presses
+    /// * `time_threshold`- timer to hold a press before considered a long press, `None`
+    ///   disables long press detection
+    /// * `callback` - Function to call when the switch is pressed or released
+    pub fn new(
+        switch_name: &str,
+        switch_name_long_press: Option<&str>,
+        time_threshold: Option<Duration>,
+        callback: fn(&str, bool),
+    ) -> Self {
+        Self {
+            pressed: AtomicBool::new(false),
+            switch: switch_encoder::Shared::new(
+                switch_name,
+                switch_name_long_press,
+                true,
+                time_threshold,
+                callback,
+            ),
+            clock: Arc::new(SystemClock),
+            started: Instant::now(),
+        }
+    }
+
+    /// Take the time of simulated edges from `clock`, e.g. a [`crate::clock::MockClock`]
+    /// to test long presses without sleeping
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.started = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Time since creation according to the clock, the timestamp of simulated edges
+    fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.started)
+    }
+
+    /// Name of the switch
+    pub fn name(&self) -> Arc<str> {
+        self.switch.name()
+    }
+
+    /// Name reported for the release of a long press, if configured
+    pub fn long_press_name(&self) -> Option<Arc<str>> {
+        self.switch.long_press_name()
+    }
+
+    /// Register a function to call on every release with the time the switch was held
+    pub fn add_release_callback(&mut self, callback: fn(&str, Duration)) {
+        self.switch.add_release_callback(callback);
+    }
+
+    /// Log under `target` instead of the module path
+    #[cfg(feature = "mock-gpio")]
+    pub(crate) fn log_target(mut self, target: &str) -> Self {
+        self.switch.set_log_target(target);
+        self
+    }
+
+    /// Skip the release callbacks after a long press, like
+    /// [`switch_encoder::Encoder::set_suppress_long_release`]
+    pub fn suppress_long_release(self, suppress: bool) -> Self {
+        self.switch.set_suppress_long_release(suppress);
+        self
+    }
+
+    /// Change the long press threshold, like
+    /// [`switch_encoder::Encoder::set_long_press_threshold`]
+    pub fn set_long_press_threshold(&self, threshold: Option<Duration>) {
+        self.switch.set_long_press_threshold(threshold);
+    }
+
+    /// Report presses and releases or toggle a latched state, see [`SwitchMode`]
+    pub fn mode(mut self, mode: SwitchMode) -> Self {
+        self.switch.set_mode(mode);
+        self
+    }
+
+    /// Latched state in [`SwitchMode::Toggle`], like [`switch_encoder::Encoder::state`]
+    pub fn state(&self) -> bool {
+        self.switch.state()
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    pub(crate) fn replace_callback(&self, callback: fn(&str, bool)) {
+        self.switch.replace_callback(callback);
+    }
+
+    #[cfg(feature = "mock-gpio")]
+    pub(crate) fn add_listener(&self, listener: switch_encoder::Listener) {
+        self.switch.add_listener(listener);
+    }
+
+    /// Whether the switch is held down by [`SimulatedSwitch::simulate_press`], like
+    /// [`switch_encoder::Encoder::is_pressed`]
+    pub fn is_pressed(&self) -> bool {
+        self.pressed.load(Ordering::SeqCst)
+    }
+
+    /// Presses accepted so far, like [`switch_encoder::Encoder::press_count`]
+    pub fn press_count(&self) -> u64 {
+        self.switch.press_count()
+    }
+
+    /// Releases of accepted presses so far, like [`switch_encoder::Encoder::release_count`]
+    pub fn release_count(&self) -> u64 {
+        self.switch.release_count()
+    }
+
+    /// Snapshot of the simulated presses, like [`switch_encoder::Encoder::metrics`]
+    pub fn metrics(&self) -> SwitchMetrics {
+        self.switch.metrics()
+    }
+
+    /// Simulate pressing the switch
+    pub fn simulate_press(&self) {
+        self.pressed.store(true, Ordering::SeqCst);
+        self.switch
+            .handle_edge(Trigger::FallingEdge, self.elapsed());
+    }
+
+    /// Simulate releasing the switch
+    pub fn simulate_release(&self) {
+        self.pressed.store(false, Ordering::SeqCst);
+        self.switch.handle_edge(Trigger::RisingEdge, self.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::Mutex;
+
+    static ROTARY_LOG: Mutex<Vec<(String, Direction)>> = Mutex::new(Vec::new());
+    static SWITCH_LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+    fn rotary_callback(name: &str, direction: Direction) {
+        ROTARY_LOG
+            .lock()
+            .unwrap()
+            .push((name.to_owned(), direction));
+    }
+
+    fn switch_callback(name: &str, pressed: bool) {
+        SWITCH_LOG.lock().unwrap().push((name.to_owned(), pressed));
+    }
+
+    #[test]
+    fn test_simulated_rotation_and_shift() {
+        let encoder = SimulatedEncoder::new("sim", Some("sim_shifted"), rotary_callback)
+            .with_switch("sim_button", None, None, switch_callback);
+
+        encoder.simulate_clockwise();
+        encoder.simulate_press();
+        encoder.simulate_counter_clockwise();
+        encoder.simulate_release();
+        encoder.simulate_clockwise();
+
+        assert_eq!(
+            *ROTARY_LOG.lock().unwrap(),
+            vec![
+                ("sim".to_owned(), Direction::Clockwise),
+                ("sim_shifted".to_owned(), Direction::CounterClockwise),
+                ("sim".to_owned(), Direction::Clockwise),
+            ]
+        );
+        assert_eq!(
+            *SWITCH_LOG.lock().unwrap(),
+            vec![
+                ("sim_button".to_owned(), true),
+                ("sim_button".to_owned(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulated_long_press_with_mock_clock() {
+        static LONG_PRESS_LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+        let clock = Arc::new(MockClock::new());
+        let switch = SimulatedSwitch::new(
+            "button",
+            Some("button_long"),
+            Some(Duration::from_secs(2)),
+            |name, pressed| {
+                LONG_PRESS_LOG
+                    .lock()
+                    .unwrap()
+                    .push((name.to_owned(), pressed))
+            },
+        )
+        .with_clock(clock.clone());
+
+        switch.simulate_press();
+        clock.advance(Duration::from_secs(1));
+        switch.simulate_release();
+        switch.simulate_press();
+        clock.advance(Duration::from_secs(3));
+        switch.simulate_release();
+
+        assert_eq!(
+            *LONG_PRESS_LOG.lock().unwrap(),
+            vec![
+                ("button".to_owned(), true),
+                ("button".to_owned(), false),
+                ("button".to_owned(), true),
+                ("button_long".to_owned(), false)
+            ]
+        );
+    }
+}
@@ -1,20 +1,107 @@
-use rppal::gpio::{Event, Gpio, InputPin, Trigger};
+use rppal::gpio::{Event, Gpio, InputPin, Level, Trigger};
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::EncoderError;
+use crate::gesture::{Gesture, GestureDetector, GestureTiming};
+use crate::selector::{read_code, validate_pins};
+use crate::util::{SharedPins, catch_panic, log_non_edge, spawn_named, trigger_to_pressed};
 
-use anyhow::{Result, anyhow};
 use atomic_time::AtomicOptionDuration;
-use log::{error, trace};
-use std::sync::Arc;
-use std::sync::atomic::Ordering;
-use std::time::Duration;
+use log::trace;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, Weak};
+use std::time::{Duration, Instant};
+
+/// Callback invoked on every press and release with the reported name
+type Callback = fn(&str, bool);
+
+/// Callback invoked on release with the reported name and the time the switch was held
+type ReleaseCallback = fn(&str, Duration);
+
+/// Closure invoked on every press and release in addition to the callback
+pub(crate) type Listener = Arc<dyn Fn(&str, bool) + Send + Sync>;
+
+/// How long the press timer sleeps while no press is pending, bounding how long it
+/// outlives its encoder
+const PRESS_TIMER_IDLE: Duration = Duration::from_secs(1);
+
+/// Press held back until it lasted the minimum press time, see [`SwitchBuilder::min_press`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PressGate {
+    /// Released, or no minimum press time configured
+    Idle,
+    /// Pressed at the given time, not reported yet
+    Pending(Instant),
+    /// Pressed long enough and reported
+    Reported,
+}
+
+/// What a press of a switch reports, see [`SwitchBuilder::mode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SwitchMode {
+    /// The callback receives `true` on press and `false` on release
+    #[default]
+    Momentary,
+    /// Every press flips a latched state, which is passed to the callback; releases are not
+    /// reported, so a long press name has no effect. See [`Encoder::state`].
+    Toggle,
+}
+
+/// Snapshot of a switch's counters, see [`Encoder::metrics`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwitchMetrics {
+    /// Times the switch was pressed
+    pub presses: u64,
+    /// Times the switch was released
+    pub releases: u64,
+    /// Releases reported under the long press name
+    pub long_presses: u64,
+}
 
-#[allow(dead_code)]
+/// A switch on a single pin, reporting presses and releases.
+///
+/// `Encoder` is `Send` and `Sync`, the state shared with the interrupt thread is atomic or
+/// behind a lock. Callbacks run in rppal's interrupt thread.
 pub struct Encoder {
-    name: String,
-    name_lp: Option<String>,
     pin: InputPin,
-    time_threshold: Option<Duration>,
-    last_press: Arc<AtomicOptionDuration>,
-    callback: fn(&str, bool),
+    pin_number: u8,
+    debounce: Option<Duration>,
+    pressed_at_init: bool,
+    shared: Arc<Shared>,
+}
+
+/// Switch state shared between the encoder and its interrupt handler
+pub(crate) struct Shared {
+    name: Arc<str>,
+    name_lp: Option<Arc<str>>,
+    active_low: bool,
+    /// Presses held longer are long presses, `None` disables long press detection
+    time_threshold: AtomicOptionDuration,
+    last_press: AtomicOptionDuration,
+    callback: RwLock<fn(&str, bool)>,
+    release_callbacks: RwLock<Vec<ReleaseCallback>>,
+    listeners: RwLock<Vec<Listener>>,
+    enabled: AtomicBool,
+    /// Skip the release callbacks for releases reported under the long press name
+    suppress_long_release: AtomicBool,
+    gestures: Option<Arc<GestureDetector>>,
+    mode: SwitchMode,
+    latched: AtomicBool,
+    trigger: Trigger,
+    min_press: Option<Duration>,
+    press_gate: Mutex<PressGate>,
+    press_wake: Condvar,
+    /// Time source of the minimum press time
+    clock: Arc<dyn Clock>,
+    log_target: String,
+    presses: AtomicU64,
+    releases: AtomicU64,
+    long_presses: AtomicU64,
+    /// Presses and releases that passed the minimum press time, see [`Encoder::press_count`]
+    accepted_presses: AtomicU64,
+    accepted_releases: AtomicU64,
 }
 
 impl Encoder {
@@ -24,8 +111,12 @@ impl Encoder {
     /// * `encoder_name_long_press` - Name of the encoder for long presses
     /// * `gpio` - Gpio instance to use for the encoder
     /// * `pin_number` - GPIO pin number for the switch signal
-    /// * `time_threshold`- timer to hold a press before considered a long press
+    /// * `time_threshold`- timer to hold a press before considered a long press, `None`
+    ///   disables long press detection like [`Encoder::set_long_press_threshold`]
     /// * `callback` - Function to call when the encoder is switched
+    ///
+    /// The switch pulls the pin low when pressed and is debounced by 50ms. See
+    /// [`Encoder::builder`] for further options, e.g. [`SwitchBuilder::active_low`].
     pub fn new(
         encoder_name: &str,
         encoder_name_long_press: Option<&str>,
@@ -33,106 +124,1321 @@ impl Encoder {
         pin_number: u8,
         time_threshold: Option<Duration>,
         callback: fn(&str, bool),
-    ) -> Result<Self> {
-        trace!("Initializing GPIO for switch encoder {}", encoder_name);
+    ) -> Result<Self, EncoderError> {
+        let mut builder = Self::builder()
+            .name(encoder_name)
+            .pin(pin_number)
+            .callback(callback);
+        if let Some(name) = encoder_name_long_press {
+            builder = builder.long_press_name(name);
+        }
+        if let Some(threshold) = time_threshold {
+            builder = builder.long_press_threshold(threshold);
+        }
+        builder.build(gpio)
+    }
 
-        let pin = gpio.get(pin_number)?.into_input_pullup();
+    /// Start configuring a new switch
+    pub fn builder() -> SwitchBuilder {
+        SwitchBuilder::default()
+    }
 
+    /// Open the GPIO peripheral and create a single switch pulling the pin low when pressed,
+    /// without long press detection and with a 50ms debounce. The claimed pin keeps the
+    /// peripheral open for as long as the encoder lives.
+    ///
+    /// For several encoders open one `Gpio` and pass it to [`Encoder::new`] or
+    /// [`SwitchBuilder::build`] instead.
+    /// # Arguments
+    /// * `encoder_name` - Name of the encoder
+    /// * `pin_number` - GPIO pin number for the switch signal
+    /// * `callback` - Function to call when the encoder is switched
+    pub fn standalone(
+        encoder_name: &str,
+        pin_number: u8,
+        callback: fn(&str, bool),
+    ) -> Result<Self, EncoderError> {
+        Self::builder()
+            .name(encoder_name)
+            .pin(pin_number)
+            .callback(callback)
+            .build(&Gpio::new()?)
+    }
+
+    /// Create a new switch encoder reporting [`Gesture`]s instead of presses and releases
+    /// # Arguments
+    /// * `encoder_name` - Name of the encoder
+    /// * `gpio` - Gpio instance to use for the encoder
+    /// * `pin_number` - GPIO pin number for the switch signal
+    /// * `active_low` - `true` if the switch pulls the pin low when pressed (pull-up is used),
+    ///   `false` if it drives the pin high when pressed (pull-down is used)
+    /// * `timing` - Tap, double tap and long press timing, see [`crate::gesture`]
+    /// * `debounce` - rppal software debounce for the switch signal (e.g. 50ms),
+    ///   `None` disables debouncing entirely for fast tactile switches
+    /// * `callback` - Function to call when a gesture is recognised
+    pub fn with_gestures(
+        encoder_name: &str,
+        gpio: &Gpio,
+        pin_number: u8,
+        active_low: bool,
+        timing: GestureTiming,
+        debounce: Option<Duration>,
+        callback: fn(&str, Gesture),
+    ) -> Result<Self, EncoderError> {
+        let detector = Arc::new(GestureDetector::new(encoder_name, timing, callback));
+        GestureDetector::spawn_timer(&detector);
+        let mut shared = Shared::new(encoder_name, None, active_low, None, |_, _| {});
+        shared.gestures = Some(detector);
+        Self::create(gpio, pin_number, debounce, shared)
+    }
+
+    pub(crate) fn create(
+        gpio: &Gpio,
+        pin_number: u8,
+        debounce: Option<Duration>,
+        shared: Shared,
+    ) -> Result<Self, EncoderError> {
+        trace!(target: &shared.log_target, "Initializing GPIO for switch encoder {}", shared.name);
+        validate_trigger(shared.trigger)?;
+
+        let pin = match shared.active_low {
+            true => gpio.get(pin_number)?.into_input_pullup(),
+            false => gpio.get(pin_number)?.into_input_pulldown(),
+        };
+
+        // an already held switch produces no edge, so its level is only seen by reading it
+        let pressed_at_init = (pin.read() == Level::Low) == shared.active_low;
+        let shared = Arc::new(shared);
+        Shared::spawn_press_timer(&shared);
         let mut encoder = Self {
-            name: encoder_name.to_owned(),
-            name_lp: encoder_name_long_press.map(|s| s.to_owned()),
             pin,
-            time_threshold,
-            last_press: Arc::new(AtomicOptionDuration::new(None)),
-            callback,
+            pin_number,
+            debounce,
+            pressed_at_init,
+            shared,
         };
 
         encoder
             .enable_callback()
-            .map_err(|e| anyhow!("Failed to enable callbacks: {}", e))?;
+            .map_err(EncoderError::InterruptFailed)?;
         trace!(
+            target: &encoder.shared.log_target,
             "Switch encoder {}/{:?} initialized",
-            encoder.name, encoder.name_lp
+            encoder.shared.name, encoder.shared.name_lp
         );
         Ok(encoder)
     }
 
-    fn enable_callback(&mut self) -> Result<()> {
+    /// Name of the switch, an `Arc` like the swappable name of a rotary encoder
+    pub fn name(&self) -> Arc<str> {
+        self.shared.name()
+    }
+
+    /// Name reported for the release of a long press, if configured
+    pub fn long_press_name(&self) -> Option<Arc<str>> {
+        self.shared.long_press_name()
+    }
+
+    /// GPIO pin number of the switch
+    pub fn pin(&self) -> u8 {
+        self.pin_number
+    }
+
+    /// Register a function to call on every release with the (long press) name and the
+    /// time the switch was held, e.g. to tell short taps from medium holds
+    pub fn add_release_callback(&mut self, callback: fn(&str, Duration)) {
+        self.shared.add_release_callback(callback);
+    }
+
+    /// Replace the callback the encoder was created with, keeping all listeners
+    pub(crate) fn replace_callback(&self, callback: fn(&str, bool)) {
+        self.shared.replace_callback(callback);
+    }
+
+    /// Register a closure to call on every press and release, e.g. to forward them
+    pub(crate) fn add_listener(&self, listener: Listener) {
+        self.shared.add_listener(listener);
+    }
+
+    /// Enable or disable the callbacks without releasing the pin.
+    /// Presses are still tracked while disabled, so long press timing stays correct.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.shared.set_enabled(enabled);
+    }
+
+    /// Skip the release callbacks (see [`Encoder::add_release_callback`]) for a release that
+    /// ended a long press, which the callback already reports under the long press name.
+    /// Code that handles both then sees a long press once, not also as a long release.
+    pub fn set_suppress_long_release(&self, suppress: bool) {
+        self.shared.set_suppress_long_release(suppress);
+    }
+
+    /// Change the time a press has to be held to count as a long press, e.g. from an
+    /// accessibility setting, without rebuilding the switch. `None` disables long press
+    /// detection, like at construction, all releases are then reported under the switch
+    /// name. Applies from the next release on, including one of a press already held.
+    pub fn set_long_press_threshold(&self, threshold: Option<Duration>) {
+        self.shared.set_long_press_threshold(threshold);
+    }
+
+    /// Presses accepted since creation, e.g. for usage statistics. Unlike
+    /// [`SwitchMetrics::presses`] this leaves out presses released before the minimum press
+    /// time; presses while disabled are counted as well.
+    pub fn press_count(&self) -> u64 {
+        self.shared.press_count()
+    }
+
+    /// Releases of accepted presses since creation, see [`Encoder::press_count`]
+    pub fn release_count(&self) -> u64 {
+        self.shared.release_count()
+    }
+
+    /// Snapshot of the presses and releases seen so far, e.g. to monitor input health.
+    /// Presses are counted while disabled as well.
+    pub fn metrics(&self) -> SwitchMetrics {
+        self.shared.metrics()
+    }
+
+    /// Whether the switch was already held down when the encoder was created, e.g. to detect
+    /// a factory reset button held during boot. No press is reported for such a switch.
+    ///
+    /// The level is read right after the pull resistor is enabled. Long wires or a debounce
+    /// capacitor may need longer than that to charge, reading as pressed although the switch
+    /// is not; check [`Encoder::is_pressed`] again after a few milliseconds if that matters.
+    pub fn was_pressed_at_init(&self) -> bool {
+        self.pressed_at_init
+    }
+
+    /// Latched state of a switch in [`SwitchMode::Toggle`], starting as `false`.
+    /// Always `false` for a momentary switch.
+    pub fn state(&self) -> bool {
+        self.shared.state()
+    }
+
+    /// Whether the switch is currently held down, read directly from the pin
+    pub fn is_pressed(&self) -> bool {
+        (self.pin.read() == Level::Low) == self.shared.active_low
+    }
+
+    fn enable_callback(&mut self) -> Result<(), rppal::gpio::Error> {
         trace!(
-            "Enabling callbacks for rotary encoder {}/{:?}",
-            self.name, self.name_lp
-        );
-
-        let name = self.name.to_owned();
-        let last_press = Arc::clone(&self.last_press);
-        let time_threshold: Duration = self
-            .time_threshold
-            .unwrap_or_else(|| Duration::from_secs(0));
-        let callback = self.callback;
-
-        match self.name_lp.as_ref() {
-            None => {
-                self.pin.set_async_interrupt(
-                    Trigger::Both,
-                    Some(Duration::from_millis(50)),
-                    move |event: Event| {
-                        trace!("Switch encoder {} event: {:?}", name, event);
-                        callback(
-                            &name,
-                            match event.trigger {
-                                Trigger::RisingEdge => false, // release
-                                Trigger::FallingEdge => true, // press
-                                _ => {
-                                    error!("Unexpected event trigger: {:?}", event.trigger);
-                                    return;
-                                }
-                            },
-                        );
-                    },
-                )?;
+            target: &self.shared.log_target,
+            "Enabling callbacks for switch encoder {}/{:?}",
+            self.shared.name, self.shared.name_lp
+        );
+
+        let shared = Arc::clone(&self.shared);
+        self.pin
+            .set_async_interrupt(self.shared.trigger, self.debounce, move |event: Event| {
+                trace!(
+                    target: &shared.log_target,
+                    "Switch encoder {} event: {:?} (last timestamp {:?})",
+                    shared.name,
+                    event,
+                    shared.last_press.load(Ordering::SeqCst)
+                );
+                shared.handle_edge(event.trigger, event.timestamp);
+            })?;
+
+        Ok(())
+    }
+}
+
+impl Shared {
+    pub(crate) fn new(
+        name: &str,
+        name_long_press: Option<&str>,
+        active_low: bool,
+        time_threshold: Option<Duration>,
+        callback: fn(&str, bool),
+    ) -> Self {
+        Self {
+            name: Arc::from(name),
+            name_lp: name_long_press.map(Arc::from),
+            active_low,
+            time_threshold: AtomicOptionDuration::new(time_threshold),
+            last_press: AtomicOptionDuration::new(None),
+            callback: RwLock::new(callback),
+            release_callbacks: RwLock::new(Vec::new()),
+            listeners: RwLock::new(Vec::new()),
+            enabled: AtomicBool::new(true),
+            suppress_long_release: AtomicBool::new(false),
+            gestures: None,
+            mode: SwitchMode::Momentary,
+            latched: AtomicBool::new(false),
+            trigger: Trigger::Both,
+            min_press: None,
+            press_gate: Mutex::new(PressGate::Idle),
+            press_wake: Condvar::new(),
+            clock: Arc::new(SystemClock),
+            log_target: module_path!().to_owned(),
+            presses: AtomicU64::new(0),
+            releases: AtomicU64::new(0),
+            long_presses: AtomicU64::new(0),
+            accepted_presses: AtomicU64::new(0),
+            accepted_releases: AtomicU64::new(0),
+        }
+    }
+
+    /// Log under `target` instead of the module path
+    pub(crate) fn set_log_target(&mut self, target: &str) {
+        self.log_target = target.to_owned();
+    }
+
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_suppress_long_release(&self, suppress: bool) {
+        self.suppress_long_release
+            .store(suppress, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_long_press_threshold(&self, threshold: Option<Duration>) {
+        self.time_threshold.store(threshold, Ordering::Release);
+    }
+
+    pub(crate) fn add_release_callback(&self, callback: ReleaseCallback) {
+        self.release_callbacks
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(callback);
+    }
+
+    pub(crate) fn replace_callback(&self, callback: fn(&str, bool)) {
+        *self.callback.write().unwrap_or_else(|e| e.into_inner()) = callback;
+    }
+
+    pub(crate) fn set_mode(&mut self, mode: SwitchMode) {
+        self.mode = mode;
+    }
+
+    pub(crate) fn state(&self) -> bool {
+        self.latched.load(Ordering::Acquire)
+    }
+
+    /// Hold presses back for the minimum press time, see [`SwitchBuilder::min_press`]
+    pub(crate) fn set_min_press(&mut self, min_press: Option<Duration>) {
+        self.min_press = min_press;
+    }
+
+    /// Time the minimum press time with `clock`
+    #[cfg(test)]
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Start the thread reporting presses once they lasted the minimum press time.
+    /// It only holds a weak reference and ends soon after the encoder is dropped.
+    pub(crate) fn spawn_press_timer(shared: &Arc<Self>) {
+        if shared.min_press.is_some() {
+            let weak = Arc::downgrade(shared);
+            spawn_named(&shared.name, move || Shared::run_press_timer(weak));
+        }
+    }
+
+    fn run_press_timer(weak: Weak<Shared>) {
+        while let Some(shared) = weak.upgrade() {
+            let (gate, timeout) = shared.expire_press();
+            drop(
+                shared
+                    .press_wake
+                    .wait_timeout(gate, timeout)
+                    .unwrap_or_else(|e| e.into_inner()),
+            );
+        }
+    }
+
+    fn lock_gate(&self) -> MutexGuard<'_, PressGate> {
+        self.press_gate.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Report a pending press that lasted the minimum press time by now, returning the
+    /// locked gate and how long to wait until the next check
+    fn expire_press(&self) -> (MutexGuard<'_, PressGate>, Duration) {
+        let min_press = self.min_press.unwrap_or_default();
+        let mut gate = self.lock_gate();
+        let PressGate::Pending(since) = *gate else {
+            return (gate, PRESS_TIMER_IDLE);
+        };
+        let waited = self.clock.now().saturating_duration_since(since);
+        if waited < min_press {
+            return (gate, min_press - waited);
+        }
+        *gate = PressGate::Reported;
+        self.accepted_presses.fetch_add(1, Ordering::Relaxed);
+        // reported under the lock, so a release waits until the press was reported
+        if self.enabled.load(Ordering::Relaxed) {
+            self.report_press();
+        }
+        (gate, PRESS_TIMER_IDLE)
+    }
+
+    pub(crate) fn add_listener(&self, listener: Listener) {
+        self.listeners
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(listener);
+    }
+
+    /// Report a press, flipping the latched state in toggle mode
+    fn report_press(&self) {
+        match self.mode {
+            SwitchMode::Momentary => self.notify(&self.name, true),
+            SwitchMode::Toggle => {
+                let state = !self.latched.fetch_xor(true, Ordering::AcqRel);
+                trace!(target: &self.log_target, "Switch {} toggled to {}", self.name, state);
+                self.notify(&self.name, state);
             }
-            Some(name_lp) => {
-                let name_lp = name_lp.to_owned();
-                self.pin.set_async_interrupt(
-                    Trigger::Both,
-                    Some(Duration::from_millis(50)),
-                    move |event: Event| {
-                        let previous_timestamp = last_press.load(Ordering::SeqCst);
-                        trace!(
-                            "Switch encoder {} event: {:?} (last timestamp {:?})",
-                            name, event, previous_timestamp
-                        );
-
-                        match event.trigger {
-                            // false: release
-                            Trigger::RisingEdge => {
-                                if let Some(prev_ts) = previous_timestamp
-                                    && event.timestamp - prev_ts > time_threshold
-                                {
-                                    callback(&name_lp, false);
-                                } else {
-                                    callback(&name, false);
-                                }
-                                last_press.store(None, Ordering::SeqCst);
-                            }
-                            // true: press
-                            Trigger::FallingEdge => {
-                                trace!(
-                                    "Storing current time stamp {:?} from seq# {:?}",
-                                    event.timestamp, event.seqno
-                                );
-                                last_press.store(Some(event.timestamp), Ordering::SeqCst);
-                                (callback)(&name, true);
-                            }
-                            _ => {
-                                error!("Unexpected event trigger: {:?}", event.trigger);
-                            }
-                        }
-                    },
-                )?;
+        }
+    }
+
+    /// Invoke the callback and all listeners
+    fn notify(&self, name: &str, pressed: bool) {
+        let callback = *self.callback.read().unwrap_or_else(|e| e.into_inner());
+        catch_panic(&self.log_target, name, || callback(name, pressed));
+        for listener in self
+            .listeners
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            catch_panic(&self.log_target, name, || listener(name, pressed));
+        }
+    }
+
+    pub(crate) fn name(&self) -> Arc<str> {
+        Arc::clone(&self.name)
+    }
+
+    pub(crate) fn long_press_name(&self) -> Option<Arc<str>> {
+        self.name_lp.clone()
+    }
+
+    pub(crate) fn press_count(&self) -> u64 {
+        self.accepted_presses.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn release_count(&self) -> u64 {
+        self.accepted_releases.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn metrics(&self) -> SwitchMetrics {
+        SwitchMetrics {
+            presses: self.presses.load(Ordering::Relaxed),
+            releases: self.releases.load(Ordering::Relaxed),
+            long_presses: self.long_presses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Feed a single edge of the switch pin into the encoder
+    /// # Arguments
+    /// * `trigger` - Edge reported by rppal
+    /// * `timestamp` - Time of the edge, used for long press detection
+    pub(crate) fn handle_edge(&self, trigger: Trigger, timestamp: Duration) {
+        let Some(pressed) = trigger_to_pressed(trigger, self.active_low) else {
+            log_non_edge(&self.log_target, trigger);
+            return;
+        };
+        match pressed {
+            true => self.presses.fetch_add(1, Ordering::Relaxed),
+            false => self.releases.fetch_add(1, Ordering::Relaxed),
+        };
+        if let Some(gestures) = &self.gestures {
+            match pressed {
+                true => self.accepted_presses.fetch_add(1, Ordering::Relaxed),
+                false => self.accepted_releases.fetch_add(1, Ordering::Relaxed),
+            };
+            if self.enabled.load(Ordering::Relaxed) {
+                gestures.handle(pressed, timestamp);
             }
+            return;
+        }
+        if pressed {
+            trace!(target: &self.log_target, "Storing current time stamp {:?}", timestamp);
+            self.last_press.store(Some(timestamp), Ordering::SeqCst);
+            if self.min_press.is_some() {
+                *self.lock_gate() = PressGate::Pending(self.clock.now());
+                self.press_wake.notify_one();
+                return;
+            }
+            self.accepted_presses.fetch_add(1, Ordering::Relaxed);
+            if self.enabled.load(Ordering::Relaxed) {
+                self.report_press();
+            }
+            return;
         }
 
-        Ok(())
+        if self.min_press.is_some() {
+            let mut gate = self.lock_gate();
+            let suppressed = matches!(*gate, PressGate::Pending(_));
+            *gate = PressGate::Idle;
+            if suppressed {
+                trace!(
+                    target: &self.log_target,
+                    "Switch {} released before the minimum press time, ignoring the press",
+                    self.name
+                );
+                self.last_press.store(None, Ordering::SeqCst);
+                return;
+            }
+        }
+        self.accepted_releases.fetch_add(1, Ordering::Relaxed);
+
+        // a release without a recorded press (e.g. held during startup) has no duration
+        let held = self
+            .last_press
+            .swap(None, Ordering::SeqCst)
+            .map(|prev_ts| timestamp.saturating_sub(prev_ts));
+        let threshold = self.time_threshold.load(Ordering::Acquire);
+        let (name, long_press) = match (self.name_lp.as_ref(), held, threshold) {
+            (Some(name_lp), Some(held), Some(threshold)) if held > threshold => {
+                self.long_presses.fetch_add(1, Ordering::Relaxed);
+                (name_lp, true)
+            }
+            _ => (&self.name, false),
+        };
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.mode == SwitchMode::Momentary {
+            self.notify(name, false);
+        }
+        if long_press && self.suppress_long_release.load(Ordering::Relaxed) {
+            trace!(
+                target: &self.log_target,
+                "Switch {} long press reported, skipping the release callbacks",
+                self.name
+            );
+            return;
+        }
+        if let Some(held) = held {
+            for release_callback in self
+                .release_callbacks
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+            {
+                catch_panic(&self.log_target, name, || release_callback(name, held));
+            }
+        }
+    }
+}
+
+/// Builder for [`Encoder`], avoiding long positional argument lists
+pub struct SwitchBuilder {
+    name: Option<String>,
+    name_long_press: Option<String>,
+    pin: Option<u8>,
+    time_threshold: Option<Duration>,
+    active_low: bool,
+    debounce: Option<Duration>,
+    min_press: Option<Duration>,
+    mode: SwitchMode,
+    trigger: Trigger,
+    callback: Option<Callback>,
+    log_target: Option<String>,
+}
+
+impl Default for SwitchBuilder {
+    fn default() -> Self {
+        Self {
+            name: None,
+            name_long_press: None,
+            pin: None,
+            time_threshold: None,
+            active_low: true,
+            debounce: Some(Duration::from_millis(50)),
+            min_press: None,
+            mode: SwitchMode::Momentary,
+            trigger: Trigger::Both,
+            callback: None,
+            log_target: None,
+        }
+    }
+}
+
+impl SwitchBuilder {
+    /// Name of the switch (required)
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Name reported on the release of a long press, see
+    /// [`SwitchBuilder::long_press_threshold`]
+    pub fn long_press_name(mut self, name: &str) -> Self {
+        self.name_long_press = Some(name.to_owned());
+        self
+    }
+
+    /// GPIO pin number of the switch signal (required)
+    pub fn pin(mut self, pin: u8) -> Self {
+        self.pin = Some(pin);
+        self
+    }
+
+    /// Time to hold a press before it is released under the long press name. Without it
+    /// long press detection is disabled, see [`Encoder::set_long_press_threshold`].
+    pub fn long_press_threshold(mut self, threshold: Duration) -> Self {
+        self.time_threshold = Some(threshold);
+        self
+    }
+
+    /// `true` (the default) if the switch pulls the pin low when pressed (pull-up is used),
+    /// `false` if it drives the pin high when pressed (pull-down is used)
+    pub fn active_low(mut self, active_low: bool) -> Self {
+        self.active_low = active_low;
+        self
+    }
+
+    /// rppal software debounce for the switch signal, 50ms by default. `None` disables
+    /// debouncing entirely for fast tactile switches.
+    pub fn debounce(mut self, debounce: Option<Duration>) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Minimum time a press must last to be reported at all (e.g. 20ms), filtering phantom
+    /// presses of noisy buttons. Neither a shorter press nor its release is reported, a
+    /// longer one is reported once it lasted `min_press`.
+    pub fn min_press(mut self, min_press: Duration) -> Self {
+        self.min_press = Some(min_press);
+        self
+    }
+
+    /// Report presses and releases (the default) or toggle a latched state, see [`SwitchMode`]
+    pub fn mode(mut self, mode: SwitchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Edges to register the interrupt for, `Trigger::Both` by default. A single edge halves
+    /// the interrupts of a press-only button: with `FallingEdge` an active low switch reports
+    /// its presses only, never a release. `Trigger::Disabled` is rejected.
+    pub fn trigger(mut self, trigger: Trigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    /// Function to call when the switch is pressed or released (required)
+    pub fn callback(mut self, callback: fn(&str, bool)) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    /// Log under `target` instead of the module path, e.g. `myapp::mute_button`
+    pub fn log_target(mut self, target: &str) -> Self {
+        self.log_target = Some(target.to_owned());
+        self
+    }
+
+    /// Name, pin and callback, which have no defaults
+    fn required(&self) -> Result<(String, u8, Callback), EncoderError> {
+        let missing = |field| EncoderError::MissingField {
+            builder: "switch",
+            field,
+        };
+        Ok((
+            self.name.clone().ok_or_else(|| missing("name"))?,
+            self.pin.ok_or_else(|| missing("pin"))?,
+            self.callback.ok_or_else(|| missing("callback"))?,
+        ))
+    }
+
+    /// Claim the GPIO pin and enable the interrupt
+    pub fn build(self, gpio: &Gpio) -> Result<Encoder, EncoderError> {
+        let (name, pin, callback) = self.required()?;
+        let mut shared = Shared::new(
+            &name,
+            self.name_long_press.as_deref(),
+            self.active_low,
+            self.time_threshold,
+            callback,
+        );
+        if let Some(log_target) = &self.log_target {
+            shared.set_log_target(log_target);
+        }
+        shared.set_min_press(self.min_press);
+        shared.set_mode(self.mode);
+        shared.trigger = self.trigger;
+        Encoder::create(gpio, pin, self.debounce, shared)
+    }
+}
+
+/// Bank of switches read as one bitmask, e.g. a DIP switch selecting a configuration.
+/// Switch `i` sets bit `i` of the mask while it is closed, i.e. pulls its pin low.
+pub struct SwitchBank {
+    pins: SharedPins<Vec<InputPin>>,
+    shared: Arc<BankShared>,
+}
+
+/// Bank state shared between the bank and its interrupt handlers
+pub(crate) struct BankShared {
+    name: String,
+    mask: AtomicU32,
+    callback: fn(&str, u32),
+    log_target: String,
+}
+
+impl SwitchBank {
+    /// Create a new switch bank
+    /// # Arguments
+    /// * `name` - Name of the bank
+    /// * `gpio` - Gpio instance to use for the bank
+    /// * `pins` - GPIO pin numbers, least significant bit first (at most 32)
+    /// * `callback` - Function to call with the new mask whenever a switch changes
+    pub fn new(
+        name: &str,
+        gpio: &Gpio,
+        pins: &[u8],
+        callback: fn(&str, u32),
+    ) -> Result<Self, EncoderError> {
+        Self::new_with_log_target(name, gpio, pins, callback, module_path!())
+    }
+
+    /// Create a new switch bank logging under `log_target`, e.g. `myapp::dip_switches` to
+    /// filter its messages with `RUST_LOG=myapp::dip_switches=trace`
+    /// # Arguments
+    /// * `name` - Name of the bank
+    /// * `gpio` - Gpio instance to use for the bank
+    /// * `pins` - GPIO pin numbers, least significant bit first (at most 32)
+    /// * `callback` - Function to call with the new mask whenever a switch changes
+    /// * `log_target` - Target of the bank's log messages
+    pub fn new_with_log_target(
+        name: &str,
+        gpio: &Gpio,
+        pins: &[u8],
+        callback: fn(&str, u32),
+        log_target: &str,
+    ) -> Result<Self, EncoderError> {
+        trace!(target: log_target, "Initializing GPIO for switch bank {}", name);
+        validate_pins(name, pins)?;
+        if pins.len() > 32 {
+            return Err(EncoderError::TooManyPins {
+                name: name.to_owned(),
+                count: pins.len(),
+                max: 32,
+            });
+        }
+
+        let pins = pins
+            .iter()
+            .map(|&p| Ok(gpio.get(p)?.into_input_pullup()))
+            .collect::<Result<Vec<InputPin>, EncoderError>>()?;
+
+        // start from the current mask without reporting it as a change
+        let mut shared = BankShared::new(name, read_code(&pins), callback);
+        shared.log_target = log_target.to_owned();
+        let bank = Self {
+            pins: SharedPins::new(pins),
+            shared: Arc::new(shared),
+        };
+        bank.enable_callbacks()
+            .map_err(EncoderError::InterruptFailed)?;
+        trace!(target: log_target, "Switch bank {} initialized", name);
+        Ok(bank)
+    }
+
+    /// Name of the bank
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    /// Current mask, read directly from the pins
+    pub fn read(&self) -> u32 {
+        self.pins.with(|pins| read_code(pins))
+    }
+
+    fn enable_callbacks(&self) -> Result<(), rppal::gpio::Error> {
+        trace!(
+            target: &self.shared.log_target,
+            "Enabling callbacks for switch bank {}", self.shared.name
+        );
+        self.pins.with(|pins| {
+            for pin in pins.iter_mut() {
+                let all_pins = self.pins.handle();
+                let shared = Arc::clone(&self.shared);
+                pin.set_async_interrupt(Trigger::Both, None, move |_: Event| {
+                    // flipping one switch may bounce others, so read the whole bank
+                    if let Some(mask) = all_pins.with(|pins| read_code(pins)) {
+                        shared.update(mask);
+                    }
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl BankShared {
+    pub(crate) fn new(name: &str, mask: u32, callback: fn(&str, u32)) -> Self {
+        Self {
+            name: name.to_owned(),
+            mask: AtomicU32::new(mask),
+            callback,
+            log_target: module_path!().to_owned(),
+        }
+    }
+
+    /// Record the mask read from the pins, reporting only actual changes
+    pub(crate) fn update(&self, mask: u32) {
+        if self.mask.swap(mask, Ordering::AcqRel) != mask {
+            trace!(target: &self.log_target, "Switch bank {} changed to {:#b}", self.name, mask);
+            catch_panic(&self.log_target, &self.name, || {
+                (self.callback)(&self.name, mask)
+            });
+        }
+    }
+}
+
+/// Combination of switches that all have to be pressed within a time window, e.g. two
+/// buttons pressed together to open a hidden menu. Releasing any of them starts over.
+pub struct Chord {
+    // kept to hold the interrupt handlers
+    _pins: Vec<InputPin>,
+    shared: Arc<ChordShared>,
+}
+
+/// Chord state shared between the chord and its interrupt handlers
+pub(crate) struct ChordShared {
+    name: String,
+    window: Duration,
+    callback: fn(&str),
+    log_target: String,
+    /// Time of the pending press of every switch, `None` while not pressed
+    presses: Mutex<Vec<Option<Duration>>>,
+}
+
+impl Chord {
+    /// Create a new chord of switches pulling their pins low when pressed
+    /// # Arguments
+    /// * `name` - Name of the chord
+    /// * `gpio` - Gpio instance to use for the chord
+    /// * `pins` - GPIO pin numbers of the switches
+    /// * `window` - Time between the first and the last press for the chord to fire
+    /// * `callback` - Function to call when the chord was pressed
+    pub fn new(
+        name: &str,
+        gpio: &Gpio,
+        pins: &[u8],
+        window: Duration,
+        callback: fn(&str),
+    ) -> Result<Self, EncoderError> {
+        Self::new_with_log_target(name, gpio, pins, window, callback, module_path!())
+    }
+
+    /// Create a new chord logging under `log_target`, like [`SwitchBank::new_with_log_target`]
+    /// # Arguments
+    /// * `name` - Name of the chord
+    /// * `gpio` - Gpio instance to use for the chord
+    /// * `pins` - GPIO pin numbers of the switches
+    /// * `window` - Time between the first and the last press for the chord to fire
+    /// * `callback` - Function to call when the chord was pressed
+    /// * `log_target` - Target of the chord's log messages
+    pub fn new_with_log_target(
+        name: &str,
+        gpio: &Gpio,
+        pins: &[u8],
+        window: Duration,
+        callback: fn(&str),
+        log_target: &str,
+    ) -> Result<Self, EncoderError> {
+        trace!(target: log_target, "Initializing GPIO for chord {}", name);
+        validate_pins(name, pins)?;
+
+        let mut shared = ChordShared::new(name, pins.len(), window, callback);
+        shared.log_target = log_target.to_owned();
+        let shared = Arc::new(shared);
+        let mut claimed = Vec::with_capacity(pins.len());
+        for (index, &pin_number) in pins.iter().enumerate() {
+            let mut pin = gpio.get(pin_number)?.into_input_pullup();
+            let handler_shared = Arc::clone(&shared);
+            pin.set_async_interrupt(
+                Trigger::Both,
+                Some(Duration::from_millis(50)),
+                move |event: Event| {
+                    handler_shared.handle_edge(index, event.trigger, event.timestamp)
+                },
+            )
+            .map_err(EncoderError::InterruptFailed)?;
+            claimed.push(pin);
+        }
+        trace!(target: log_target, "Chord {} initialized", name);
+        Ok(Self {
+            _pins: claimed,
+            shared,
+        })
+    }
+
+    /// Name of the chord
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+}
+
+impl ChordShared {
+    pub(crate) fn new(name: &str, switches: usize, window: Duration, callback: fn(&str)) -> Self {
+        Self {
+            name: name.to_owned(),
+            window,
+            callback,
+            log_target: module_path!().to_owned(),
+            presses: Mutex::new(vec![None; switches]),
+        }
+    }
+
+    /// Handle an edge of switch `index`, firing once all switches were pressed within the window
+    pub(crate) fn handle_edge(&self, index: usize, trigger: Trigger, timestamp: Duration) {
+        let Some(pressed) = trigger_to_pressed(trigger, true) else {
+            log_non_edge(&self.log_target, trigger);
+            return;
+        };
+        let mut presses = self.presses.lock().unwrap_or_else(|e| e.into_inner());
+        if !pressed {
+            trace!(
+                target: &self.log_target,
+                "Chord {} reset by release of switch {}", self.name, index
+            );
+            presses.fill(None);
+            return;
+        }
+        presses[index] = Some(timestamp);
+        let Some(pressed) = presses.iter().copied().collect::<Option<Vec<Duration>>>() else {
+            return;
+        };
+        let first = pressed.iter().min().copied().unwrap_or_default();
+        let last = pressed.iter().max().copied().unwrap_or_default();
+        if last - first <= self.window {
+            // every switch has to be pressed anew for the chord to fire again
+            presses.fill(None);
+            drop(presses);
+            trace!(target: &self.log_target, "Chord {} pressed", self.name);
+            catch_panic(&self.log_target, &self.name, || (self.callback)(&self.name));
+        }
+    }
+}
+
+/// Reject triggers for which rppal would never call the interrupt handler
+fn validate_trigger(trigger: Trigger) -> Result<(), EncoderError> {
+    match trigger {
+        Trigger::Disabled => Err(EncoderError::InvalidTrigger(trigger)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_builder_requires_fields() {
+        fn cb(_: &str, _: bool) {}
+        let error = |builder: SwitchBuilder| builder.required().unwrap_err().to_string();
+
+        assert_eq!(
+            error(Encoder::builder().pin(22).callback(cb)),
+            "switch builder: missing required field `name`"
+        );
+        assert_eq!(
+            error(Encoder::builder().name("button").callback(cb)),
+            "switch builder: missing required field `pin`"
+        );
+        assert_eq!(
+            error(Encoder::builder().name("button").pin(22)),
+            "switch builder: missing required field `callback`"
+        );
+        assert!(
+            Encoder::builder()
+                .name("button")
+                .pin(22)
+                .callback(cb)
+                .required()
+                .is_ok()
+        );
+    }
+
+    static ACTIVE_LOW_LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+    static ACTIVE_HIGH_LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+    static LONG_PRESS_LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+    fn active_low_callback(name: &str, pressed: bool) {
+        ACTIVE_LOW_LOG
+            .lock()
+            .unwrap()
+            .push((name.to_owned(), pressed));
+    }
+
+    fn active_high_callback(name: &str, pressed: bool) {
+        ACTIVE_HIGH_LOG
+            .lock()
+            .unwrap()
+            .push((name.to_owned(), pressed));
+    }
+
+    static DISABLED_LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+    static HELD_LOG: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+    fn long_press_callback(name: &str, pressed: bool) {
+        LONG_PRESS_LOG
+            .lock()
+            .unwrap()
+            .push((name.to_owned(), pressed));
+    }
+
+    #[test]
+    fn test_handle_edge_active_low() {
+        let shared = Shared::new("button", None, true, None, active_low_callback);
+        shared.handle_edge(Trigger::FallingEdge, Duration::ZERO);
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_millis(100));
+        assert_eq!(
+            *ACTIVE_LOW_LOG.lock().unwrap(),
+            vec![("button".to_owned(), true), ("button".to_owned(), false)]
+        );
+    }
+
+    #[test]
+    fn test_panicking_callback_keeps_reporting() {
+        static LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+        let shared = Shared::new("button", None, true, None, |_, pressed| {
+            if pressed {
+                panic!("presses are not supported");
+            }
+        });
+        shared.add_listener(Arc::new(|name, pressed| {
+            LOG.lock().unwrap().push((name.to_owned(), pressed))
+        }));
+        shared.add_release_callback(|_, _| panic!("releases are not supported either"));
+        for _ in 0..2 {
+            shared.handle_edge(Trigger::FallingEdge, Duration::ZERO);
+            shared.handle_edge(Trigger::RisingEdge, Duration::from_millis(100));
+        }
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec![
+                ("button".to_owned(), true),
+                ("button".to_owned(), false),
+                ("button".to_owned(), true),
+                ("button".to_owned(), false)
+            ]
+        );
+        assert_eq!(shared.metrics().releases, 2);
+    }
+
+    #[test]
+    fn test_validate_trigger() {
+        assert!(validate_trigger(Trigger::Both).is_ok());
+        assert!(validate_trigger(Trigger::FallingEdge).is_ok());
+        assert!(validate_trigger(Trigger::RisingEdge).is_ok());
+        let err = validate_trigger(Trigger::Disabled).unwrap_err();
+        assert!(matches!(
+            err,
+            EncoderError::InvalidTrigger(Trigger::Disabled)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Switch trigger Disabled does not report any edge"
+        );
+    }
+
+    #[test]
+    fn test_bank_reports_changed_masks() {
+        static LOG: Mutex<Vec<(String, u32)>> = Mutex::new(Vec::new());
+        let bank = BankShared::new("dip", 0b0101, |name, mask| {
+            LOG.lock().unwrap().push((name.to_owned(), mask))
+        });
+        // the initial mask and repeated reads of the same one are not reported
+        bank.update(0b0101);
+        bank.update(0b0111);
+        bank.update(0b0111);
+        bank.update(0b0011);
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec![("dip".to_owned(), 0b0111), ("dip".to_owned(), 0b0011)]
+        );
+    }
+
+    fn chord_log() -> &'static Mutex<Vec<String>> {
+        static LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        &LOG
+    }
+
+    #[test]
+    fn test_chord_within_window() {
+        let chord = ChordShared::new("menu", 2, Duration::from_millis(100), |name| {
+            chord_log().lock().unwrap().push(name.to_owned())
+        });
+        let ms = Duration::from_millis;
+        // too far apart
+        chord.handle_edge(0, Trigger::FallingEdge, ms(0));
+        chord.handle_edge(1, Trigger::FallingEdge, ms(150));
+        assert!(chord_log().lock().unwrap().is_empty());
+        // pressing the first switch again brings both within the window
+        chord.handle_edge(0, Trigger::FallingEdge, ms(200));
+        assert_eq!(*chord_log().lock().unwrap(), vec!["menu"]);
+        chord.handle_edge(0, Trigger::RisingEdge, ms(250));
+        chord.handle_edge(1, Trigger::RisingEdge, ms(260));
+        // a release resets the pending press of the other switch
+        chord.handle_edge(0, Trigger::FallingEdge, ms(300));
+        chord.handle_edge(0, Trigger::RisingEdge, ms(320));
+        chord.handle_edge(1, Trigger::FallingEdge, ms(330));
+        assert_eq!(chord_log().lock().unwrap().len(), 1);
+        chord.handle_edge(0, Trigger::FallingEdge, ms(340));
+        assert_eq!(chord_log().lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_handle_edge_toggle() {
+        static LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+        let mut shared = Shared::new("light", None, true, None, |name, state| {
+            LOG.lock().unwrap().push((name.to_owned(), state))
+        });
+        shared.set_mode(SwitchMode::Toggle);
+        for i in 0..3 {
+            shared.handle_edge(Trigger::FallingEdge, Duration::from_secs(i));
+            shared.handle_edge(
+                Trigger::RisingEdge,
+                Duration::from_secs(i) + Duration::from_millis(100),
+            );
+        }
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec![
+                ("light".to_owned(), true),
+                ("light".to_owned(), false),
+                ("light".to_owned(), true)
+            ]
+        );
+        assert!(shared.state());
+        assert_eq!(shared.metrics().releases, 3);
+    }
+
+    #[test]
+    fn test_handle_edge_min_press() {
+        static LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+        let mut shared = Shared::new("button", None, true, None, |name, pressed| {
+            LOG.lock().unwrap().push((name.to_owned(), pressed))
+        });
+        let clock = Arc::new(MockClock::new());
+        shared.set_min_press(Some(Duration::from_millis(20)));
+        shared.set_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+
+        // a phantom press released before the minimum press time is never reported
+        shared.handle_edge(Trigger::FallingEdge, Duration::ZERO);
+        clock.advance(Duration::from_millis(5));
+        let (gate, timeout) = shared.expire_press();
+        drop(gate);
+        assert_eq!(timeout, Duration::from_millis(15));
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_millis(5));
+        assert!(LOG.lock().unwrap().is_empty());
+
+        // a real press is reported once it lasted the minimum press time
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_secs(1));
+        clock.advance(Duration::from_millis(19));
+        drop(shared.expire_press());
+        assert!(LOG.lock().unwrap().is_empty());
+        clock.advance(Duration::from_millis(1));
+        drop(shared.expire_press());
+        assert_eq!(*LOG.lock().unwrap(), vec![("button".to_owned(), true)]);
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_secs(2));
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec![("button".to_owned(), true), ("button".to_owned(), false)]
+        );
+        // the phantom press is seen, but not counted as accepted
+        assert_eq!(shared.metrics().presses, 2);
+        assert_eq!((shared.press_count(), shared.release_count()), (1, 1));
+    }
+
+    #[test]
+    fn test_handle_edge_active_high() {
+        let shared = Shared::new("button", None, false, None, active_high_callback);
+        shared.handle_edge(Trigger::RisingEdge, Duration::ZERO);
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_millis(100));
+        assert_eq!(
+            *ACTIVE_HIGH_LOG.lock().unwrap(),
+            vec![("button".to_owned(), true), ("button".to_owned(), false)]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_long_press() {
+        let shared = Shared::new(
+            "button",
+            Some("button_long"),
+            true,
+            Some(Duration::from_secs(1)),
+            long_press_callback,
+        );
+        shared.handle_edge(Trigger::FallingEdge, Duration::ZERO);
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_millis(500));
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_secs(2));
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_secs(4));
+        assert_eq!(
+            *LONG_PRESS_LOG.lock().unwrap(),
+            vec![
+                ("button".to_owned(), true),
+                ("button".to_owned(), false),
+                ("button".to_owned(), true),
+                ("button_long".to_owned(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_metrics() {
+        let shared = Shared::new(
+            "button",
+            Some("button_long"),
+            true,
+            Some(Duration::from_secs(1)),
+            |_, _| {},
+        );
+        shared.handle_edge(Trigger::FallingEdge, Duration::ZERO);
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_millis(500));
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_secs(2));
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_secs(4));
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_secs(5));
+        assert_eq!(
+            shared.metrics(),
+            SwitchMetrics {
+                presses: 3,
+                releases: 2,
+                long_presses: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_reports_held_duration() {
+        let shared = Shared::new(
+            "button",
+            Some("button_long"),
+            true,
+            Some(Duration::from_secs(1)),
+            |_, _| {},
+        );
+        shared.add_release_callback(|name, held| {
+            HELD_LOG.lock().unwrap().push((name.to_owned(), held))
+        });
+
+        // release without a recorded press
+        shared.handle_edge(Trigger::RisingEdge, Duration::ZERO);
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_millis(100));
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_millis(350));
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_secs(2));
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_secs(4));
+        assert_eq!(
+            *HELD_LOG.lock().unwrap(),
+            vec![
+                ("button".to_owned(), Duration::from_millis(250)),
+                ("button_long".to_owned(), Duration::from_secs(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_suppresses_long_release() {
+        static LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let shared = Shared::new(
+            "button",
+            Some("button_long"),
+            true,
+            Some(Duration::from_secs(1)),
+            |name, pressed| LOG.lock().unwrap().push(format!("{} {}", name, pressed)),
+        );
+        shared
+            .add_release_callback(|name, _| LOG.lock().unwrap().push(format!("{} released", name)));
+        shared.set_suppress_long_release(true);
+
+        // press, hold past the threshold, long press reported on release, no release callback
+        shared.handle_edge(Trigger::FallingEdge, Duration::ZERO);
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_secs(2));
+        // a short press afterwards is released normally
+        shared.handle_edge(Trigger::FallingEdge, Duration::from_secs(3));
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_millis(3200));
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec![
+                "button true",
+                "button_long false",
+                "button true",
+                "button false",
+                "button released"
+            ]
+        );
+        assert_eq!(shared.metrics().long_presses, 1);
+    }
+
+    #[test]
+    fn test_set_long_press_threshold() {
+        static LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let ms = Duration::from_millis;
+        let switch = |time_threshold| {
+            Shared::new(
+                "button",
+                Some("button_long"),
+                true,
+                time_threshold,
+                |name, pressed| {
+                    if !pressed {
+                        LOG.lock().unwrap().push(name.to_owned())
+                    }
+                },
+            )
+        };
+        let hold = |shared: &Shared, from: u64, to: u64| {
+            shared.handle_edge(Trigger::FallingEdge, ms(from));
+            shared.handle_edge(Trigger::RisingEdge, ms(to));
+        };
+
+        // 600ms is short with the initial threshold, long with a shorter one
+        let shared = switch(Some(Duration::from_secs(1)));
+        hold(&shared, 0, 600);
+        shared.set_long_press_threshold(Some(ms(500)));
+        hold(&shared, 1000, 1600);
+        // disabled, even a very long press is a normal one
+        shared.set_long_press_threshold(None);
+        hold(&shared, 2000, 9000);
+        assert_eq!(shared.metrics().long_presses, 1);
+
+        // created without a threshold, long press detection is disabled until one is set
+        let shared = switch(None);
+        hold(&shared, 0, 9000);
+        shared.set_long_press_threshold(Some(ms(500)));
+        hold(&shared, 10000, 10600);
+        assert_eq!(shared.metrics().long_presses, 1);
+
+        assert_eq!(
+            *LOG.lock().unwrap(),
+            vec!["button", "button_long", "button", "button", "button_long"]
+        );
+    }
+
+    #[test]
+    fn test_handle_edge_disabled() {
+        let shared = Shared::new(
+            "button",
+            Some("button_long"),
+            true,
+            Some(Duration::from_secs(1)),
+            |name, pressed| {
+                DISABLED_LOG
+                    .lock()
+                    .unwrap()
+                    .push((name.to_owned(), pressed))
+            },
+        );
+        shared.set_enabled(false);
+        shared.handle_edge(Trigger::FallingEdge, Duration::ZERO);
+        shared.set_enabled(true);
+        // the press while disabled still counts towards the long press
+        shared.handle_edge(Trigger::RisingEdge, Duration::from_secs(2));
+        assert_eq!(
+            *DISABLED_LOG.lock().unwrap(),
+            vec![("button_long".to_owned(), false)]
+        );
     }
 }
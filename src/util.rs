@@ -0,0 +1,190 @@
+//! Mapping of rppal edges to the levels and presses the encoders work with, the internal
+//! threads of the encoders and pins shared with their own interrupt handlers.
+
+use log::{debug, error};
+use rppal::gpio::Trigger;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread::{self, JoinHandle};
+
+/// Level a pin has after `trigger`, `1` meaning pulled low as in the quadrature states,
+/// `None` for triggers that are no edge
+pub(crate) fn trigger_to_level(trigger: Trigger) -> Option<u8> {
+    match trigger {
+        Trigger::RisingEdge => Some(0),
+        Trigger::FallingEdge => Some(1),
+        _ => None,
+    }
+}
+
+/// Log a trigger that is no edge. rppal may deliver [`Trigger::Disabled`] when an interrupt
+/// is reconfigured, which is harmless and ignored; anything else points at a bug.
+pub(crate) fn log_non_edge(target: &str, trigger: Trigger) {
+    match trigger {
+        Trigger::Disabled => debug!(target: target, "Ignoring disabled trigger"),
+        _ => error!(target: target, "Unexpected event trigger: {:?}", trigger),
+    }
+}
+
+/// Whether a switch is pressed after `trigger`, `None` for triggers that are no edge
+/// # Arguments
+/// * `trigger` - Edge reported by rppal
+/// * `active_low` - `true` if the switch pulls the pin low when pressed
+pub(crate) fn trigger_to_pressed(trigger: Trigger, active_low: bool) -> Option<bool> {
+    trigger_to_level(trigger).map(|level| (level == 1) == active_low)
+}
+
+/// Call a user callback of the input `name`, catching a panic instead of letting it unwind
+/// into rppal's interrupt thread, which would end that thread and with it all further events
+/// of the pin. The panic is logged under `target` and its message returned.
+pub(crate) fn catch_panic(target: &str, name: &str, callback: impl FnOnce()) -> Option<String> {
+    let payload = panic::catch_unwind(AssertUnwindSafe(callback)).err()?;
+    let message = panic_message(payload.as_ref()).to_owned();
+    error!(target: target, "Callback of {} panicked: {}", name, message);
+    Some(message)
+}
+
+/// Message of a caught panic, which is a `&str` or `String` unless the payload was
+/// passed to [`std::panic::panic_any`]
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap_or("unknown payload")
+}
+
+/// Start an internal thread of the input `name`, named `rse-{name}` so the threads of
+/// many inputs can be told apart in `top -H`, `perf` or a debugger
+pub(crate) fn spawn_named<F, T>(name: &str, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    thread::Builder::new()
+        .name(format!("rse-{}", name))
+        .spawn(f)
+        .expect("failed to spawn thread")
+}
+
+/// Pins whose interrupt handlers read all of them, e.g. to decode a multi-bit code.
+///
+/// The handlers only hold a weak reference, the pins own the handlers. A handler that
+/// upgraded it while the owner is dropped would otherwise drop the pins on rppal's interrupt
+/// thread, where rppal joins that very thread. Dropping `SharedPins` therefore takes the pins
+/// out and drops them on the owner's thread; handlers running afterwards find them gone.
+pub(crate) struct SharedPins<T> {
+    pins: Arc<Mutex<Option<T>>>,
+}
+
+/// Weak reference of an interrupt handler to its [`SharedPins`]
+pub(crate) struct PinsHandle<T> {
+    pins: Weak<Mutex<Option<T>>>,
+}
+
+impl<T> SharedPins<T> {
+    pub(crate) fn new(pins: T) -> Self {
+        Self {
+            pins: Arc::new(Mutex::new(Some(pins))),
+        }
+    }
+
+    /// Run `f` with the pins locked
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(lock(&self.pins)
+            .as_mut()
+            .expect("pins are only taken on drop"))
+    }
+
+    /// Reference for an interrupt handler of the pins
+    pub(crate) fn handle(&self) -> PinsHandle<T> {
+        PinsHandle {
+            pins: Arc::downgrade(&self.pins),
+        }
+    }
+}
+
+impl<T> Drop for SharedPins<T> {
+    fn drop(&mut self) {
+        // release the lock before the pins join their interrupt threads
+        let pins = lock(&self.pins).take();
+        drop(pins);
+    }
+}
+
+impl<T> PinsHandle<T> {
+    /// Run `f` with the pins locked, `None` once they were dropped
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let pins = self.pins.upgrade()?;
+        let mut pins = lock(&pins);
+        pins.as_mut().map(f)
+    }
+}
+
+fn lock<T>(pins: &Mutex<T>) -> MutexGuard<'_, T> {
+    pins.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_pins_dropped_on_owner_thread() {
+        use std::sync::mpsc;
+        use std::thread::ThreadId;
+        use std::time::Duration;
+
+        static DROPPED_ON: Mutex<Option<ThreadId>> = Mutex::new(None);
+        struct Pins;
+        impl Drop for Pins {
+            fn drop(&mut self) {
+                *DROPPED_ON.lock().unwrap() = Some(thread::current().id());
+            }
+        }
+
+        let pins = SharedPins::new(Pins);
+        let handle = pins.handle();
+        let (started, running) = mpsc::channel();
+        let (dropped, owner_gone) = mpsc::channel();
+        // a handler still running when the owner is dropped
+        let handler = thread::spawn(move || {
+            handle.with(|_| {
+                started.send(()).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            });
+            owner_gone.recv().unwrap();
+            handle.with(|_| ())
+        });
+        running.recv().unwrap();
+        drop(pins);
+        dropped.send(()).unwrap();
+
+        assert_eq!(*DROPPED_ON.lock().unwrap(), Some(thread::current().id()));
+        assert_eq!(handler.join().unwrap(), None);
+    }
+
+    #[test]
+    fn test_spawn_named() {
+        let name = spawn_named("volume", || thread::current().name().map(str::to_owned));
+        assert_eq!(name.join().unwrap().as_deref(), Some("rse-volume"));
+    }
+
+    #[test]
+    fn test_trigger_to_level() {
+        assert_eq!(trigger_to_level(Trigger::RisingEdge), Some(0));
+        assert_eq!(trigger_to_level(Trigger::FallingEdge), Some(1));
+        assert_eq!(trigger_to_level(Trigger::Both), None);
+        assert_eq!(trigger_to_level(Trigger::Disabled), None);
+    }
+
+    #[test]
+    fn test_trigger_to_pressed() {
+        assert_eq!(trigger_to_pressed(Trigger::FallingEdge, true), Some(true));
+        assert_eq!(trigger_to_pressed(Trigger::RisingEdge, true), Some(false));
+        assert_eq!(trigger_to_pressed(Trigger::FallingEdge, false), Some(false));
+        assert_eq!(trigger_to_pressed(Trigger::RisingEdge, false), Some(true));
+        assert_eq!(trigger_to_pressed(Trigger::Disabled, true), None);
+    }
+}
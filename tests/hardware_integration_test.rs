@@ -32,10 +32,12 @@
 //! sudo usermod -a -G gpio $USER
 //! ```
 use rotary_switch_helper::rotary_encoder;
-use rotary_switch_helper::rotary_encoder::Direction;
+use rotary_switch_helper::rotary_encoder::{ClkPin, Direction, DtPin, SwPin};
 use rotary_switch_helper::switch_encoder;
+use rotary_switch_helper::{EncoderError, PiInput, RotaryDefinition, SwitchDefinition};
 use rppal::gpio::Gpio;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::thread;
 use std::time::Duration;
 use test_log::test;
@@ -44,8 +46,8 @@ use test_log::test;
 static CALLBACK_LOG: Mutex<Vec<(String, rotary_encoder::Direction)>> = Mutex::new(Vec::new());
 static CALLBACK_SW_LOG: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
 
-const DT_PIN_NUMBER: u8 = 9;
-const CLK_PIN_NUMBER: u8 = 10;
+const DT_PIN: DtPin = DtPin(9);
+const CLK_PIN: ClkPin = ClkPin(10);
 const SW_PIN_NUMBER: u8 = 11;
 
 /// Test callback function that logs all invocations
@@ -127,9 +129,9 @@ fn test_rotary_encoder_initialization() {
         "test_encoder",
         None,
         &gpio,
-        DT_PIN_NUMBER,  // DT pin
-        CLK_PIN_NUMBER, // CLK pin
-        None,           // No switch pin
+        DT_PIN,  // DT pin
+        CLK_PIN, // CLK pin
+        None,    // No switch pin
         test_callback,
     );
 
@@ -141,6 +143,33 @@ fn test_rotary_encoder_initialization() {
     wait_for_gpio_cleanup();
 }
 
+#[test]
+#[ignore]
+fn test_rotary_from_existing_pins() {
+    println!("\n=== Testing Encoder on Pre-configured Pins ===");
+    println!("Please turn the encoder in any direction within 10 seconds...");
+
+    clear_log();
+
+    let gpio = Gpio::new().expect("Failed to initialize GPIO");
+    let dt = gpio.get(DT_PIN.0).expect("DT pin").into_input_pullup();
+    let clk = gpio.get(CLK_PIN.0).expect("CLK pin").into_input_pullup();
+    let encoder =
+        rotary_encoder::Encoder::from_pins("existing_pins", None, dt, clk, None, test_callback)
+            .expect("Failed to create encoder");
+    assert_eq!(encoder.pins().dt, DT_PIN.0);
+    assert_eq!(encoder.pins().clk, CLK_PIN.0);
+
+    thread::sleep(Duration::from_secs(10));
+    assert!(
+        get_callback_count() > 0,
+        "Expected at least one detent on the pre-configured pins"
+    );
+    println!("✓ Encoder reports detents on pre-configured pins");
+    drop(encoder);
+    wait_for_gpio_cleanup();
+}
+
 #[test]
 #[ignore]
 fn test_rotary_clockwise_turns() {
@@ -155,8 +184,8 @@ fn test_rotary_clockwise_turns() {
         "clockwise_test",
         None,
         &gpio,
-        DT_PIN_NUMBER,
-        CLK_PIN_NUMBER,
+        DT_PIN,
+        CLK_PIN,
         None,
         test_callback,
     )
@@ -204,8 +233,8 @@ fn test_rotary_counterclockwise_turns() {
         "counterclockwise_test",
         None,
         &gpio,
-        DT_PIN_NUMBER,
-        CLK_PIN_NUMBER,
+        DT_PIN,
+        CLK_PIN,
         None,
         test_callback,
     )
@@ -253,8 +282,8 @@ fn test_rotary_both_directions() {
         "bidirectional_test",
         None,
         &gpio,
-        DT_PIN_NUMBER,
-        CLK_PIN_NUMBER,
+        DT_PIN,
+        CLK_PIN,
         None,
         test_callback,
     )
@@ -312,9 +341,9 @@ fn test_rotary_with_shifted_name() {
         "normal_name",
         Some("shifted_name"),
         &gpio,
-        DT_PIN_NUMBER,
-        CLK_PIN_NUMBER,
-        Some(SW_PIN_NUMBER), // Switch pin
+        DT_PIN,
+        CLK_PIN,
+        Some(SwPin(SW_PIN_NUMBER)), // Switch pin
         test_callback,
     )
     .expect("Failed to create encoder with shift support");
@@ -350,8 +379,8 @@ fn test_rotary_rapid_turns() {
         "rapid_test",
         None,
         &gpio,
-        DT_PIN_NUMBER,
-        CLK_PIN_NUMBER,
+        DT_PIN,
+        CLK_PIN,
         None,
         test_callback,
     )
@@ -382,6 +411,66 @@ fn test_rotary_rapid_turns() {
     wait_for_gpio_cleanup();
 }
 
+static REJECTED_TRANSITIONS: AtomicU32 = AtomicU32::new(0);
+
+fn count_rejected(_name: &str, _error: anyhow::Error) {
+    REJECTED_TRANSITIONS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+#[ignore]
+fn test_rotary_debounce_reduces_rejections() {
+    println!("\n=== Testing DT/CLK Debounce ===");
+    println!("Please turn the encoder CLOCKWISE 10 times in each of the two rounds...");
+    println!("You have 10 seconds per round.");
+
+    let gpio = Gpio::new().expect("Failed to initialize GPIO");
+    let mut results = Vec::new();
+    for debounce in [None, Some(Duration::from_millis(3))] {
+        clear_log();
+        REJECTED_TRANSITIONS.store(0, Ordering::SeqCst);
+
+        let mut builder = rotary_encoder::Encoder::builder()
+            .name("debounce_test")
+            .dt_pin(DT_PIN)
+            .clk_pin(CLK_PIN)
+            .callback(test_callback)
+            .on_error(count_rejected);
+        if let Some(debounce) = debounce {
+            builder = builder.debounce(debounce);
+        }
+        let encoder = builder.build(&gpio).expect("Failed to create encoder");
+
+        println!("\n>>> START TURNING NOW (debounce {:?}) <<<\n", debounce);
+        thread::sleep(Duration::from_secs(10));
+        drop(encoder);
+        wait_for_gpio_cleanup();
+
+        let rejected = REJECTED_TRANSITIONS.load(Ordering::SeqCst);
+        println!(
+            "debounce {:?}: {} detents, {} rejected transitions",
+            debounce,
+            get_callback_count(),
+            rejected
+        );
+        results.push((get_callback_count(), rejected));
+    }
+
+    let (plain, debounced) = (results[0], results[1]);
+    assert!(
+        debounced.0 >= 10,
+        "Debouncing must not lose detents, got {}",
+        debounced.0
+    );
+    assert!(
+        debounced.1 <= plain.1,
+        "Expected debouncing to reduce rejected transitions ({} without, {} with)",
+        plain.1,
+        debounced.1
+    );
+    println!("✓ Debounce reduces contact bounce without losing detents");
+}
+
 #[test]
 #[ignore]
 fn test_switch_press() {
@@ -416,7 +505,7 @@ fn test_switch_press() {
 
     let callbacks = get_callbacks_switch();
     assert!(
-        callbacks.get(0).unwrap().1,
+        callbacks.first().unwrap().1,
         "Expected first callback to be a press not release event"
     );
 
@@ -468,3 +557,86 @@ fn test_switch_long_press() {
     println!("✓ Presses handled successfully");
     wait_for_gpio_cleanup();
 }
+
+#[cfg(feature = "async")]
+#[test]
+#[ignore]
+fn test_rotary_async_channel() {
+    println!("\n=== Testing Async Channel ===");
+    println!("Please turn the encoder in BOTH directions when prompted...");
+    println!("You have 10 seconds.");
+
+    let gpio = Gpio::new().expect("Failed to initialize GPIO");
+    let (_encoder, mut receiver) =
+        rotary_encoder::Encoder::spawn("async_test", None, &gpio, DT_PIN, CLK_PIN, None, 64)
+            .expect("Failed to create encoder");
+
+    println!("\n>>> START TURNING IN BOTH DIRECTIONS NOW <<<\n");
+    thread::sleep(Duration::from_secs(10));
+
+    let mut events = Vec::new();
+    while let Ok(event) = receiver.try_recv() {
+        events.push(event);
+    }
+
+    println!("\n--- Results ---");
+    for (i, event) in events.iter().enumerate() {
+        println!("  {}. {:?}", i + 1, event);
+    }
+
+    assert!(!events.is_empty(), "Expected events on the channel");
+    println!("✓ Async channel delivered events successfully");
+    wait_for_gpio_cleanup();
+}
+
+#[test]
+#[ignore]
+fn test_pi_input_releases_pins_on_failure() {
+    println!("\n=== Testing PiInput Cleanup After a Failed Definition ===");
+
+    let gpio = Gpio::new().expect("Failed to initialize GPIO");
+    // hold the switch pin, so the switch fails after the rotary claimed its pins
+    let blocker = gpio.get(SW_PIN_NUMBER).expect("Failed to claim switch pin");
+    let switches = [SwitchDefinition {
+        name: "blocked".to_owned(),
+        name_long_press: None,
+        sw_pin: SW_PIN_NUMBER,
+        active_low: true,
+        callback: test_callback_switch,
+        on_release: None,
+        time_threshold: None,
+        debounce: None,
+        min_press: None,
+        mode: switch_encoder::SwitchMode::Momentary,
+        log_target: None,
+    }];
+    let rotaries = [RotaryDefinition {
+        name: "rollback".to_owned(),
+        name_shifted: None,
+        sw_pin: None,
+        dt_pin: DT_PIN,
+        clk_pin: CLK_PIN,
+        callback: |_, _, _| {},
+        on_error: None,
+        on_detent: None,
+        reversed: false,
+        debounce: None,
+        log_target: None,
+    }];
+
+    let result = PiInput::with_gpio(&gpio, &switches, &rotaries);
+    assert!(
+        matches!(result, Err(EncoderError::PinInUse(pin)) if pin == SW_PIN_NUMBER),
+        "Creating the switch on a claimed pin should fail"
+    );
+    drop(blocker);
+
+    for pin in [DT_PIN.0, CLK_PIN.0, SW_PIN_NUMBER] {
+        assert!(
+            gpio.get(pin).is_ok(),
+            "GPIO {pin} is still claimed after the failure"
+        );
+    }
+    println!("✓ All pins were released after the failed definition");
+    wait_for_gpio_cleanup();
+}